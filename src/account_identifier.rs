@@ -1,23 +1,25 @@
 use candid::{CandidType, Principal};
-use serde::{de, de::Error, Deserialize, Serialize};
+use serde::{de, Deserialize, Serialize};
 use std::{
     convert::{TryFrom, TryInto},
     fmt::{Display, Formatter},
     str::FromStr,
 };
 
-use ic_ledger_types::AccountIdentifier;
+use ic_ledger_types::{AccountIdentifier, Subaccount, DEFAULT_SUBACCOUNT};
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Copy)]
 pub struct ShikuAccountIdentifier(pub AccountIdentifier);
 
 impl Default for ShikuAccountIdentifier {
     fn default() -> Self {
-        Self(AccountIdentifier::new(&Principal::anonymous(), &ic_ledger_types::Subaccount([0u8;32])))
+        Self::new(&Principal::anonymous(), &DEFAULT_SUBACCOUNT)
     }
 }
 
 impl ShikuAccountIdentifier {
-
+    pub fn new(owner: &Principal, subaccount: &Subaccount) -> ShikuAccountIdentifier {
+        ShikuAccountIdentifier(AccountIdentifier::new(owner, subaccount))
+    }
 
     pub fn from_hex(hex_str: &str) -> Result<ShikuAccountIdentifier, String> {
         let hex: Vec<u8> = hex::decode(hex_str).map_err(|e| e.to_string())?;
@@ -47,22 +49,45 @@ impl ShikuAccountIdentifier {
                 // It's a 32-byte blob. Validate the checksum.
                 check_sum(h).map_err(AccountIdParseError::InvalidChecksum)
             }
-            Err(_) => Err(AccountIdParseError::InvalidLength(v.to_vec())), 
+            Err(_) => match v.try_into() {
+                // Try parsing it as a 28-byte hash.
+                Ok(hash) => Ok(Self::from_hash(hash)),
+                Err(_) => Err(AccountIdParseError::InvalidLength(v.to_vec())),
+            },
         }
     }
 
+    /// Builds the account identifier of a 28-byte hash, prefixing its checksum.
+    pub fn from_hash(hash: [u8; 28]) -> ShikuAccountIdentifier {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&hash);
+
+        let mut address = [0u8; 32];
+        address[0..4].copy_from_slice(&hasher.finalize().to_be_bytes());
+        address[4..32].copy_from_slice(&hash);
+        // The checksum was computed from the hash just above.
+        ShikuAccountIdentifier(AccountIdentifier::try_from(address).expect("valid checksum"))
+    }
+
     pub fn to_hex(&self) -> String {
         let data = self.0;
         //hex::encode(data.as_ref().to_vec())
         data.to_string()
     }
 
+    /// Returns the 28-byte hash, without the checksum.
+    pub fn hash(&self) -> [u8; 28] {
+        let mut hash = [0u8; 28];
+        hash.copy_from_slice(&self.0.as_ref()[4..32]);
+        hash
+    }
+
     /// Converts this account identifier into a binary "address".
     /// The address is CRC32(identifier) . identifier.
     pub fn to_address(&self) -> [u8; 32] {
         let mut result = [0u8; 32];
         result[0..4].copy_from_slice(&self.generate_checksum());
-        result[4..32].copy_from_slice(&[0u8]);
+        result[4..32].copy_from_slice(&self.hash());
         result
     }
 
@@ -105,28 +130,167 @@ impl Serialize for ShikuAccountIdentifier {
 }
 
 impl<'de> Deserialize<'de> for ShikuAccountIdentifier {
-    // This is the canonical way to read a this from string
+    // Accepts the hex text this serializes to, as well as the raw blob
+    // (32-byte address or 28-byte hash) for formats that carry bytes.
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
         D::Error: de::Error,
     {
-        let hex: [u8; 32] = hex::serde::deserialize(deserializer)?;
-        check_sum(hex).map_err(D::Error::custom)
+        deserializer.deserialize_any(AccountIdentifierVisitor)
     }
 }
 
+struct AccountIdentifierVisitor;
+
+impl<'de> de::Visitor<'de> for AccountIdentifierVisitor {
+    type Value = ShikuAccountIdentifier;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "an account identifier as hex text or as a 32-byte blob")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        ShikuAccountIdentifier::from_hex(v)
+            .map_err(|err| E::custom(format!("invalid account identifier: {}", err)))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        ShikuAccountIdentifier::from_slice(v)
+            .map_err(|err| E::custom(format!("invalid account identifier: {}", err)))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(32));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+impl From<Account> for ShikuAccountIdentifier {
+    fn from(account: Account) -> Self {
+        Self::new(&account.owner, &account.subaccount.unwrap_or(DEFAULT_SUBACCOUNT))
+    }
+}
+
+impl From<&Account> for ShikuAccountIdentifier {
+    fn from(account: &Account) -> Self {
+        Self::new(&account.owner, &account.subaccount.unwrap_or(DEFAULT_SUBACCOUNT))
+    }
+}
+
+/// An ICRC-1 account.
+#[derive(Debug, Clone, Copy, CandidType, Deserialize, Serialize)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Subaccount>,
+}
+
+impl Account {
+    /// The subaccount, or `None` for the default (all-zero) subaccount.
+    fn effective_subaccount(&self) -> Option<&Subaccount> {
+        self.subaccount.as_ref().filter(|sub| **sub != DEFAULT_SUBACCOUNT)
+    }
+
+    /// CRC32 of owner and subaccount, base32 encoded as the ICRC-1 textual
+    /// format expects.
+    fn text_checksum(owner: &Principal, subaccount: &Subaccount) -> String {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(owner.as_slice());
+        hasher.update(&subaccount.0);
+        base32(&hasher.finalize().to_be_bytes())
+    }
+}
+
+impl PartialEq for Account {
+    fn eq(&self, other: &Self) -> bool {
+        self.owner == other.owner && self.effective_subaccount() == other.effective_subaccount()
+    }
+}
+
+impl Eq for Account {}
+
+impl Display for Account {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.effective_subaccount() {
+            None => write!(f, "{}", self.owner),
+            Some(subaccount) => write!(
+                f,
+                "{}-{}.{}",
+                self.owner,
+                Self::text_checksum(&self.owner, subaccount),
+                hex::encode(subaccount.0).trim_start_matches('0'),
+            ),
+        }
+    }
+}
+
+impl FromStr for Account {
+    type Err = String;
+
+    /// Parses the ICRC-1 textual format `principal[-checksum.subaccount]`.
+    fn from_str(s: &str) -> Result<Account, String> {
+        let (owner_and_checksum, subaccount_hex) = match s.rsplit_once('.') {
+            None => {
+                let owner = Principal::from_text(s).map_err(|e| e.to_string())?;
+                return Ok(Account { owner, subaccount: None });
+            }
+            Some(parts) => parts,
+        };
+        let (owner, checksum) = owner_and_checksum
+            .rsplit_once('-')
+            .ok_or_else(|| format!("{} is missing the account checksum", s))?;
+        let owner = Principal::from_text(owner).map_err(|e| e.to_string())?;
+
+        if subaccount_hex.is_empty() || subaccount_hex.starts_with('0') || subaccount_hex.len() > 64 {
+            return Err(format!("{} has a non-canonical subaccount", s));
+        }
+        let mut subaccount = [0u8; 32];
+        hex::decode_to_slice(format!("{:0>64}", subaccount_hex), &mut subaccount)
+            .map_err(|e| e.to_string())?;
+        let subaccount = Subaccount(subaccount);
+
+        let expected_checksum = Self::text_checksum(&owner, &subaccount);
+        if checksum != expected_checksum {
+            return Err(format!(
+                "Checksum failed for {}, expected {} but found {}",
+                s, expected_checksum, checksum
+            ));
+        }
+        Ok(Account { owner, subaccount: Some(subaccount) })
+    }
+}
+
+/// Lowercase RFC 4648 base32 without padding.
+fn base32(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut output = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in input {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    output
+}
 
 fn check_sum(hex: [u8; 32]) -> Result<ShikuAccountIdentifier, ChecksumError> {
     // Get the checksum provided
     let found_checksum = &hex[0..4];
 
     // Copy the hash into a new array
-    //let mut hash = [0; 28];
-    //hash.copy_from_slice(&hex[4..32]);
-    let shiku_aid = AccountIdentifier::try_from(hex).unwrap();
+    let mut hash = [0; 28];
+    hash.copy_from_slice(&hex[4..32]);
 
-    let account_id = ShikuAccountIdentifier(shiku_aid);
+    let account_id = ShikuAccountIdentifier::from_hash(hash);
 
     let expected_checksum = account_id.generate_checksum();
 
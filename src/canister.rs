@@ -0,0 +1,1021 @@
+//! Canister entry points.
+//!
+//! `export_canister!()` exports every DIP721, EXT, ICRC-3, ICRC-7 and ICRC-37
+//! endpoint of this crate, plus the `init`, `pre_upgrade` and `post_upgrade`
+//! hooks, from the crate it is invoked in. Endpoints are named after their
+//! Candid methods and can be left out with `disable`:
+//!
+//! ```ignore
+//! ext_based_dip721_lib::export_canister!(disable: [dip721_set_minter, getTokens]);
+//! ```
+//!
+//! Naming anything that is not one of these endpoints fails the build.
+//!
+//! The invoking crate needs `ic-cdk` as a dependency. Building this crate with
+//! the `canister` feature for `wasm32` exports the full canister from the
+//! library itself.
+
+#[macro_export]
+macro_rules! export_canister {
+    () => {
+        $crate::export_canister!(disable: []);
+    };
+    (disable: [$($off:ident),* $(,)?]) => {
+        $crate::__export_canister_endpoints!(
+            [$($off)*]
+            [dip721_total_supply dip721_balance_of dip721_transfer_from dip721_transfer_batch
+            dip721_custodians dip721_set_paused dip721_is_paused dip721_freeze_metadata
+            dip721_is_metadata_frozen dip721_set_name dip721_set_logo dip721_set_symbol
+            dip721_mint_policy dip721_set_mint_policy dip721_set_locked dip721_is_locked dip721_change_status dip721_status_of
+            dip721_tokens_with_status
+            dip721_mint dip721_mint_batch dip721_burn dip721_approve dip721_get_registry dip721_token_metadata
+            dip721_owner_token_identifiers dip721_operator_token_identifiers dip721_owner_of
+            dip721_owner_of_batch dip721_token_metadata_batch
+            dip721_allowance dip721_token_identitfier_operator dip721_owner_counts
+            dip721_set_minter dip721_minted_info dip721_history dip721_owner_of_certified
+            dip721_balance_of_certified dip721_set_class dip721_class_supply dip721_class_balance_of
+            dip721_operator_of dip721_owner_token_metadata dip721_operator_token_metadata
+            dip721_set_custodians dip721_metadata dip721_name dip721_logo dip721_symbol
+            dip721_total_unique_holders dip721_total_transactions dip721_transaction dip721_cycles
+            dip721_stats dip721_supported_interfaces dip721_transfer dip721_set_approval_for_all
+            dip721_is_approved_for_all
+            extensions balance bearer metadata supply getRegistry getTokens tokens tokens_ext
+            transfer batch_transfer transfer_range allowance approve
+            icrc7_collection_metadata icrc7_symbol icrc7_name icrc7_description icrc7_logo
+            icrc7_total_supply icrc7_supply_cap icrc7_max_query_batch_size
+            icrc7_max_update_batch_size icrc7_default_take_value icrc7_max_take_value
+            icrc7_max_memo_size icrc7_atomic_batch_transfers icrc7_tx_window
+            icrc7_permitted_drift icrc7_token_metadata icrc7_owner_of icrc7_balance_of
+            icrc7_tokens icrc7_tokens_of icrc7_transfer icrc10_supported_standards
+            icrc37_metadata icrc37_max_approvals_per_token_or_collection
+            icrc37_max_revoke_approvals icrc37_approve_tokens icrc37_approve_collection
+            icrc37_revoke_token_approvals icrc37_revoke_collection_approvals
+            icrc37_is_approved icrc37_get_token_approvals icrc37_get_collection_approvals
+            icrc37_transfer_from icrc3_get_blocks icrc3_get_archives icrc3_get_tip_certificate
+            icrc3_supported_block_types]
+        );
+
+        #[$crate::ic_cdk_macros::init]
+        fn __canister_init(args: Option<$crate::InitArgs>) {
+            $crate::dip721_init(args);
+        }
+
+        #[$crate::ic_cdk_macros::pre_upgrade]
+        fn __canister_pre_upgrade() {
+            $crate::dip721_pre_upgrade();
+        }
+
+        #[$crate::ic_cdk_macros::post_upgrade]
+        fn __canister_post_upgrade() {
+            $crate::dip721_post_upgrade();
+        }
+
+        #[$crate::ic_cdk_macros::query(name = "__get_candid_interface_tmp_hack")]
+        fn __canister_candid_interface() -> String {
+            $crate::candid_interface_excluding(&[$(stringify!($off)),*])
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __export_canister_endpoints {
+    (@known [$($off:ident)*] $names:tt) => {
+        $($crate::__export_canister_endpoint!(@check $off $names strict);)*
+    };
+    (@export $off:tt [$($name:ident)*]) => {
+        $($crate::__export_canister_endpoint!(@check $name $off);)*
+    };
+    ($off:tt $names:tt) => {
+        $crate::__export_canister_endpoints!(@known $off $names);
+        $crate::__export_canister_endpoints!(@export $off $names);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __export_canister_endpoint {
+    (@check dip721_total_supply [dip721_total_supply $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_balance_of [dip721_balance_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_transfer_from [dip721_transfer_from $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_transfer_batch [dip721_transfer_batch $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_custodians [dip721_custodians $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_paused [dip721_set_paused $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_is_paused [dip721_is_paused $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_freeze_metadata [dip721_freeze_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_is_metadata_frozen [dip721_is_metadata_frozen $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_name [dip721_set_name $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_logo [dip721_set_logo $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_symbol [dip721_set_symbol $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_locked [dip721_set_locked $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_is_locked [dip721_is_locked $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_mint_policy [dip721_mint_policy $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_mint_policy [dip721_set_mint_policy $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_change_status [dip721_change_status $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_status_of [dip721_status_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_tokens_with_status [dip721_tokens_with_status $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_mint [dip721_mint $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_mint_batch [dip721_mint_batch $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_burn [dip721_burn $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_approve [dip721_approve $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_get_registry [dip721_get_registry $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_token_metadata [dip721_token_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_owner_token_identifiers [dip721_owner_token_identifiers $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_operator_token_identifiers [dip721_operator_token_identifiers $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_owner_of [dip721_owner_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_owner_of_batch [dip721_owner_of_batch $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_token_metadata_batch [dip721_token_metadata_batch $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_owner_of_certified [dip721_owner_of_certified $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_balance_of_certified [dip721_balance_of_certified $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_allowance [dip721_allowance $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_token_identitfier_operator [dip721_token_identitfier_operator $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_owner_counts [dip721_owner_counts $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_minter [dip721_set_minter $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_minted_info [dip721_minted_info $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_history [dip721_history $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_class [dip721_set_class $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_class_supply [dip721_class_supply $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_class_balance_of [dip721_class_balance_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_operator_of [dip721_operator_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_owner_token_metadata [dip721_owner_token_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_operator_token_metadata [dip721_operator_token_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_custodians [dip721_set_custodians $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_metadata [dip721_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_name [dip721_name $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_logo [dip721_logo $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_symbol [dip721_symbol $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_total_unique_holders [dip721_total_unique_holders $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_total_transactions [dip721_total_transactions $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_transaction [dip721_transaction $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_cycles [dip721_cycles $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_stats [dip721_stats $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_supported_interfaces [dip721_supported_interfaces $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_transfer [dip721_transfer $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_set_approval_for_all [dip721_set_approval_for_all $($rest:ident)*] $($strict:ident)?) => {};
+    (@check dip721_is_approved_for_all [dip721_is_approved_for_all $($rest:ident)*] $($strict:ident)?) => {};
+    (@check extensions [extensions $($rest:ident)*] $($strict:ident)?) => {};
+    (@check balance [balance $($rest:ident)*] $($strict:ident)?) => {};
+    (@check bearer [bearer $($rest:ident)*] $($strict:ident)?) => {};
+    (@check metadata [metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check supply [supply $($rest:ident)*] $($strict:ident)?) => {};
+    (@check getRegistry [getRegistry $($rest:ident)*] $($strict:ident)?) => {};
+    (@check getTokens [getTokens $($rest:ident)*] $($strict:ident)?) => {};
+    (@check tokens [tokens $($rest:ident)*] $($strict:ident)?) => {};
+    (@check tokens_ext [tokens_ext $($rest:ident)*] $($strict:ident)?) => {};
+    (@check transfer [transfer $($rest:ident)*] $($strict:ident)?) => {};
+    (@check batch_transfer [batch_transfer $($rest:ident)*] $($strict:ident)?) => {};
+    (@check transfer_range [transfer_range $($rest:ident)*] $($strict:ident)?) => {};
+    (@check allowance [allowance $($rest:ident)*] $($strict:ident)?) => {};
+    (@check approve [approve $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_collection_metadata [icrc7_collection_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_symbol [icrc7_symbol $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_name [icrc7_name $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_description [icrc7_description $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_logo [icrc7_logo $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_total_supply [icrc7_total_supply $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_supply_cap [icrc7_supply_cap $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_max_query_batch_size [icrc7_max_query_batch_size $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_max_update_batch_size [icrc7_max_update_batch_size $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_default_take_value [icrc7_default_take_value $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_max_take_value [icrc7_max_take_value $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_max_memo_size [icrc7_max_memo_size $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_atomic_batch_transfers [icrc7_atomic_batch_transfers $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_tx_window [icrc7_tx_window $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_permitted_drift [icrc7_permitted_drift $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_token_metadata [icrc7_token_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_owner_of [icrc7_owner_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_balance_of [icrc7_balance_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_tokens [icrc7_tokens $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_tokens_of [icrc7_tokens_of $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc7_transfer [icrc7_transfer $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc10_supported_standards [icrc10_supported_standards $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_metadata [icrc37_metadata $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_max_approvals_per_token_or_collection [icrc37_max_approvals_per_token_or_collection $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_max_revoke_approvals [icrc37_max_revoke_approvals $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_approve_tokens [icrc37_approve_tokens $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_approve_collection [icrc37_approve_collection $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_revoke_token_approvals [icrc37_revoke_token_approvals $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_revoke_collection_approvals [icrc37_revoke_collection_approvals $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_is_approved [icrc37_is_approved $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_get_token_approvals [icrc37_get_token_approvals $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_get_collection_approvals [icrc37_get_collection_approvals $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc37_transfer_from [icrc37_transfer_from $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc3_get_blocks [icrc3_get_blocks $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc3_get_archives [icrc3_get_archives $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc3_get_tip_certificate [icrc3_get_tip_certificate $($rest:ident)*] $($strict:ident)?) => {};
+    (@check icrc3_supported_block_types [icrc3_supported_block_types $($rest:ident)*] $($strict:ident)?) => {};
+    (@check $name:ident [$head:ident $($rest:ident)*] $($strict:ident)?) => {
+        $crate::__export_canister_endpoint!(@check $name [$($rest)*] $($strict)?);
+    };
+    (@check $name:ident []) => {
+        $crate::__export_canister_endpoint!(@emit $name);
+    };
+    (@check $name:ident [] strict) => {
+        compile_error!(concat!("`", stringify!($name), "` in `disable` is not an endpoint of export_canister!"));
+    };
+
+    (@emit dip721_total_supply) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_total_supply")]
+        fn __canister_dip721_total_supply() -> $crate::ic_cdk::export::candid::Nat {
+            $crate::dip721_total_supply()
+        }
+    };
+    (@emit dip721_balance_of) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_balance_of")]
+        fn __canister_dip721_balance_of(
+            owner: $crate::AccountIdentifier_shiku,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_balance_of(owner)
+        }
+    };
+    (@emit dip721_transfer_from) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_transfer_from")]
+        fn __canister_dip721_transfer_from(
+            owner: $crate::AccountIdentifier_shiku,
+            to: $crate::AccountIdentifier_shiku,
+            token_identifier: $crate::TokenId,
+            created_at_time: Option<u64>,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_transfer_from(owner, to, token_identifier, created_at_time)
+        }
+    };
+    (@emit dip721_transfer_batch) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_transfer_batch")]
+        fn __canister_dip721_transfer_batch(
+            owner: $crate::AccountIdentifier_shiku,
+            transfers: Vec<($crate::AccountIdentifier_shiku, $crate::TokenId)>,
+        ) -> Result<Vec<$crate::ic_cdk::export::candid::Nat>, $crate::NftError> {
+            $crate::dip721_transfer_batch(owner, transfers)
+        }
+    };
+    (@emit dip721_custodians) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_custodians")]
+        fn __canister_dip721_custodians() -> std::collections::HashSet<$crate::ic_cdk::export::Principal> {
+            $crate::dip721_custodians()
+        }
+    };
+    (@emit dip721_set_paused) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_paused")]
+        fn __canister_dip721_set_paused(paused: bool) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_paused(paused)
+        }
+    };
+    (@emit dip721_is_paused) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_is_paused")]
+        fn __canister_dip721_is_paused() -> bool {
+            $crate::dip721_is_paused()
+        }
+    };
+    (@emit dip721_freeze_metadata) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_freeze_metadata")]
+        fn __canister_dip721_freeze_metadata() -> Result<(), $crate::NftError> {
+            $crate::dip721_freeze_metadata()
+        }
+    };
+    (@emit dip721_is_metadata_frozen) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_is_metadata_frozen")]
+        fn __canister_dip721_is_metadata_frozen() -> bool {
+            $crate::dip721_is_metadata_frozen()
+        }
+    };
+    (@emit dip721_set_name) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_name")]
+        fn __canister_dip721_set_name(name: String) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_name(name)
+        }
+    };
+    (@emit dip721_set_logo) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_logo")]
+        fn __canister_dip721_set_logo(logo: String) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_logo(logo)
+        }
+    };
+    (@emit dip721_set_symbol) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_symbol")]
+        fn __canister_dip721_set_symbol(symbol: String) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_symbol(symbol)
+        }
+    };
+    (@emit dip721_set_locked) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_locked")]
+        fn __canister_dip721_set_locked(
+            token_identifier: $crate::TokenId,
+            locked: bool,
+        ) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_locked(token_identifier, locked)
+        }
+    };
+    (@emit dip721_is_locked) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_is_locked")]
+        fn __canister_dip721_is_locked(token_identifier: $crate::TokenId) -> bool {
+            $crate::dip721_is_locked(token_identifier)
+        }
+    };
+    (@emit dip721_mint_policy) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_mint_policy")]
+        fn __canister_dip721_mint_policy() -> $crate::MintPolicy {
+            $crate::dip721_mint_policy()
+        }
+    };
+    (@emit dip721_set_mint_policy) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_mint_policy")]
+        fn __canister_dip721_set_mint_policy(
+            mint_policy: $crate::MintPolicy,
+        ) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_mint_policy(mint_policy)
+        }
+    };
+    (@emit dip721_change_status) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_change_status")]
+        fn __canister_dip721_change_status(
+            token_identifier: $crate::TokenId,
+            action: $crate::TokenAction,
+        ) -> Result<$crate::TokenStatus, $crate::NftError> {
+            $crate::dip721_change_status(token_identifier, action)
+        }
+    };
+    (@emit dip721_status_of) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_status_of")]
+        fn __canister_dip721_status_of(
+            token_identifier: $crate::TokenId,
+        ) -> Result<$crate::TokenStatus, $crate::NftError> {
+            $crate::dip721_status_of(token_identifier)
+        }
+    };
+    (@emit dip721_tokens_with_status) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_tokens_with_status")]
+        fn __canister_dip721_tokens_with_status(
+            status: $crate::TokenStatus,
+            prev: Option<$crate::TokenId>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::TokenId> {
+            $crate::dip721_tokens_with_status(status, prev, take)
+        }
+    };
+    (@emit dip721_mint) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_mint")]
+        fn __canister_dip721_mint(
+            to: $crate::AccountIdentifier_shiku,
+            token_identifier: $crate::TokenId,
+            created_at_time: Option<u64>,
+            soulbound: Option<bool>,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_mint(to, &token_identifier, created_at_time, soulbound)
+        }
+    };
+    (@emit dip721_mint_batch) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_mint_batch")]
+        fn __canister_dip721_mint_batch(
+            items: Vec<$crate::MintBatchItem>,
+        ) -> Result<Vec<$crate::ic_cdk::export::candid::Nat>, $crate::NftError> {
+            $crate::dip721_mint_batch(items)
+        }
+    };
+    (@emit dip721_burn) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_burn")]
+        fn __canister_dip721_burn(
+            token_identifier: $crate::TokenId,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_burn(token_identifier)
+        }
+    };
+    (@emit dip721_approve) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_approve")]
+        fn __canister_dip721_approve(
+            operator: $crate::AccountIdentifier_shiku,
+            token_identifier: $crate::TokenId,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_approve(operator, token_identifier)
+        }
+    };
+    (@emit dip721_get_registry) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_get_registry")]
+        fn __canister_dip721_get_registry(
+            prev: Option<$crate::TokenIndexU32>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<($crate::TokenIndexU32, $crate::AccountIdentifier__1)> {
+            $crate::dip721_get_registry(prev, take)
+        }
+    };
+    (@emit dip721_token_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_token_metadata")]
+        fn __canister_dip721_token_metadata(
+            token_identifier: $crate::TokenId,
+        ) -> Result<$crate::TokenMetadata, $crate::NftError> {
+            $crate::dip721_token_metadata(token_identifier)
+        }
+    };
+    (@emit dip721_owner_token_identifiers) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_owner_token_identifiers")]
+        fn __canister_dip721_owner_token_identifiers(
+            owner: $crate::AccountIdentifier_shiku,
+            prev: Option<$crate::TokenId>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Result<Vec<$crate::TokenId>, $crate::NftError> {
+            $crate::dip721_owner_token_identifiers(owner, prev, take)
+        }
+    };
+    (@emit dip721_operator_token_identifiers) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_operator_token_identifiers")]
+        fn __canister_dip721_operator_token_identifiers(
+            operator: $crate::AccountIdentifier_shiku,
+            prev: Option<$crate::TokenId>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Result<Vec<$crate::TokenId>, $crate::NftError> {
+            $crate::dip721_operator_token_identifiers(operator, prev, take)
+        }
+    };
+    (@emit dip721_owner_of) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_owner_of")]
+        fn __canister_dip721_owner_of(
+            token: $crate::TokenId,
+        ) -> Result<Option<$crate::AccountIdentifier_shiku>, $crate::NftError> {
+            $crate::dip721_owner_of(token)
+        }
+    };
+    (@emit dip721_owner_of_batch) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_owner_of_batch")]
+        fn __canister_dip721_owner_of_batch(
+            tokens: Vec<$crate::TokenId>,
+        ) -> Result<Vec<Result<Option<$crate::AccountIdentifier_shiku>, $crate::NftError>>, $crate::NftError> {
+            $crate::dip721_owner_of_batch(tokens)
+        }
+    };
+    (@emit dip721_token_metadata_batch) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_token_metadata_batch")]
+        fn __canister_dip721_token_metadata_batch(
+            tokens: Vec<$crate::TokenId>,
+        ) -> Result<Vec<Result<$crate::TokenMetadata, $crate::NftError>>, $crate::NftError> {
+            $crate::dip721_token_metadata_batch(tokens)
+        }
+    };
+    (@emit dip721_owner_of_certified) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_owner_of_certified")]
+        fn __canister_dip721_owner_of_certified(
+            token: $crate::TokenId,
+        ) -> Option<$crate::ICRC3DataCertificate> {
+            $crate::dip721_owner_of_certified(token)
+        }
+    };
+    (@emit dip721_balance_of_certified) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_balance_of_certified")]
+        fn __canister_dip721_balance_of_certified(
+            owner: $crate::AccountIdentifier_shiku,
+        ) -> Option<$crate::ICRC3DataCertificate> {
+            $crate::dip721_balance_of_certified(owner)
+        }
+    };
+    (@emit dip721_allowance) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_allowance")]
+        fn __canister_dip721_allowance(
+            owner: $crate::AccountIdentifier_shiku,
+            spender: $crate::AccountIdentifier_shiku,
+            token: $crate::TokenId,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::CommonError> {
+            $crate::dip721_allowance(&owner, &spender, &token)
+        }
+    };
+    (@emit dip721_token_identitfier_operator) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_token_identitfier_operator")]
+        fn __canister_dip721_token_identitfier_operator(
+            operator: $crate::AccountIdentifier_shiku,
+            prev: Option<$crate::TokenId>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Result<Vec<$crate::TokenId>, $crate::NftError> {
+            $crate::dip721_token_identitfier_operator(operator, prev, take)
+        }
+    };
+    (@emit dip721_owner_counts) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_owner_counts")]
+        fn __canister_dip721_owner_counts() -> usize {
+            $crate::dip721_owner_counts()
+        }
+    };
+    (@emit dip721_set_minter) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_minter")]
+        fn __canister_dip721_set_minter(
+            new_minter: $crate::ic_cdk::export::Principal,
+        ) -> Result<(), $crate::CommonError> {
+            $crate::dip721_set_minter(new_minter)
+        }
+    };
+    (@emit dip721_minted_info) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_minted_info")]
+        fn __canister_dip721_minted_info(
+            prev: Option<$crate::ic_cdk::export::candid::Nat>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::ic_cdk::export::candid::Nat> {
+            $crate::dip721_minted_info(prev, take)
+        }
+    };
+    (@emit dip721_history) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_history")]
+        fn __canister_dip721_history(
+            prev: Option<$crate::ic_cdk::export::candid::Nat>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::BlockWithId> {
+            $crate::dip721_history(prev, take)
+        }
+    };
+    (@emit dip721_set_class) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_class")]
+        fn __canister_dip721_set_class(
+            class: String,
+            token_identifiers: Vec<$crate::TokenId>,
+        ) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_class(class, token_identifiers)
+        }
+    };
+    (@emit dip721_class_supply) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_class_supply")]
+        fn __canister_dip721_class_supply(class: String) -> $crate::ic_cdk::export::candid::Nat {
+            $crate::dip721_class_supply(class)
+        }
+    };
+    (@emit dip721_class_balance_of) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_class_balance_of")]
+        fn __canister_dip721_class_balance_of(
+            class: String,
+            owner: $crate::AccountIdentifier_shiku,
+        ) -> $crate::ic_cdk::export::candid::Nat {
+            $crate::dip721_class_balance_of(class, owner)
+        }
+    };
+    (@emit dip721_operator_of) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_operator_of")]
+        fn __canister_dip721_operator_of(
+            token: $crate::TokenId,
+        ) -> Result<Option<$crate::AccountIdentifier_shiku>, $crate::NftError> {
+            $crate::dip721_operator_of(token)
+        }
+    };
+    (@emit dip721_owner_token_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_owner_token_metadata")]
+        fn __canister_dip721_owner_token_metadata(
+            owner: $crate::AccountIdentifier_shiku,
+            prev: Option<$crate::TokenId>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Result<Vec<$crate::TokenMetadata>, $crate::NftError> {
+            $crate::dip721_owner_token_metadata(owner, prev, take)
+        }
+    };
+    (@emit dip721_operator_token_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_operator_token_metadata")]
+        fn __canister_dip721_operator_token_metadata(
+            operator: $crate::AccountIdentifier_shiku,
+            prev: Option<$crate::TokenId>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Result<Vec<$crate::TokenMetadata>, $crate::NftError> {
+            $crate::dip721_operator_token_metadata(operator, prev, take)
+        }
+    };
+    (@emit dip721_set_custodians) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_custodians")]
+        fn __canister_dip721_set_custodians(
+            custodians: std::collections::HashSet<$crate::ic_cdk::export::Principal>,
+        ) -> Result<(), $crate::NftError> {
+            $crate::dip721_set_custodians(custodians)
+        }
+    };
+    (@emit dip721_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_metadata")]
+        fn __canister_dip721_metadata() -> $crate::MetaData {
+            $crate::dip721_metadata()
+        }
+    };
+    (@emit dip721_name) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_name")]
+        fn __canister_dip721_name() -> Option<String> {
+            $crate::dip721_name()
+        }
+    };
+    (@emit dip721_logo) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_logo")]
+        fn __canister_dip721_logo() -> Option<String> {
+            $crate::dip721_logo()
+        }
+    };
+    (@emit dip721_symbol) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_symbol")]
+        fn __canister_dip721_symbol() -> Option<String> {
+            $crate::dip721_symbol()
+        }
+    };
+    (@emit dip721_total_unique_holders) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_total_unique_holders")]
+        fn __canister_dip721_total_unique_holders() -> $crate::ic_cdk::export::candid::Nat {
+            $crate::dip721_total_unique_holders()
+        }
+    };
+    (@emit dip721_total_transactions) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_total_transactions")]
+        fn __canister_dip721_total_transactions() -> $crate::ic_cdk::export::candid::Nat {
+            $crate::dip721_total_transactions()
+        }
+    };
+    (@emit dip721_transaction) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_transaction")]
+        fn __canister_dip721_transaction(
+            index: $crate::ic_cdk::export::candid::Nat,
+        ) -> Result<$crate::TxEvent, $crate::NftError> {
+            $crate::dip721_transaction(index)
+        }
+    };
+    (@emit dip721_cycles) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_cycles")]
+        fn __canister_dip721_cycles() -> $crate::ic_cdk::export::candid::Nat {
+            $crate::dip721_cycles()
+        }
+    };
+    (@emit dip721_stats) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_stats")]
+        fn __canister_dip721_stats() -> $crate::Status {
+            $crate::dip721_stats()
+        }
+    };
+    (@emit dip721_supported_interfaces) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_supported_interfaces")]
+        fn __canister_dip721_supported_interfaces() -> Vec<$crate::SupportedInterface> {
+            $crate::dip721_supported_interfaces()
+        }
+    };
+    (@emit dip721_transfer) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_transfer")]
+        fn __canister_dip721_transfer(
+            to: $crate::AccountIdentifier_shiku,
+            token_identifier: $crate::TokenId,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_transfer(to, token_identifier)
+        }
+    };
+    (@emit dip721_set_approval_for_all) => {
+        #[$crate::ic_cdk_macros::update(name = "dip721_set_approval_for_all")]
+        fn __canister_dip721_set_approval_for_all(
+            operator: $crate::ic_cdk::export::Principal,
+            is_approved: bool,
+        ) -> Result<$crate::ic_cdk::export::candid::Nat, $crate::NftError> {
+            $crate::dip721_set_approval_for_all(operator, is_approved)
+        }
+    };
+    (@emit dip721_is_approved_for_all) => {
+        #[$crate::ic_cdk_macros::query(name = "dip721_is_approved_for_all")]
+        fn __canister_dip721_is_approved_for_all(
+            owner: $crate::ic_cdk::export::Principal,
+            operator: $crate::ic_cdk::export::Principal,
+        ) -> Result<bool, $crate::NftError> {
+            $crate::dip721_is_approved_for_all(owner, operator)
+        }
+    };
+    (@emit extensions) => {
+        #[$crate::ic_cdk_macros::query(name = "extensions")]
+        fn __canister_extensions() -> Vec<String> {
+            $crate::ext_extensions()
+        }
+    };
+    (@emit balance) => {
+        #[$crate::ic_cdk_macros::query(name = "balance")]
+        fn __canister_balance(request: $crate::BalanceRequest) -> $crate::BalanceResponse {
+            $crate::ext_balance(request)
+        }
+    };
+    (@emit bearer) => {
+        #[$crate::ic_cdk_macros::query(name = "bearer")]
+        fn __canister_bearer(token: $crate::TokenIdentifier__1) -> $crate::Result__1_1 {
+            $crate::ext_bearer(token)
+        }
+    };
+    (@emit metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "metadata")]
+        fn __canister_metadata(token: $crate::TokenIdentifier__1) -> $crate::Result__1 {
+            $crate::ext_metadata(token)
+        }
+    };
+    (@emit supply) => {
+        #[$crate::ic_cdk_macros::query(name = "supply")]
+        fn __canister_supply(token: $crate::TokenIdentifier__1) -> $crate::Result_2 {
+            $crate::ext_supply(token)
+        }
+    };
+    (@emit getRegistry) => {
+        #[$crate::ic_cdk_macros::query(name = "getRegistry")]
+        fn __canister_get_registry() -> Vec<($crate::TokenIndexU32, $crate::AccountIdentifier__1)> {
+            $crate::ext_get_registry()
+        }
+    };
+    (@emit getTokens) => {
+        #[$crate::ic_cdk_macros::query(name = "getTokens")]
+        fn __canister_get_tokens() -> Vec<($crate::TokenIndexU32, $crate::TokenMetaDataExt)> {
+            $crate::ext_get_tokens()
+        }
+    };
+    (@emit tokens) => {
+        #[$crate::ic_cdk_macros::query(name = "tokens")]
+        fn __canister_tokens(aid: $crate::AccountIdentifier) -> $crate::Result_1 {
+            $crate::ext_tokens(aid)
+        }
+    };
+    (@emit tokens_ext) => {
+        #[$crate::ic_cdk_macros::query(name = "tokens_ext")]
+        fn __canister_tokens_ext(aid: $crate::AccountIdentifier) -> $crate::NFTResult {
+            $crate::ext_tokens_ext(aid)
+        }
+    };
+    (@emit transfer) => {
+        #[$crate::ic_cdk_macros::update(name = "transfer")]
+        fn __canister_transfer(
+            request: $crate::TransferRequest,
+            created_at_time: Option<u64>,
+        ) -> $crate::TransferResponse {
+            $crate::ext_transfer(request, created_at_time)
+        }
+    };
+    (@emit batch_transfer) => {
+        #[$crate::ic_cdk_macros::update(name = "batch_transfer")]
+        fn __canister_batch_transfer(request: $crate::TransferRequestV2) -> $crate::TransferResponse {
+            $crate::ext_batch_transfer(request)
+        }
+    };
+    (@emit transfer_range) => {
+        #[$crate::ic_cdk_macros::update(name = "transfer_range")]
+        fn __canister_transfer_range(request: $crate::TransferRequestV1) -> $crate::TransferResponse {
+            $crate::ext_transfer_range(request)
+        }
+    };
+    (@emit allowance) => {
+        #[$crate::ic_cdk_macros::query(name = "allowance")]
+        fn __canister_allowance(request: $crate::AllowanceRequest) -> $crate::Result_2 {
+            $crate::ext_allowance(request)
+        }
+    };
+    (@emit approve) => {
+        #[$crate::ic_cdk_macros::update(name = "approve")]
+        fn __canister_approve(request: $crate::ApproveRequest) -> $crate::Result_2 {
+            match $crate::ext_approve(request) {
+                Ok(index) => $crate::Result_2::ok(index),
+                Err(e) => $crate::Result_2::err(e),
+            }
+        }
+    };
+    (@emit icrc7_collection_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_collection_metadata")]
+        fn __canister_icrc7_collection_metadata() -> Vec<(String, $crate::Value)> {
+            $crate::icrc7_collection_metadata()
+        }
+    };
+    (@emit icrc7_symbol) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_symbol")]
+        fn __canister_icrc7_symbol() -> String {
+            $crate::icrc7_symbol()
+        }
+    };
+    (@emit icrc7_name) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_name")]
+        fn __canister_icrc7_name() -> String {
+            $crate::icrc7_name()
+        }
+    };
+    (@emit icrc7_description) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_description")]
+        fn __canister_icrc7_description() -> Option<String> {
+            $crate::icrc7_description()
+        }
+    };
+    (@emit icrc7_logo) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_logo")]
+        fn __canister_icrc7_logo() -> Option<String> {
+            $crate::icrc7_logo()
+        }
+    };
+    (@emit icrc7_total_supply) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_total_supply")]
+        fn __canister_icrc7_total_supply() -> $crate::ic_cdk::export::candid::Nat {
+            $crate::icrc7_total_supply()
+        }
+    };
+    (@emit icrc7_supply_cap) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_supply_cap")]
+        fn __canister_icrc7_supply_cap() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_supply_cap()
+        }
+    };
+    (@emit icrc7_max_query_batch_size) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_max_query_batch_size")]
+        fn __canister_icrc7_max_query_batch_size() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_max_query_batch_size()
+        }
+    };
+    (@emit icrc7_max_update_batch_size) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_max_update_batch_size")]
+        fn __canister_icrc7_max_update_batch_size() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_max_update_batch_size()
+        }
+    };
+    (@emit icrc7_default_take_value) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_default_take_value")]
+        fn __canister_icrc7_default_take_value() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_default_take_value()
+        }
+    };
+    (@emit icrc7_max_take_value) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_max_take_value")]
+        fn __canister_icrc7_max_take_value() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_max_take_value()
+        }
+    };
+    (@emit icrc7_max_memo_size) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_max_memo_size")]
+        fn __canister_icrc7_max_memo_size() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_max_memo_size()
+        }
+    };
+    (@emit icrc7_atomic_batch_transfers) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_atomic_batch_transfers")]
+        fn __canister_icrc7_atomic_batch_transfers() -> Option<bool> {
+            $crate::icrc7_atomic_batch_transfers()
+        }
+    };
+    (@emit icrc7_tx_window) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_tx_window")]
+        fn __canister_icrc7_tx_window() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_tx_window()
+        }
+    };
+    (@emit icrc7_permitted_drift) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_permitted_drift")]
+        fn __canister_icrc7_permitted_drift() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_permitted_drift()
+        }
+    };
+    (@emit icrc10_supported_standards) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc10_supported_standards")]
+        fn __canister_icrc10_supported_standards() -> Vec<$crate::SupportedStandard> {
+            $crate::icrc10_supported_standards()
+        }
+    };
+    (@emit icrc7_token_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_token_metadata")]
+        fn __canister_icrc7_token_metadata(
+            token_ids: Vec<$crate::TokenId>,
+        ) -> Vec<Option<Vec<(String, $crate::Value)>>> {
+            $crate::icrc7_token_metadata(token_ids)
+        }
+    };
+    (@emit icrc7_owner_of) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_owner_of")]
+        fn __canister_icrc7_owner_of(token_ids: Vec<$crate::TokenId>) -> Vec<Option<$crate::Account>> {
+            $crate::icrc7_owner_of(token_ids)
+        }
+    };
+    (@emit icrc7_balance_of) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_balance_of")]
+        fn __canister_icrc7_balance_of(
+            accounts: Vec<$crate::Account>,
+        ) -> Vec<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc7_balance_of(accounts)
+        }
+    };
+    (@emit icrc7_tokens) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_tokens")]
+        fn __canister_icrc7_tokens(
+            prev: Option<$crate::ic_cdk::export::candid::Nat>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::TokenId> {
+            $crate::icrc7_tokens(prev, take)
+        }
+    };
+    (@emit icrc7_tokens_of) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc7_tokens_of")]
+        fn __canister_icrc7_tokens_of(
+            account: $crate::Account,
+            prev: Option<$crate::ic_cdk::export::candid::Nat>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::TokenId> {
+            $crate::icrc7_tokens_of(account, prev, take)
+        }
+    };
+    (@emit icrc7_transfer) => {
+        #[$crate::ic_cdk_macros::update(name = "icrc7_transfer")]
+        fn __canister_icrc7_transfer(
+            args: Vec<$crate::Icrc7TransferArg>,
+        ) -> Vec<Option<$crate::Icrc7TransferResult>> {
+            $crate::icrc7_transfer(args)
+        }
+    };
+    (@emit icrc37_metadata) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc37_metadata")]
+        fn __canister_icrc37_metadata() -> Vec<(String, $crate::Value)> {
+            $crate::icrc37_metadata()
+        }
+    };
+    (@emit icrc37_max_approvals_per_token_or_collection) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc37_max_approvals_per_token_or_collection")]
+        fn __canister_icrc37_max_approvals_per_token_or_collection() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc37_max_approvals_per_token_or_collection()
+        }
+    };
+    (@emit icrc37_max_revoke_approvals) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc37_max_revoke_approvals")]
+        fn __canister_icrc37_max_revoke_approvals() -> Option<$crate::ic_cdk::export::candid::Nat> {
+            $crate::icrc37_max_revoke_approvals()
+        }
+    };
+    (@emit icrc37_approve_tokens) => {
+        #[$crate::ic_cdk_macros::update(name = "icrc37_approve_tokens")]
+        fn __canister_icrc37_approve_tokens(
+            args: Vec<$crate::ApproveTokenArg>,
+        ) -> Vec<Option<$crate::ApproveTokenResult>> {
+            $crate::icrc37_approve_tokens(args)
+        }
+    };
+    (@emit icrc37_approve_collection) => {
+        #[$crate::ic_cdk_macros::update(name = "icrc37_approve_collection")]
+        fn __canister_icrc37_approve_collection(
+            args: Vec<$crate::ApproveCollectionArg>,
+        ) -> Vec<Option<$crate::ApproveCollectionResult>> {
+            $crate::icrc37_approve_collection(args)
+        }
+    };
+    (@emit icrc37_revoke_token_approvals) => {
+        #[$crate::ic_cdk_macros::update(name = "icrc37_revoke_token_approvals")]
+        fn __canister_icrc37_revoke_token_approvals(
+            args: Vec<$crate::RevokeTokenApprovalArg>,
+        ) -> Vec<Option<$crate::RevokeTokenApprovalResponse>> {
+            $crate::icrc37_revoke_token_approvals(args)
+        }
+    };
+    (@emit icrc37_revoke_collection_approvals) => {
+        #[$crate::ic_cdk_macros::update(name = "icrc37_revoke_collection_approvals")]
+        fn __canister_icrc37_revoke_collection_approvals(
+            args: Vec<$crate::RevokeCollectionApprovalArg>,
+        ) -> Vec<Option<$crate::RevokeCollectionApprovalResult>> {
+            $crate::icrc37_revoke_collection_approvals(args)
+        }
+    };
+    (@emit icrc37_is_approved) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc37_is_approved")]
+        fn __canister_icrc37_is_approved(args: Vec<$crate::IsApprovedArg>) -> Vec<bool> {
+            $crate::icrc37_is_approved(args)
+        }
+    };
+    (@emit icrc37_get_token_approvals) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc37_get_token_approvals")]
+        fn __canister_icrc37_get_token_approvals(
+            token_id: $crate::TokenId,
+            prev: Option<$crate::TokenApproval>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::TokenApproval> {
+            $crate::icrc37_get_token_approvals(token_id, prev, take)
+        }
+    };
+    (@emit icrc37_get_collection_approvals) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc37_get_collection_approvals")]
+        fn __canister_icrc37_get_collection_approvals(
+            owner: $crate::Account,
+            prev: Option<$crate::CollectionApproval>,
+            take: Option<$crate::ic_cdk::export::candid::Nat>,
+        ) -> Vec<$crate::CollectionApproval> {
+            $crate::icrc37_get_collection_approvals(owner, prev, take)
+        }
+    };
+    (@emit icrc37_transfer_from) => {
+        #[$crate::ic_cdk_macros::update(name = "icrc37_transfer_from")]
+        fn __canister_icrc37_transfer_from(
+            args: Vec<$crate::TransferFromArg>,
+        ) -> Vec<Option<$crate::TransferFromResult>> {
+            $crate::icrc37_transfer_from(args)
+        }
+    };
+    (@emit icrc3_get_blocks) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc3_get_blocks")]
+        fn __canister_icrc3_get_blocks(
+            args: Vec<$crate::GetBlocksArgs>,
+        ) -> $crate::GetBlocksResult {
+            $crate::icrc3_get_blocks(args)
+        }
+    };
+    (@emit icrc3_get_archives) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc3_get_archives")]
+        fn __canister_icrc3_get_archives(
+            args: $crate::GetArchivesArgs,
+        ) -> Vec<$crate::ICRC3ArchiveInfo> {
+            $crate::icrc3_get_archives(args)
+        }
+    };
+    (@emit icrc3_get_tip_certificate) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc3_get_tip_certificate")]
+        fn __canister_icrc3_get_tip_certificate() -> Option<$crate::ICRC3DataCertificate> {
+            $crate::icrc3_get_tip_certificate()
+        }
+    };
+    (@emit icrc3_supported_block_types) => {
+        #[$crate::ic_cdk_macros::query(name = "icrc3_supported_block_types")]
+        fn __canister_icrc3_supported_block_types() -> Vec<$crate::SupportedBlockType> {
+            $crate::icrc3_supported_block_types()
+        }
+    };
+}
+
+#[cfg(all(feature = "canister", target_arch = "wasm32"))]
+mod exports {
+    crate::export_canister!();
+}
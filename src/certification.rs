@@ -0,0 +1,548 @@
+//! Certified ownership data.
+//!
+//! After every update that appends an ICRC-3 block the ledger is summarised in
+//! a labeled Merkle tree, laid out in the IC `HashTree` format, whose root hash
+//! becomes the canister's `certified_data`:
+//!
+//! ```text
+//! last_block_index -> LEB128 index of the ICRC-3 tip
+//! last_block_hash  -> hash of the ICRC-3 tip
+//! owners/<token id in decimal>          -> 32-byte account identifier
+//! balances/<32-byte account identifier> -> LEB128 token count
+//! ```
+//!
+//! Queries return the system certificate together with a witness: the tree
+//! with everything off the requested paths pruned. Keys that are not in the
+//! tree are witnessed by their revealed neighbours, which proves absence.
+//!
+//! The owners and balances are kept in [`CertifiedMap`]s that the ledger
+//! updates along with its owner cache, so certifying an update costs time
+//! logarithmic in the number of tokens.
+
+use ic_cdk::export::candid::types::{Serializer, Type};
+use ic_cdk::export::candid::{CandidType, Deserialize, Nat, Reserved};
+use serde::de::Deserializer;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+
+use crate::icrc3::ICRC3DataCertificate;
+use crate::ledger::{self, Ledger};
+use crate::types::{AccountIdentifier_shiku, TokenId};
+
+pub const LAST_BLOCK_INDEX: &[u8] = b"last_block_index";
+pub const LAST_BLOCK_HASH: &[u8] = b"last_block_hash";
+pub const OWNERS: &[u8] = b"owners";
+pub const BALANCES: &[u8] = b"balances";
+
+pub type Hash = [u8; 32];
+
+/// A (possibly pruned) labeled tree, as returned to clients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashTree {
+    Empty,
+    Fork(Box<HashTree>, Box<HashTree>),
+    Labeled(Vec<u8>, Box<HashTree>),
+    Leaf(Vec<u8>),
+    Pruned(Hash),
+}
+
+/// The outcome of looking a path up in a witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupResult<'a> {
+    /// The path leads to this leaf.
+    Found(&'a [u8]),
+    /// The witness proves that the path is not in the tree.
+    Absent,
+    /// The path is hidden behind a pruned subtree.
+    Unknown,
+}
+
+fn domain_hash(tag: &str) -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update([tag.len() as u8]);
+    hasher.update(tag.as_bytes());
+    hasher
+}
+
+fn empty_hash() -> Hash {
+    domain_hash("ic-hashtree-empty").finalize().into()
+}
+
+fn fork_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = domain_hash("ic-hashtree-fork");
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn labeled_hash(label: &[u8], subtree: &Hash) -> Hash {
+    let mut hasher = domain_hash("ic-hashtree-labeled");
+    hasher.update(label);
+    hasher.update(subtree);
+    hasher.finalize().into()
+}
+
+fn leaf_hash(value: &[u8]) -> Hash {
+    let mut hasher = domain_hash("ic-hashtree-leaf");
+    hasher.update(value);
+    hasher.finalize().into()
+}
+
+fn write_cbor_header(out: &mut Vec<u8>, major: u8, len: u64) {
+    let major = major << 5;
+    if len < 24 {
+        out.push(major | len as u8);
+    } else if len <= u8::MAX as u64 {
+        out.extend_from_slice(&[major | 24, len as u8]);
+    } else if len <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn write_cbor_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_cbor_header(out, 2, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+impl HashTree {
+    /// The root hash of the full tree this witness was cut from.
+    pub fn reconstruct(&self) -> Hash {
+        match self {
+            HashTree::Empty => empty_hash(),
+            HashTree::Fork(left, right) => fork_hash(&left.reconstruct(), &right.reconstruct()),
+            HashTree::Labeled(label, subtree) => labeled_hash(label, &subtree.reconstruct()),
+            HashTree::Leaf(value) => leaf_hash(value),
+            HashTree::Pruned(hash) => *hash,
+        }
+    }
+
+    /// Looks `path` up following the IC interface specification.
+    pub fn lookup(&self, path: &[&[u8]]) -> LookupResult<'_> {
+        let (label, rest) = match path.split_first() {
+            None => {
+                return match self {
+                    HashTree::Leaf(value) => LookupResult::Found(value),
+                    HashTree::Pruned(_) => LookupResult::Unknown,
+                    _ => LookupResult::Absent,
+                }
+            }
+            Some(split) => split,
+        };
+        let mut nodes = Vec::new();
+        self.flatten_forks(&mut nodes);
+        let mut previous_is_smaller_label = true;
+        for node in nodes {
+            match node {
+                HashTree::Labeled(l, subtree) if l.as_slice() == *label => {
+                    return subtree.lookup(rest)
+                }
+                HashTree::Labeled(l, _) if l.as_slice() > *label => {
+                    return if previous_is_smaller_label {
+                        LookupResult::Absent
+                    } else {
+                        LookupResult::Unknown
+                    };
+                }
+                HashTree::Labeled(..) => previous_is_smaller_label = true,
+                _ => previous_is_smaller_label = false,
+            }
+        }
+        if previous_is_smaller_label {
+            LookupResult::Absent
+        } else {
+            LookupResult::Unknown
+        }
+    }
+
+    fn flatten_forks<'a>(&'a self, nodes: &mut Vec<&'a HashTree>) {
+        match self {
+            HashTree::Empty => {}
+            HashTree::Fork(left, right) => {
+                left.flatten_forks(nodes);
+                right.flatten_forks(nodes);
+            }
+            node => nodes.push(node),
+        }
+    }
+
+    /// Encodes the tree as self-described CBOR, the form agents verify.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut out = vec![0xd9, 0xd9, 0xf7];
+        self.write_cbor(&mut out);
+        out
+    }
+
+    fn write_cbor(&self, out: &mut Vec<u8>) {
+        match self {
+            HashTree::Empty => {
+                write_cbor_header(out, 4, 1);
+                write_cbor_header(out, 0, 0);
+            }
+            HashTree::Fork(left, right) => {
+                write_cbor_header(out, 4, 3);
+                write_cbor_header(out, 0, 1);
+                left.write_cbor(out);
+                right.write_cbor(out);
+            }
+            HashTree::Labeled(label, subtree) => {
+                write_cbor_header(out, 4, 3);
+                write_cbor_header(out, 0, 2);
+                write_cbor_bytes(out, label);
+                subtree.write_cbor(out);
+            }
+            HashTree::Leaf(value) => {
+                write_cbor_header(out, 4, 2);
+                write_cbor_header(out, 0, 3);
+                write_cbor_bytes(out, value);
+            }
+            HashTree::Pruned(hash) => {
+                write_cbor_header(out, 4, 2);
+                write_cbor_header(out, 0, 4);
+                write_cbor_bytes(out, hash);
+            }
+        }
+    }
+}
+
+/// A map from labels to leaves that keeps its hash tree up to date as it
+/// changes, so certifying an update costs time logarithmic in its size.
+///
+/// Entries live in a treap ordered by label, whose priorities are derived
+/// from the labels: the shape, and so the root hash, only depends on the
+/// entries and not on the order they were written in. Each entry's subtree
+/// is laid out as `fork(left, fork(labeled, right))`, dropping empty sides.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CertifiedMap {
+    root: Subtree,
+}
+
+type Subtree = Option<Box<Entry>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    label: Vec<u8>,
+    value: Vec<u8>,
+    priority: (u64, Vec<u8>),
+    left: Subtree,
+    right: Subtree,
+    /// The hash of the subtree rooted here.
+    hash: Hash,
+}
+
+impl Entry {
+    fn new(label: Vec<u8>, value: Vec<u8>) -> Box<Entry> {
+        let digest: Hash = Sha256::digest(&label).into();
+        let mut priority = [0; 8];
+        priority.copy_from_slice(&digest[..8]);
+        let mut entry = Box::new(Entry {
+            priority: (u64::from_be_bytes(priority), label.clone()),
+            label,
+            value,
+            left: None,
+            right: None,
+            hash: empty_hash(),
+        });
+        entry.rehash();
+        entry
+    }
+
+    fn labeled_hash(&self) -> Hash {
+        labeled_hash(&self.label, &leaf_hash(&self.value))
+    }
+
+    fn rehash(&mut self) {
+        let labeled = self.labeled_hash();
+        let right = match &self.right {
+            Some(right) => fork_hash(&labeled, &right.hash),
+            None => labeled,
+        };
+        self.hash = match &self.left {
+            Some(left) => fork_hash(&left.hash, &right),
+            None => right,
+        };
+    }
+
+    fn rotate_right(mut self: Box<Entry>) -> Box<Entry> {
+        let mut left = self.left.take().expect("rotating right needs a left child");
+        self.left = left.right.take();
+        self.rehash();
+        left.right = Some(self);
+        left.rehash();
+        left
+    }
+
+    fn rotate_left(mut self: Box<Entry>) -> Box<Entry> {
+        let mut right = self.right.take().expect("rotating left needs a right child");
+        self.right = right.left.take();
+        self.rehash();
+        right.left = Some(self);
+        right.rehash();
+        right
+    }
+}
+
+fn insert(subtree: Subtree, label: Vec<u8>, value: Vec<u8>) -> Box<Entry> {
+    let mut entry = match subtree {
+        None => return Entry::new(label, value),
+        Some(entry) => entry,
+    };
+    match label.cmp(&entry.label) {
+        Ordering::Equal => entry.value = value,
+        Ordering::Less => {
+            let left = insert(entry.left.take(), label, value);
+            let rotate = left.priority > entry.priority;
+            entry.left = Some(left);
+            if rotate {
+                return entry.rotate_right();
+            }
+        }
+        Ordering::Greater => {
+            let right = insert(entry.right.take(), label, value);
+            let rotate = right.priority > entry.priority;
+            entry.right = Some(right);
+            if rotate {
+                return entry.rotate_left();
+            }
+        }
+    }
+    entry.rehash();
+    entry
+}
+
+fn remove(subtree: Subtree, label: &[u8]) -> Subtree {
+    let mut entry = subtree?;
+    match label.cmp(&entry.label) {
+        Ordering::Equal => return merge(entry.left.take(), entry.right.take()),
+        Ordering::Less => entry.left = remove(entry.left.take(), label),
+        Ordering::Greater => entry.right = remove(entry.right.take(), label),
+    }
+    entry.rehash();
+    Some(entry)
+}
+
+/// Joins two treaps whose labels are all smaller on the left.
+fn merge(left: Subtree, right: Subtree) -> Subtree {
+    match (left, right) {
+        (None, subtree) | (subtree, None) => subtree,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.rehash();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.rehash();
+                Some(right)
+            }
+        }
+    }
+}
+
+/// Reveals the entries at `labels`, and the entries on the way to those
+/// missing, which prove their absence. Everything else is pruned.
+fn witness(subtree: &Subtree, labels: &[&[u8]]) -> HashTree {
+    let entry = match subtree {
+        None => return HashTree::Empty,
+        Some(entry) if labels.is_empty() => return HashTree::Pruned(entry.hash),
+        Some(entry) => entry,
+    };
+    let smaller: Vec<&[u8]> = labels.iter().copied().filter(|l| *l < &entry.label[..]).collect();
+    let larger: Vec<&[u8]> = labels.iter().copied().filter(|l| *l > &entry.label[..]).collect();
+    let value = if labels.contains(&&entry.label[..]) {
+        HashTree::Leaf(entry.value.clone())
+    } else {
+        HashTree::Pruned(leaf_hash(&entry.value))
+    };
+    let mut tree = HashTree::Labeled(entry.label.clone(), Box::new(value));
+    if entry.right.is_some() {
+        tree = HashTree::Fork(Box::new(tree), Box::new(witness(&entry.right, &larger)));
+    }
+    if entry.left.is_some() {
+        tree = HashTree::Fork(Box::new(witness(&entry.left, &smaller)), Box::new(tree));
+    }
+    tree
+}
+
+impl CertifiedMap {
+    pub fn insert(&mut self, label: Vec<u8>, value: Vec<u8>) {
+        self.root = Some(insert(self.root.take(), label, value));
+    }
+
+    pub fn remove(&mut self, label: &[u8]) {
+        self.root = remove(self.root.take(), label);
+    }
+
+    pub fn get(&self, label: &[u8]) -> Option<&[u8]> {
+        let mut subtree = &self.root;
+        while let Some(entry) = subtree {
+            subtree = match label.cmp(&entry.label) {
+                Ordering::Equal => return Some(&entry.value),
+                Ordering::Less => &entry.left,
+                Ordering::Greater => &entry.right,
+            };
+        }
+        None
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        self.root.as_ref().map_or_else(empty_hash, |entry| entry.hash)
+    }
+
+    /// Reveals the leaves at `labels`, or proves they are absent, and prunes
+    /// everything else.
+    pub fn witness(&self, labels: &[&[u8]]) -> HashTree {
+        witness(&self.root, labels)
+    }
+}
+
+/// The owners and balances certified for a ledger, kept in step with it by
+/// [`Ledger::update_owner_cache`]. They are rebuilt after an upgrade rather
+/// than saved, and encode as `reserved` so that state saved without them
+/// still loads.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CertifiedState {
+    pub owners: CertifiedMap,
+    pub balances: CertifiedMap,
+}
+
+impl CertifiedState {
+    /// Builds the state certifying `ledger` from scratch.
+    pub fn of(ledger: &Ledger) -> CertifiedState {
+        let mut state = CertifiedState::default();
+        for (owner, tokens) in &ledger.owners {
+            for token in tokens {
+                state.owners.insert(token_label(token), owner.to_address().to_vec());
+            }
+            state.set_balance(owner, tokens.len());
+        }
+        state
+    }
+
+    pub(crate) fn set_owner(&mut self, token: &TokenId, owner: Option<&AccountIdentifier_shiku>) {
+        match owner {
+            Some(owner) => self.owners.insert(token_label(token), owner.to_address().to_vec()),
+            None => self.owners.remove(&token_label(token)),
+        }
+    }
+
+    pub(crate) fn set_balance(&mut self, owner: &AccountIdentifier_shiku, count: usize) {
+        let label = owner.to_address().to_vec();
+        match count {
+            0 => self.balances.remove(&label),
+            count => self.balances.insert(label, leb128(Nat::from(count))),
+        }
+    }
+}
+
+impl CandidType for CertifiedState {
+    fn _ty() -> Type {
+        Type::Reserved
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        serializer.serialize_null(())
+    }
+}
+
+impl<'de> Deserialize<'de> for CertifiedState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Reserved::deserialize(deserializer)?;
+        Ok(CertifiedState::default())
+    }
+}
+
+/// Lays labeled subtrees, sorted by label, out as a balanced tree of forks.
+fn forest(entries: &mut Vec<(&[u8], HashTree)>) -> HashTree {
+    match entries.len() {
+        0 => HashTree::Empty,
+        1 => {
+            let (label, subtree) = entries.remove(0);
+            HashTree::Labeled(label.to_vec(), Box::new(subtree))
+        }
+        len => {
+            let mut right = entries.split_off(len / 2);
+            HashTree::Fork(Box::new(forest(entries)), Box::new(forest(&mut right)))
+        }
+    }
+}
+
+fn leb128(nat: Nat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    nat.encode(&mut bytes).expect("writing to a Vec cannot fail");
+    bytes
+}
+
+/// The label of `token` under [`OWNERS`].
+pub fn token_label(token: &TokenId) -> Vec<u8> {
+    token.0.to_str_radix(10).into_bytes()
+}
+
+/// The tree certifying `ledger`, revealing `owners` and `balances` and,
+/// with `tip`, the ICRC-3 tip. Everything else is pruned.
+fn certified_tree(ledger: &Ledger, owners: &[&[u8]], balances: &[&[u8]], tip: bool) -> HashTree {
+    let certified = &ledger.certified;
+    // In label order: balances, last_block_hash, last_block_index, owners.
+    let mut entries = vec![(BALANCES, certified.balances.witness(balances))];
+    if let Some((index, hash)) = ledger.tip() {
+        let leaf = |value: Vec<u8>| {
+            if tip {
+                HashTree::Leaf(value)
+            } else {
+                HashTree::Pruned(leaf_hash(&value))
+            }
+        };
+        entries.push((LAST_BLOCK_HASH, leaf(hash.to_vec())));
+        entries.push((LAST_BLOCK_INDEX, leaf(leb128(index))));
+    }
+    entries.push((OWNERS, certified.owners.witness(owners)));
+    forest(&mut entries)
+}
+
+/// The root hash of the tree certifying `ledger`.
+pub fn root_hash(ledger: &Ledger) -> Hash {
+    certified_tree(ledger, &[], &[], false).reconstruct()
+}
+
+/// Witnesses the owner of `token`.
+pub fn owner_witness(ledger: &Ledger, token: &TokenId) -> HashTree {
+    certified_tree(ledger, &[&token_label(token)], &[], false)
+}
+
+/// Witnesses the number of tokens held by `owner`.
+pub fn balance_witness(ledger: &Ledger, owner: &AccountIdentifier_shiku) -> HashTree {
+    certified_tree(ledger, &[], &[&owner.to_address()], false)
+}
+
+/// Witnesses the ICRC-3 tip.
+pub fn tip_witness(ledger: &Ledger) -> HashTree {
+    certified_tree(ledger, &[], &[], true)
+}
+
+/// Sets the canister's `certified_data` to the root hash of the ledger tree.
+pub fn certify() {
+    let root_hash = ledger::with(root_hash);
+    #[cfg(target_arch = "wasm32")]
+    ic_cdk::api::set_certified_data(&root_hash);
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = root_hash;
+}
+
+/// Pairs `hash_tree` with the system certificate, which is only available
+/// in query calls.
+pub(crate) fn certificate(hash_tree: HashTree) -> Option<ICRC3DataCertificate> {
+    #[cfg(target_arch = "wasm32")]
+    let certificate = ic_cdk::api::data_certificate();
+    #[cfg(not(target_arch = "wasm32"))]
+    let certificate: Option<Vec<u8>> = None;
+    certificate.map(|certificate| ICRC3DataCertificate {
+        certificate,
+        hash_tree: hash_tree.to_cbor(),
+    })
+}
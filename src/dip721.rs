@@ -1,16 +1,30 @@
-use crate::ledger;
+use crate::certification;
+use crate::icrc3;
+use crate::icrc37::ApprovalInfo;
+use crate::icrc7::{after, check_tx_args, page, take_value, MAX_QUERY_BATCH_SIZE};
+use crate::ledger::{self, TxKey};
+use crate::stable;
+use crate::status::{TokenAction, TokenStatus};
 use crate::{
+    Account,
+    BlockWithId,
+    ICRC3DataCertificate,
+    ShikuNftError,
     NftError,
     TokenId, 
     TokenMetadata,
+    TokenRef,
 };
+use ic_cdk::api::stable::{stable_bytes, StableWriter};
 use ic_cdk::api::time;
 use ic_cdk::export::candid::Nat;
 use ic_cdk::export::Principal;
 use std::cell::RefCell;
 use std::ops::Not;
 use std::sync::atomic::AtomicU32;
-use std::collections::{HashSet, HashMap};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io::Write;
 
 // use super::types::{
 //     AccountIdentifier_shiku,
@@ -27,14 +41,13 @@ use std::collections::{HashSet, HashMap};
 use super::types::*;
 use super::pid2aid;
 thread_local! {
-    static TID: RefCell<AtomicU32> = RefCell::new(AtomicU32::new(1));
+    static TID: RefCell<AtomicU32> = const { RefCell::new(AtomicU32::new(1)) };
 }
 
 pub fn new_token_id() -> u32 {
     TID.with(|tid| {
         let token = tid.borrow_mut();
-        let new_id = token.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        new_id
+        token.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
     })
 }
 
@@ -53,16 +66,50 @@ pub fn restore_tid_info(token_index: u32) {
 pub fn restore_minted_info(minted_id: Vec<Nat>) {
     change_minted_state(|minted|
         {
-            let mut mint = minted.clone();
             for id in minted_id.iter() {
-                mint.push(id.to_owned())
+                minted.insert(id.to_owned());
             }
         }
     );
 }
 
 pub fn dip721_init(args: Option<InitArgs>) {
-    ledger::with_mut(|ledger| ledger.init_metadata(ic_cdk::api::caller(), args));
+    ledger::with_mut(|ledger| ledger.init_metadata(ledger::caller(), args));
+    certification::certify();
+}
+
+/// Saves the ledger together with the token id counter, the minted ids and
+/// the minter to stable memory, in the layout of [`stable::encode`]. The
+/// ledger is moved out of the heap to save it, so call it last, from the
+/// canister's `pre_upgrade`.
+pub fn dip721_pre_upgrade() {
+    let minted: Vec<Nat> = read_minted_state(|minted| minted.iter().cloned().collect());
+    let saved = stable::encode(ledger::take(), tid_info(), &minted, get_minter())
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| {
+            StableWriter::default().write_all(&bytes).map_err(|e| e.to_string())
+        });
+    if let Err(e) = saved {
+        ic_cdk::trap(&format!("failed to save the ledger: {}", e));
+    }
+}
+
+/// Restores the state saved by [`dip721_pre_upgrade`], or by a version from
+/// before the saved ledger had a layout version. Call it from the canister's
+/// `post_upgrade`.
+pub fn dip721_post_upgrade() {
+    let (stored, tid, minted, minter) = match stable::decode(&stable_bytes()) {
+        Ok(state) => state,
+        Err(e) => ic_cdk::trap(&format!("failed to restore the ledger: {}", e)),
+    };
+    ledger::with_mut(|ledger| {
+        *ledger = stored;
+        ledger.metadata_mut().upgraded_at = time();
+    });
+    certification::certify();
+    restore_tid_info(tid.saturating_sub(tid_info()));
+    restore_minted_info(minted);
+    change_minter_state(|state| *state = minter);
 }
 
 pub fn dip721_total_supply() -> Nat {
@@ -74,16 +121,40 @@ pub fn dip721_balance_of(owner: AccountIdentifier_shiku) -> Result<Nat, NftError
         ledger
             .owner_token_identifiers(&owner)
             .map(|token_identifier| Nat::from(token_identifier.len()))
+            .map_err(NftError::from)
     })
 }
 
+/// Certifies the number of tokens held by `owner`, or that it holds none.
+/// Only available in query calls; returns `None` otherwise.
+pub fn dip721_balance_of_certified(
+    owner: AccountIdentifier_shiku,
+) -> Option<ICRC3DataCertificate> {
+    certification::certificate(ledger::with(|ledger| {
+        certification::balance_witness(ledger, &owner)
+    }))
+}
+
+/// Moves `token_identifier` from `owner` to `to`, on behalf of the caller,
+/// who must be `owner` or approved for the token. With `created_at_time`, a
+/// retry within the ICRC deduplication window returns `Ok` with the original
+/// transaction index instead of transferring again; see
+/// [`ledger::Ledger::deduplicate`].
 pub fn dip721_transfer_from(
     owner: AccountIdentifier_shiku,
     to: AccountIdentifier_shiku,
     token_identifier: TokenId,
+    created_at_time: Option<u64>,
 ) -> Result<Nat, NftError> {
-    ledger::with_mut(|ledger| {
-        let _caller = ic_cdk::api::caller();
+    let now = ledger::now();
+    check_tx_args(None, created_at_time, now).map_err(ShikuNftError::from)?;
+    let caller = ledger::caller();
+    let key = created_at_time.map(|created_at_time| {
+        let args = (owner, to, token_identifier.clone());
+        TxKey::new(&caller, "dip721_transfer_from", &args, created_at_time)
+    });
+    let caller = pid2aid(&caller)?;
+    ledger::with_mut(|ledger| ledger.deduplicate(key, now, Ok, |ledger| {
         if owner.eq(&to) {
             // insert_sync(IndefiniteEvent {
             //     caller: ic_cdk::api::caller(),
@@ -99,22 +170,15 @@ pub fn dip721_transfer_from(
             Some(owner) => owner,
             None => return Err(NftError::OwnerNotFound),
         };
-        let old_operator = match ledger.operator_of(&token_identifier).ok() {
-            Some(operator) => operator,
-            None => return Err(NftError::OperatorNotFound),
-        };
-        
+
         old_owner
             .eq(&Some(owner))
             .then_some(())
             .ok_or(NftError::UnauthorizedOwner)?;
-        old_operator
-            .eq(&Some(owner))
-            .then_some(())
-            .ok_or(NftError::UnauthorizedOperator)?;
-        ledger.update_owner_cache(&token_identifier, old_owner, Some(to));
-        ledger.update_operator_cache(&token_identifier, old_operator, Some(to));
-        ledger.transfer(owner, &token_identifier, Some(to));
+        if caller != owner && !ledger.is_approved(&token_identifier, &caller, now) {
+            return Err(NftError::UnauthorizedOperator);
+        }
+        ledger.transfer(caller, &token_identifier, Some(to))?;
 
         // insert_sync(IndefiniteEvent {
         //     caller,
@@ -129,20 +193,382 @@ pub fn dip721_transfer_from(
         //     ],
         // });
 
-        Ok(Nat::from(ledger.inc_tx() - 1))
+        Ok(ledger.tx_index())
+    }))
+}
+
+/// Transfers a token held by the caller's default account to `to`.
+pub fn dip721_transfer(to: AccountIdentifier_shiku, token_identifier: TokenId) -> Result<Nat, NftError> {
+    let owner = pid2aid(&ledger::caller())?;
+    dip721_transfer_from(owner, to, token_identifier, None)
+}
+
+/// Approves `operator`, or revokes its approval, for every token held by the
+/// caller's default account, now or later. This is an ICRC-37 collection
+/// approval that never expires.
+pub fn dip721_set_approval_for_all(operator: Principal, is_approved: bool) -> Result<Nat, NftError> {
+    let caller = ledger::caller();
+    let owner = pid2aid(&caller)?;
+    let spender = Account { owner: operator, subaccount: None };
+    let now = ledger::now();
+    ledger::with_mut(|ledger| {
+        let accounts = [Account { owner: caller, subaccount: None }, spender];
+        ledger.linking(&accounts, |ledger| {
+            if is_approved {
+                let approval = ApprovalInfo {
+                    spender,
+                    from_subaccount: None,
+                    expires_at: None,
+                    memo: None,
+                    created_at_time: now,
+                };
+                ledger.approve_collection(owner, approval, now)
+            } else {
+                ledger.revoke_collection_approvals(owner, Some(spender.into()), now)
+            }
+        })?;
+        Ok(ledger.tx_index())
+    })
+}
+
+/// Whether `operator` may move every token of `owner`'s default account.
+pub fn dip721_is_approved_for_all(owner: Principal, operator: Principal) -> Result<bool, NftError> {
+    let (owner, operator) = (pid2aid(&owner)?, pid2aid(&operator)?);
+    let now = ledger::now();
+    ledger::with(|ledger| {
+        Ok(ledger
+            .collection_approvals(&owner, now)
+            .any(|approval| approval.spender_aid() == operator))
     })
 }
 
+/// Transfers each token of `owner` to its recipient, all or nothing, and
+/// returns the transaction index of each. The caller must be `owner` or
+/// approved for every token. The transfers are logged as one contiguous
+/// group of blocks.
+pub fn dip721_transfer_batch(
+    owner: AccountIdentifier_shiku,
+    transfers: Vec<(AccountIdentifier_shiku, TokenId)>,
+) -> Result<Vec<Nat>, NftError> {
+    if transfers.len() > MAX_TRANSFER_BATCH_SIZE {
+        return Err(NftError::Other(format!(
+            "A batch may transfer at most {} tokens, split it into chunks",
+            MAX_TRANSFER_BATCH_SIZE
+        )));
+    }
+    let caller = pid2aid(&ledger::caller())?;
+    let now = ledger::now();
+    ledger::with_mut(|ledger| {
+        for (position, (to, token_identifier)) in transfers.iter().enumerate() {
+            if *to == owner || ledger.owner_of(token_identifier).ok() != Some(Some(owner)) {
+                return Err(batch_error((position, ShikuNftError::UnauthorizedOwner)));
+            }
+            if caller != owner && !ledger.is_approved(token_identifier, &caller, now) {
+                return Err(batch_error((position, ShikuNftError::UnauthorizedOperator)));
+            }
+        }
+        let count = transfers.len();
+        let transfers = transfers
+            .into_iter()
+            .map(|(to, token_identifier)| (token_identifier, Some(to)))
+            .collect();
+        ledger.transfer_batch(caller, transfers).map_err(batch_error)?;
+        Ok(ledger.tx_indexes(count))
+    })
+}
+
+/// Puts each token into `class`, all or none of them. A token's class is
+/// set once and for all, by the minter or a custodian.
+pub fn dip721_set_class(class: String, token_identifiers: Vec<TokenId>) -> Result<(), NftError> {
+    let caller = ledger::caller();
+    if caller != get_minter() && !dip721_custodians().contains(&caller) {
+        return Err(NftError::Other(
+            "Only the minter or a custodian can set classes".to_string(),
+        ));
+    }
+    if class.is_empty() {
+        return Err(NftError::Other("The class must not be empty".to_string()));
+    }
+    ledger::with_mut(|ledger| Ok(ledger.set_class(&class, &token_identifiers)?))
+}
+
+/// The number of live tokens in `class`.
+pub fn dip721_class_supply(class: String) -> Nat {
+    ledger::with(|ledger| Nat::from(ledger.class_supply(&class)))
+}
+
+/// The number of tokens of `class` held by `owner`.
+pub fn dip721_class_balance_of(class: String, owner: AccountIdentifier_shiku) -> Nat {
+    ledger::with(|ledger| Nat::from(ledger.class_balance(&class, &owner)))
+}
+
 pub fn dip721_custodians() -> HashSet<Principal> {
     ledger::with(|ledger| ledger.metadata().custodians.clone())
 }
 
+/// Fails unless the caller is a custodian, saying it is needed to `action`.
+fn check_custodian(action: &str) -> Result<(), NftError> {
+    if !dip721_custodians().contains(&ledger::caller()) {
+        return Err(NftError::Other(format!("Only a custodian can {}", action)));
+    }
+    Ok(())
+}
+
+/// Replaces the custodians. The collection always keeps at least one, and
+/// they can be changed even once the metadata is frozen.
+pub fn dip721_set_custodians(custodians: HashSet<Principal>) -> Result<(), NftError> {
+    check_custodian("change the custodians")?;
+    if custodians.is_empty() {
+        return Err(NftError::Other("The collection needs a custodian".to_string()));
+    }
+    ledger::with_mut(|ledger| ledger.metadata_mut().custodians = custodians);
+    Ok(())
+}
+
+pub fn dip721_metadata() -> MetaData {
+    ledger::with(|ledger| ledger.metadata().clone())
+}
+
+pub fn dip721_name() -> Option<String> {
+    ledger::with(|ledger| ledger.metadata().name.clone())
+}
+
+pub fn dip721_logo() -> Option<String> {
+    ledger::with(|ledger| ledger.metadata().logo.clone())
+}
+
+pub fn dip721_symbol() -> Option<String> {
+    ledger::with(|ledger| ledger.metadata().symbol.clone())
+}
+
+pub fn dip721_mint_policy() -> MintPolicy {
+    ledger::with(|ledger| ledger.mint_policy.clone())
+}
+
+/// Whether `caller` may mint to every one of `recipients`: the minter and the
+/// custodians always may, anyone else only to themselves during the public
+/// phase.
+pub fn check_mint_caller(
+    caller: Principal,
+    recipients: &[AccountIdentifier_shiku],
+) -> Result<(), NftError> {
+    let public = ledger::with(|ledger| ledger.mint_policy.phase == MintPhase::Public);
+    let caller_aid = pid2aid(&caller)?;
+    if public && recipients.iter().all(|recipient| *recipient == caller_aid) {
+        return Ok(());
+    }
+    if caller != get_minter() && !dip721_custodians().contains(&caller) {
+        return Err(NftError::Other(if public {
+            "Public mints go to the caller's own account".to_string()
+        } else {
+            "Only the minter or a custodian can mint".to_string()
+        }));
+    }
+    Ok(())
+}
+
+/// Replaces the mint policy. Tokens already minted are left alone, even
+/// beyond a lowered limit.
+pub fn dip721_set_mint_policy(mint_policy: MintPolicy) -> Result<(), NftError> {
+    check_custodian("change the mint policy")?;
+    if let (Some(start), Some(end)) = (mint_policy.mint_start, mint_policy.mint_end) {
+        if start >= end {
+            return Err(NftError::Other("The mint must start before it ends".to_string()));
+        }
+    }
+    ledger::with_mut(|ledger| ledger.set_mint_policy(mint_policy));
+    Ok(())
+}
+
+/// Pauses or resumes every mint, transfer, burn, approval and revocation,
+/// whichever standard it comes through.
+pub fn dip721_set_paused(paused: bool) -> Result<(), NftError> {
+    check_custodian("pause the collection")?;
+    ledger::with_mut(|ledger| ledger.set_paused(paused));
+    Ok(())
+}
+
+pub fn dip721_is_paused() -> bool {
+    ledger::with(|ledger| ledger.paused)
+}
+
+/// Freezes the collection metadata and token classes. There is no way back.
+pub fn dip721_freeze_metadata() -> Result<(), NftError> {
+    check_custodian("freeze the metadata")?;
+    ledger::with_mut(|ledger| ledger.freeze_metadata());
+    Ok(())
+}
+
+pub fn dip721_is_metadata_frozen() -> bool {
+    ledger::with(|ledger| ledger.metadata_frozen)
+}
+
+pub fn dip721_set_name(name: String) -> Result<(), NftError> {
+    check_custodian("change the metadata")?;
+    ledger::with_mut(|ledger| Ok(ledger.edit_metadata(|metadata| metadata.name = Some(name))?))
+}
+
+pub fn dip721_set_logo(logo: String) -> Result<(), NftError> {
+    check_custodian("change the metadata")?;
+    ledger::with_mut(|ledger| Ok(ledger.edit_metadata(|metadata| metadata.logo = Some(logo))?))
+}
+
+pub fn dip721_set_symbol(symbol: String) -> Result<(), NftError> {
+    check_custodian("change the metadata")?;
+    ledger::with_mut(|ledger| {
+        Ok(ledger.edit_metadata(|metadata| metadata.symbol = Some(symbol))?)
+    })
+}
+
+/// Locks a token in place: it can still be queried and approved, but not
+/// transferred or burned until unlocked. Only custodians lock tokens.
+pub fn dip721_set_locked(token_identifier: TokenId, locked: bool) -> Result<(), NftError> {
+    check_custodian("lock tokens")?;
+    let action = if locked { TokenAction::Lock } else { TokenAction::Unlock };
+    dip721_change_status(token_identifier, action).map(|_| ())
+}
+
+/// Stakes, lists, locks or otherwise changes the status of a token, as far as
+/// [`TRANSITIONS`](crate::TRANSITIONS) allows. Returns the new status.
+///
+/// Custodians may take any action. Anyone else must own the token or be
+/// approved for it, and may not lock or unlock it.
+pub fn dip721_change_status(
+    token_identifier: TokenId,
+    action: TokenAction,
+) -> Result<TokenStatus, NftError> {
+    let caller = ledger::caller();
+    let custodian = dip721_custodians().contains(&caller);
+    if !custodian && matches!(action, TokenAction::Lock | TokenAction::Unlock) {
+        return Err(NftError::Other("Only a custodian can lock tokens".to_string()));
+    }
+    let caller = pid2aid(&caller)?;
+    let now = ledger::now();
+    ledger::with_mut(|ledger| {
+        if !custodian
+            && ledger.owner_of(&token_identifier) != Ok(Some(caller))
+            && !ledger.is_approved(&token_identifier, &caller, now)
+        {
+            return Err(NftError::UnauthorizedOperator);
+        }
+        Ok(ledger.change_status(caller, &token_identifier, action)?)
+    })
+}
+
+pub fn dip721_status_of(token_identifier: TokenId) -> Result<TokenStatus, NftError> {
+    ledger::with(|ledger| Ok(ledger.status_of(&token_identifier)?))
+}
+
+/// The tokens in `status` after `prev`, in ascending order, at most `take` of
+/// them.
+pub fn dip721_tokens_with_status(
+    status: TokenStatus,
+    prev: Option<TokenId>,
+    take: Option<Nat>,
+) -> Vec<TokenId> {
+    ledger::with(|ledger| {
+        ledger
+            .tokens_with_status(status, prev.as_ref())
+            .take(take_value(take))
+            .cloned()
+            .collect()
+    })
+}
+
+pub fn dip721_is_locked(token_identifier: TokenId) -> bool {
+    ledger::with(|ledger| ledger.is_locked(&token_identifier))
+}
+
+/// The most tokens one `dip721_mint_batch` call mints, which keeps the call
+/// well within the instruction limit. Larger batches are rejected whole, so
+/// larger drops take several calls.
+pub const MAX_MINT_BATCH_SIZE: usize = 1_000;
+
+/// The most tokens one `dip721_transfer_batch` call moves.
+pub const MAX_TRANSFER_BATCH_SIZE: usize = 1_000;
+
+fn batch_error((position, e): (usize, ShikuNftError)) -> NftError {
+    NftError::Other(format!("item {}: {}", position, e))
+}
+
+/// The recipient, id and properties of a token to mint, and whether it is
+/// soulbound.
+pub type MintBatchItem = (AccountIdentifier_shiku, TokenId, Option<MetaData>, Option<bool>);
+
+fn new_token(
+    to: AccountIdentifier_shiku,
+    token_identifier: &TokenId,
+    properties: Option<MetaData>,
+    soulbound: bool,
+    now: u64,
+) -> TokenMetadata {
+    TokenMetadata {
+        token_identifier: token_identifier.to_owned(),
+        owner: Some(to),
+        operator: Some(to),
+        properties,
+        is_burned: false,
+        minted_at: now,
+        minted_by: to,
+        transferred_at: None,
+        transferred_by: None,
+        approved_at: None,
+        approved_by: None,
+        burned_at: None,
+        burned_by: None,
+        status: TokenStatus::Active,
+        soulbound,
+    }
+}
+
+/// Mints every item or none of them, returning the transaction index of
+/// each. The whole batch is checked against [`check_mint_caller`], for
+/// existing or repeated ids and against the mint policy before anything is
+/// written.
+pub fn dip721_mint_batch(items: Vec<MintBatchItem>) -> Result<Vec<Nat>, NftError> {
+    let recipients: Vec<_> = items.iter().map(|(to, ..)| *to).collect();
+    check_mint_caller(ledger::caller(), &recipients)?;
+    if items.len() > MAX_MINT_BATCH_SIZE {
+        return Err(NftError::Other(format!(
+            "A batch may mint at most {} tokens, split it into chunks",
+            MAX_MINT_BATCH_SIZE
+        )));
+    }
+    let now = ledger::now();
+    ledger::with_mut(|ledger| {
+        let count = items.len();
+        let tokens = items
+            .into_iter()
+            .map(|(to, token_identifier, properties, soulbound)| {
+                let soulbound = soulbound.unwrap_or(false);
+                let token_metadata = new_token(to, &token_identifier, properties, soulbound, now);
+                (token_identifier, token_metadata)
+            })
+            .collect();
+        ledger.mint_batch(tokens).map_err(batch_error)?;
+        Ok(ledger.tx_indexes(count))
+    })
+}
+
+/// Mints `token_identifier` to `to`. With `created_at_time`, a retry within
+/// the ICRC deduplication window returns `Ok` with the original transaction
+/// index instead of failing with `ExistedNFT`; see
+/// [`ledger::Ledger::deduplicate`].
 pub fn dip721_mint(
     to: AccountIdentifier_shiku,
     token_identifier: &TokenId,
+    created_at_time: Option<u64>,
+    soulbound: Option<bool>,
     // properties: CoCreateMetadata,
 ) -> Result<Nat, NftError> {
-    ledger::with_mut(|ledger| {
+    check_mint_caller(ledger::caller(), &[to])?;
+    let now = ledger::now();
+    check_tx_args(None, created_at_time, now).map_err(ShikuNftError::from)?;
+    let key = created_at_time.map(|created_at_time| {
+        let args = (to, token_identifier.clone(), soulbound);
+        TxKey::new(&ledger::caller(), "dip721_mint", &args, created_at_time)
+    });
+    ledger::with_mut(|ledger| ledger.deduplicate(key, now, Ok, |ledger| {
         // let caller = ic_cdk::api::caller();
         if !ledger.is_token_existed(token_identifier).not() {
             // insert_sync(IndefiniteEvent {
@@ -160,27 +586,8 @@ pub fn dip721_mint(
 
         // let cocreate_prop = CoCreateMetadata::new(Some(name), None, Some(description));
 
-        ledger.add_token_metadata(
-            token_identifier,
-            TokenMetadata {
-                token_identifier: token_identifier.to_owned(),
-                owner: Some(to),
-                operator: Some(to),
-                properties: None,
-                is_burned: false,
-                minted_at: time(),
-                minted_by: to,
-                transferred_at: None,
-                transferred_by: None,
-                approved_at: None,
-                approved_by: None,
-                burned_at: None,
-                burned_by: None,
-                status: 1,
-            },
-        );
-        ledger.update_owner_cache(&token_identifier, None, Some(to));
-        ledger.update_operator_cache(&token_identifier, None, Some(to));
+        let token_metadata = new_token(to, token_identifier, None, soulbound.unwrap_or(false), now);
+        ledger.mint(token_identifier, token_metadata)?;
         // insert_sync(IndefiniteEvent {
         //     caller,
         //     operation: "mint".into(),
@@ -193,13 +600,13 @@ pub fn dip721_mint(
         //     ],
         // });
 
-        Ok(Nat::from(ledger.inc_tx() - 1))
-    })
+        Ok(ledger.tx_index())
+    }))
 }
 
 pub fn dip721_burn(token_identifier: TokenId) -> Result<Nat, NftError> {
     ledger::with_mut(|ledger| {
-        let caller = pid2aid(&ic_cdk::api::caller());
+        let caller = pid2aid(&ledger::caller())?;
         let old_owner = match ledger.owner_of(&token_identifier).ok() {
             Some(owner) => owner,
             None => return Err(NftError::OwnerNotFound),
@@ -216,13 +623,7 @@ pub fn dip721_burn(token_identifier: TokenId) -> Result<Nat, NftError> {
             // });
             return Err(NftError::UnauthorizedOwner);
         }
-        let old_operator = match ledger.operator_of(&token_identifier).ok() {
-            Some(operator) => operator,
-            None => return Err(NftError::OperatorNotFound),
-        };
-        ledger.update_owner_cache(&token_identifier, old_owner, None);
-        ledger.update_operator_cache(&token_identifier, old_operator, None);
-        ledger.burn(caller, &token_identifier);
+        ledger.burn(caller, &token_identifier)?;
 
         // insert_sync(IndefiniteEvent {
         //     caller,
@@ -233,7 +634,7 @@ pub fn dip721_burn(token_identifier: TokenId) -> Result<Nat, NftError> {
         //     )],
         // });
 
-        Ok(Nat::from(ledger.inc_tx() - 1))
+        Ok(ledger.tx_index())
     })
 }
 
@@ -242,7 +643,7 @@ pub fn dip721_approve(
     token_identifier: TokenId,
 ) -> Result<Nat, NftError> {
     ledger::with_mut(|ledger| {
-        let caller = pid2aid(&ic_cdk::api::caller());
+        let caller = pid2aid(&ledger::caller())?;
         if operator.eq(&caller) {
             // insert_sync(IndefiniteEvent {
             //     caller: ic_cdk::api::caller(),
@@ -266,12 +667,7 @@ pub fn dip721_approve(
             // });
             return Err(NftError::UnauthorizedOwner);
         }
-        ledger.update_operator_cache(
-            &token_identifier,
-            ledger.operator_of(&token_identifier)?,
-            Some(operator),
-        );
-        ledger.approve(caller, &token_identifier, Some(operator));
+        ledger.approve(caller, &token_identifier, Some(operator))?;
 
         // insert_sync(IndefiniteEvent {
         //     caller,
@@ -285,12 +681,24 @@ pub fn dip721_approve(
         //     ],
         // });
 
-        Ok(Nat::from(ledger.inc_tx() - 1))
+        Ok(ledger.tx_index())
     })
 }
 
-pub fn dip721_get_registry() -> HashMap<TokenIndexU32, AccountIdentifier__1> {
-    ledger::with(|ledger| ledger.get_registry())
+/// The registry entries after `prev`, ordered by token index, at most `take`
+/// of them.
+pub fn dip721_get_registry(
+    prev: Option<TokenIndexU32>,
+    take: Option<Nat>,
+) -> Vec<(TokenIndexU32, AccountIdentifier__1)> {
+    ledger::with(|ledger| {
+        ledger
+            .idx2aid
+            .range(after(prev.as_ref()))
+            .take(take_value(take))
+            .map(|(idx, aid)| (*idx, aid.clone()))
+            .collect()
+    })
 }
 
 
@@ -301,94 +709,242 @@ pub fn dip721_add_aid_idx(k: &TokenIndexU32, v: &AccountIdentifier__1) {
 }
 
 pub fn dip721_token_metadata(token_identifier: TokenId) -> Result<TokenMetadata, NftError> {
-    ledger::with(|ledger| ledger.token_metadata(&token_identifier).cloned())
+    ledger::with(|ledger| ledger.token_metadata(&token_identifier).cloned().map_err(NftError::from))
 }
 
+/// The tokens of `owner` after `prev`, in ascending order, at most `take` of
+/// them.
 pub fn dip721_owner_token_identifiers(
     owner: AccountIdentifier_shiku,
-) -> Result<HashSet<TokenId>, NftError> {
-    ledger::with(|ledger| ledger.owner_token_identifiers(&owner).cloned())
+    prev: Option<TokenId>,
+    take: Option<Nat>,
+) -> Result<Vec<TokenId>, NftError> {
+    ledger::with(|ledger| {
+        let tokens = ledger.owner_token_identifiers(&owner)?;
+        Ok(page(tokens, prev, take))
+    })
 }
 
-pub fn dip721_operator_token_identifiers(
+pub fn dip721_operator_of(
     token: TokenId,
 ) -> Result<Option<AccountIdentifier_shiku>, NftError> {
-    ledger::with(|ledger| ledger.operator_of(&token))
+    ledger::with(|ledger| ledger.operator_of(&token).map_err(NftError::from))
+}
+
+/// The metadata of the tokens of `owner` after `prev`, in ascending order, at
+/// most `take` of them.
+pub fn dip721_owner_token_metadata(
+    owner: AccountIdentifier_shiku,
+    prev: Option<TokenId>,
+    take: Option<Nat>,
+) -> Result<Vec<TokenMetadata>, NftError> {
+    let tokens = dip721_owner_token_identifiers(owner, prev, take)?;
+    ledger::with(|ledger| {
+        tokens
+            .iter()
+            .map(|token| ledger.token_metadata(token).cloned().map_err(NftError::from))
+            .collect()
+    })
+}
+
+/// The metadata of the tokens operated by `operator` after `prev`, in
+/// ascending order, at most `take` of them.
+pub fn dip721_operator_token_metadata(
+    operator: AccountIdentifier_shiku,
+    prev: Option<TokenId>,
+    take: Option<Nat>,
+) -> Result<Vec<TokenMetadata>, NftError> {
+    let tokens = dip721_operator_token_identifiers(operator, prev, take)?;
+    ledger::with(|ledger| {
+        tokens
+            .iter()
+            .map(|token| ledger.token_metadata(token).cloned().map_err(NftError::from))
+            .collect()
+    })
 }
 
 pub fn dip721_owner_of(
     token: TokenId,
 ) -> Result<Option<AccountIdentifier_shiku>, NftError> {
-    ledger::with(|ledger| ledger.owner_of(&token))
+    ledger::with(|ledger| ledger.owner_of(&token).map_err(NftError::from))
 }
 
+/// Checks that a batch query asks for at most [`MAX_QUERY_BATCH_SIZE`] tokens.
+fn check_query_batch(tokens: &[TokenId]) -> Result<(), NftError> {
+    if tokens.len() as u64 > MAX_QUERY_BATCH_SIZE {
+        return Err(NftError::Other(format!(
+            "A batch may query at most {} tokens",
+            MAX_QUERY_BATCH_SIZE
+        )));
+    }
+    Ok(())
+}
 
-pub fn dip721_allowance(owner: &AccountIdentifier_shiku, spender: &AccountIdentifier_shiku, token: &TokenId) -> Result<Nat, CommonError> {
-    let _token_owner = match dip721_owner_of(token.to_owned()) {
-        Ok(_owner) => {
-            match _owner {
-                Some(internal_owner) => internal_owner,
-                None => AccountIdentifier_shiku::default(),
-            }
-        },
-        Err(_) => AccountIdentifier_shiku::default(),
-    
-    };
+/// The owner of each token, in the order asked. Unknown tokens get an error of
+/// their own instead of failing the call.
+pub fn dip721_owner_of_batch(
+    tokens: Vec<TokenId>,
+) -> Result<Vec<Result<Option<AccountIdentifier_shiku>, NftError>>, NftError> {
+    check_query_batch(&tokens)?;
+    ledger::with(|ledger| {
+        Ok(tokens
+            .iter()
+            .map(|token| ledger.owner_of(token).map_err(NftError::from))
+            .collect())
+    })
+}
 
-    if _token_owner != owner.to_owned() {
-        return Err(CommonError::Other("Invalid Owner".to_string()));
-    }
+/// The metadata of each token, in the order asked. Unknown tokens get an error
+/// of their own instead of failing the call.
+pub fn dip721_token_metadata_batch(
+    tokens: Vec<TokenId>,
+) -> Result<Vec<Result<TokenMetadata, NftError>>, NftError> {
+    check_query_batch(&tokens)?;
+    ledger::with(|ledger| {
+        Ok(tokens
+            .iter()
+            .map(|token| ledger.token_metadata(token).cloned().map_err(NftError::from))
+            .collect())
+    })
+}
+
+/// Certifies the owner of `token`, or that it has none. Only available in
+/// query calls; returns `None` otherwise.
+pub fn dip721_owner_of_certified(token: TokenId) -> Option<ICRC3DataCertificate> {
+    certification::certificate(ledger::with(|ledger| {
+        certification::owner_witness(ledger, &token)
+    }))
+}
 
-    let encoded_token = Nat::from(token.to_owned());
-    let pid = match dip721_operator_token_identifiers(encoded_token) {
-        Ok(principal) => principal,
-        Err(_) => Some(AccountIdentifier_shiku::default()),
-    };
-            if let Some(principal) = pid {
-                if principal == spender.to_owned() {
-                    Ok(Nat::from(1u32))
-                } else {
-                    Ok(Nat::from(0u32)) 
-                }
-            } else {
-               Ok(Nat::from(0u32)) 
-            }
-       
 
+/// 1 if `spender` may move `owner`'s token, as transfers decide through
+/// [`ledger::Ledger::is_approved`], 0 otherwise. `token` may be given in any
+/// form [`TokenRef`] converts from.
+pub fn dip721_allowance<T>(
+    owner: &AccountIdentifier_shiku,
+    spender: &AccountIdentifier_shiku,
+    token: T,
+) -> Result<Nat, CommonError>
+where
+    T: TryInto<TokenRef>,
+    CommonError: From<T::Error>,
+{
+    let token_id = TokenId::from(token.try_into()?);
+    let now = ledger::now();
+    ledger::with(|ledger| {
+        if ledger.owner_of(&token_id).ok().flatten() != Some(*owner) {
+            return Err(CommonError::Other("Invalid Owner".to_string()));
+        }
+        Ok(Nat::from(u32::from(ledger.is_approved(&token_id, spender, now))))
+    })
+}
+
+/// The tokens operated by `operator` after `prev`, in ascending order, at
+/// most `take` of them.
+pub fn dip721_operator_token_identifiers(
+    operator: AccountIdentifier_shiku,
+    prev: Option<TokenId>,
+    take: Option<Nat>,
+) -> Result<Vec<TokenId>, NftError> {
+    ledger::with(|ledger| {
+        let tokens = ledger.operator_token_identifier(&operator)?;
+        Ok(page(tokens, prev, take))
+    })
 }
 
+/// [`dip721_operator_token_identifiers`] under its former name.
 pub fn dip721_token_identitfier_operator(
-    operator: AccountIdentifier_shiku
-) -> Result<HashSet<TokenId>, NftError> {
-    ledger::with(|ledger| ledger.operator_token_identifier(&operator).cloned())
+    operator: AccountIdentifier_shiku,
+    prev: Option<TokenId>,
+    take: Option<Nat>,
+) -> Result<Vec<TokenId>, NftError> {
+    dip721_operator_token_identifiers(operator, prev, take)
 }
 
 pub fn dip721_owner_counts() -> usize {
     ledger::with(|ledger| ledger.owners_count() )
 }
 
+pub fn dip721_total_unique_holders() -> Nat {
+    Nat::from(dip721_owner_counts())
+}
+
+/// The number of transactions in the ledger's log, see
+/// [`ledger::Ledger::log_length`].
+pub fn dip721_total_transactions() -> Nat {
+    ledger::with(|ledger| Nat::from(ledger.log_length()))
+}
+
+/// The block at `index` of the ledger's log, seen as a DIP721 transaction.
+pub fn dip721_transaction(index: Nat) -> Result<TxEvent, NftError> {
+    ledger::with(|ledger| {
+        u64::try_from(&index.0)
+            .ok()
+            .and_then(|index| ledger.block(index))
+            .and_then(icrc3::block_tx_event)
+            .ok_or(NftError::TxNotFound)
+    })
+}
+
+pub fn dip721_cycles() -> Nat {
+    Nat::from(ic_cdk::api::canister_balance128())
+}
+
+pub fn dip721_stats() -> Status {
+    Status {
+        total_transactions: dip721_total_transactions(),
+        total_supply: dip721_total_supply(),
+        cycles: dip721_cycles(),
+        total_unique_holders: dip721_total_unique_holders(),
+    }
+}
+
+pub fn dip721_supported_interfaces() -> Vec<SupportedInterface> {
+    vec![
+        SupportedInterface::Approval,
+        SupportedInterface::Mint,
+        SupportedInterface::Burn,
+        SupportedInterface::TransactionHistory,
+    ]
+}
+
 pub fn dip721_set_minter(new_minter: Principal) -> Result<(), CommonError>{
 
-    let caller = ic_cdk::api::caller();
+    let caller = ledger::caller();
     let owner = dip721_custodians();
     // let aid = pid2aid(&caller);
     match valid_minter(caller, &owner) {
         SetMinterResponse::ok => {
-            change_minter_state(|minter| *minter = Principal::from(new_minter));
+            change_minter_state(|minter| *minter = new_minter);
             // MINTER.with(|minter| {
             //     *minter.borrow_mut() = Principal::from(new_minter);
             // });
             Ok(()) 
         },
-        SetMinterResponse::err(e) => return Err(e),
+        SetMinterResponse::err(e) => Err(e),
     }
 }
 
-pub fn dip721_minted_info() -> Vec<Nat> {
-    read_minted_state(|minted| minted.to_owned())
-    // MINTEDID.with(|minted| {
-    //     minted.borrow().to_owned()
-    // })
+/// The minted ids after `prev`, in ascending order, at most `take` of them.
+pub fn dip721_minted_info(prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    read_minted_state(|minted| page(minted, prev, take))
+}
+
+/// The blocks of the ICRC-3 log after `prev`, oldest first, at most `take` of
+/// them.
+pub fn dip721_history(prev: Option<Nat>, take: Option<Nat>) -> Vec<BlockWithId> {
+    ledger::with(|ledger| {
+        let start = prev
+            .map_or(0, |prev| u64::try_from(&prev.0).unwrap_or(u64::MAX).saturating_add(1))
+            .clamp(ledger.log_start, ledger.log_length());
+        let end = start.saturating_add(take_value(take) as u64).min(ledger.log_length());
+        (start..end)
+            .filter_map(|id| {
+                let block = ledger.block(id)?.clone();
+                Some(BlockWithId { id: Nat::from(id), block })
+            })
+            .collect()
+    })
 }
 
 pub fn get_minter() -> Principal {
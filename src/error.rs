@@ -0,0 +1,191 @@
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+
+use crate::status::{TokenAction, TokenStatus};
+use crate::token_identifier::ShikuError;
+use crate::types::{AccountIdentifier, CommonError, CommonError__1, NftError, TransferResponseDetails};
+
+/// The crate-wide error.
+///
+/// Ledger logic returns this type, and each endpoint converts it into the
+/// Candid error variant its standard expects (`NftError` for DIP721,
+/// `CommonError` and `TransferResponseDetails` for EXT). Variants carry over
+/// only where the target has a matching one; `InvalidToken` always holds the
+/// token identifier the caller sent, and anything else becomes `Other`.
+#[derive(Debug, CandidType, Clone, Deserialize, PartialEq, Eq)]
+pub enum ShikuNftError {
+    UnauthorizedOwner,
+    UnauthorizedOperator,
+    OwnerNotFound,
+    OperatorNotFound,
+    TokenNotFound,
+    ExistedNFT,
+    SelfApprove,
+    TxNotFound,
+    ApprovalNotFound,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    InvalidToken(String),
+    Unauthorized(AccountIdentifier),
+    InsufficientBalance,
+    CannotNotify(AccountIdentifier),
+    Rejected,
+    Paused,
+    MetadataFrozen,
+    TokenLocked,
+    Soulbound,
+    MintClosed,
+    SupplyCapReached { max_supply: u64 },
+    MintLimitReached { max_per_account: u64 },
+    InvalidTransition { status: TokenStatus, action: TokenAction },
+    Other(String),
+}
+
+impl Display for ShikuNftError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnauthorizedOwner => write!(f, "caller is not the owner of the token"),
+            Self::UnauthorizedOperator => write!(f, "caller is not the operator of the token"),
+            Self::OwnerNotFound => write!(f, "owner not found"),
+            Self::OperatorNotFound => write!(f, "operator not found"),
+            Self::TokenNotFound => write!(f, "token not found"),
+            Self::ExistedNFT => write!(f, "token already exists"),
+            Self::SelfApprove => write!(f, "cannot approve the caller itself"),
+            Self::TxNotFound => write!(f, "transaction not found"),
+            Self::ApprovalNotFound => write!(f, "approval not found"),
+            Self::TooOld => write!(f, "created_at_time is outside the deduplication window"),
+            Self::CreatedInFuture { ledger_time } => {
+                write!(f, "created_at_time is ahead of the ledger time {}", ledger_time)
+            }
+            Self::InvalidToken(token) => write!(f, "invalid token: {}", token),
+            Self::Unauthorized(aid) => write!(f, "unauthorized account: {}", aid),
+            Self::InsufficientBalance => write!(f, "insufficient balance"),
+            Self::CannotNotify(aid) => write!(f, "cannot notify account: {}", aid),
+            Self::Rejected => write!(f, "rejected"),
+            Self::Paused => write!(f, "the collection is paused"),
+            Self::MetadataFrozen => write!(f, "the metadata is frozen"),
+            Self::TokenLocked => write!(f, "the token is locked"),
+            Self::MintClosed => write!(f, "minting is closed"),
+            Self::SupplyCapReached { max_supply } => {
+                write!(f, "the supply cap of {} tokens is reached", max_supply)
+            }
+            Self::MintLimitReached { max_per_account } => {
+                write!(f, "the account already holds its {} mints", max_per_account)
+            }
+            Self::Soulbound => write!(f, "the token is soulbound and cannot change hands"),
+            Self::InvalidTransition { status, action } => {
+                write!(f, "cannot {} a token that is {}", action, status)
+            }
+            Self::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShikuNftError {}
+
+impl From<NftError> for ShikuNftError {
+    fn from(err: NftError) -> Self {
+        match err {
+            NftError::UnauthorizedOwner => Self::UnauthorizedOwner,
+            NftError::UnauthorizedOperator => Self::UnauthorizedOperator,
+            NftError::OwnerNotFound => Self::OwnerNotFound,
+            NftError::OperatorNotFound => Self::OperatorNotFound,
+            NftError::TokenNotFound => Self::TokenNotFound,
+            NftError::ExistedNFT => Self::ExistedNFT,
+            NftError::SelfApprove => Self::SelfApprove,
+            NftError::TxNotFound => Self::TxNotFound,
+            NftError::Other(msg) => Self::Other(msg),
+        }
+    }
+}
+
+impl From<ShikuNftError> for NftError {
+    fn from(err: ShikuNftError) -> Self {
+        match err {
+            ShikuNftError::UnauthorizedOwner => Self::UnauthorizedOwner,
+            ShikuNftError::UnauthorizedOperator => Self::UnauthorizedOperator,
+            ShikuNftError::OwnerNotFound => Self::OwnerNotFound,
+            ShikuNftError::OperatorNotFound => Self::OperatorNotFound,
+            ShikuNftError::TokenNotFound => Self::TokenNotFound,
+            ShikuNftError::ExistedNFT => Self::ExistedNFT,
+            ShikuNftError::SelfApprove => Self::SelfApprove,
+            ShikuNftError::TxNotFound => Self::TxNotFound,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<CommonError> for ShikuNftError {
+    fn from(err: CommonError) -> Self {
+        match err {
+            CommonError::InvalidToken(token) => Self::InvalidToken(token),
+            CommonError::Other(msg) => Self::Other(msg),
+        }
+    }
+}
+
+impl From<ShikuNftError> for CommonError {
+    fn from(err: ShikuNftError) -> Self {
+        match err {
+            ShikuNftError::InvalidToken(token) => Self::InvalidToken(token),
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<Infallible> for CommonError {
+    fn from(infallible: Infallible) -> Self {
+        match infallible {}
+    }
+}
+
+impl From<CommonError__1> for ShikuNftError {
+    fn from(err: CommonError__1) -> Self {
+        match err {
+            CommonError__1::InvalidToken(token) => Self::InvalidToken(token),
+            CommonError__1::Other(msg) => Self::Other(msg),
+        }
+    }
+}
+
+impl From<ShikuNftError> for CommonError__1 {
+    fn from(err: ShikuNftError) -> Self {
+        match CommonError::from(err) {
+            CommonError::InvalidToken(token) => Self::InvalidToken(token),
+            CommonError::Other(msg) => Self::Other(msg),
+        }
+    }
+}
+
+impl From<TransferResponseDetails> for ShikuNftError {
+    fn from(err: TransferResponseDetails) -> Self {
+        match err {
+            TransferResponseDetails::CannotNotify(aid) => Self::CannotNotify(aid),
+            TransferResponseDetails::InsufficientBalance => Self::InsufficientBalance,
+            TransferResponseDetails::InvalidToken(token) => Self::InvalidToken(token),
+            TransferResponseDetails::Other(msg) => Self::Other(msg),
+            TransferResponseDetails::Rejected => Self::Rejected,
+            TransferResponseDetails::Unauthorized(aid) => Self::Unauthorized(aid),
+        }
+    }
+}
+
+impl From<ShikuNftError> for TransferResponseDetails {
+    fn from(err: ShikuNftError) -> Self {
+        match err {
+            ShikuNftError::CannotNotify(aid) => Self::CannotNotify(aid),
+            ShikuNftError::InsufficientBalance => Self::InsufficientBalance,
+            ShikuNftError::InvalidToken(token) => Self::InvalidToken(token),
+            ShikuNftError::Rejected => Self::Rejected,
+            ShikuNftError::Unauthorized(aid) => Self::Unauthorized(aid),
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<ShikuNftError> for ShikuError {
+    fn from(err: ShikuNftError) -> Self {
+        Self::err(err.to_string())
+    }
+}
@@ -0,0 +1,281 @@
+use crate::icrc7::check_tx_args;
+use crate::ledger::{self, TxKey};
+use crate::{
+    dip721_allowance,
+    pid2aid,
+    Account,
+    CanisterId,
+    CommonError,
+    ShikuNftError,
+    TokenId,
+    TokenRef,
+};
+use ic_cdk::export::candid::Nat;
+use std::convert::TryFrom;
+
+use super::types::*;
+
+pub const EXTENSIONS: [&str; 3] = ["@ext/common", "@ext/nonfungible", "@ext/allowance"];
+
+fn ext_token_id(token: &TokenIdentifier__1) -> Result<TokenId, CommonError> {
+    let token_id =
+        TokenRef::from_token_identifier(token, &CanisterId(ledger::canister_id()))?.to_token_id();
+    ledger::with(|ledger| {
+        ledger
+            .is_token_existed(&token_id)
+            .then_some(token_id)
+            .ok_or_else(|| CommonError::InvalidToken(token.to_owned()))
+    })
+}
+
+fn ext_token_indexes(aid: AccountIdentifier) -> Result<Vec<TokenIndexU32>, CommonError> {
+    let owner = User::address(aid).to_account()?;
+    ledger::with(|ledger| {
+        let mut indexes: Vec<TokenIndexU32> = ledger
+            .owner_token_identifiers(&owner)
+            .map_err(|_| CommonError::Other("No tokens".to_string()))?
+            .iter()
+            .filter_map(|token_id| TokenRef::try_from(token_id).ok().map(TokenIndexU32::from))
+            .collect();
+        indexes.sort_unstable();
+        Ok(indexes)
+    })
+}
+
+/// The default accounts of the users given as principals, which the ledger
+/// links; bare account identifiers stay unlinked.
+fn principal_accounts(users: &[&User]) -> Vec<Account> {
+    users
+        .iter()
+        .filter_map(|user| match user {
+            User::principal(owner) => Some(Account { owner: *owner, subaccount: None }),
+            User::address(_) => None,
+        })
+        .collect()
+}
+
+pub fn ext_extensions() -> Vec<String> {
+    EXTENSIONS.iter().map(|ext| ext.to_string()).collect()
+}
+
+pub fn ext_balance(request: BalanceRequest) -> BalanceResponse {
+    let balance = ext_token_id(&request.token).and_then(|token_id| {
+        let user = request.user.to_account()?;
+        let owner = ledger::with(|ledger| ledger.owner_of(&token_id))?;
+        Ok(Nat::from((owner == Some(user)) as u32))
+    });
+    match balance {
+        Ok(balance) => BalanceResponse::ok(balance),
+        Err(e) => BalanceResponse::err(ShikuNftError::from(e).into()),
+    }
+}
+
+pub fn ext_bearer(token: TokenIdentifier__1) -> Result__1_1 {
+    let bearer = ext_token_id(&token).and_then(|token_id| {
+        ledger::with(|ledger| ledger.owner_of(&token_id))?
+            .map(|owner| owner.to_hex())
+            .ok_or_else(|| CommonError::InvalidToken(token.to_owned()))
+    });
+    match bearer {
+        Ok(aid) => Result__1_1::ok(aid),
+        Err(e) => Result__1_1::err(e),
+    }
+}
+
+pub fn ext_metadata(token: TokenIdentifier__1) -> Result__1 {
+    match ext_token_id(&token) {
+        Ok(_) => Result__1::ok(TokenMetaDataExt::nonfungible(MetaDataNonFungibleDetails {
+            metadata: None,
+        })),
+        Err(e) => Result__1::err(e),
+    }
+}
+
+pub fn ext_supply(token: TokenIdentifier__1) -> Result_2 {
+    match ext_token_id(&token) {
+        Ok(token_id) => {
+            let burned = ledger::with(|ledger| {
+                ledger
+                    .token_metadata(&token_id)
+                    .map(|token_metadata| token_metadata.is_burned)
+            });
+            Result_2::ok(Nat::from((burned == Ok(false)) as u32))
+        }
+        Err(e) => Result_2::err(e),
+    }
+}
+
+pub fn ext_get_registry() -> Vec<(TokenIndexU32, AccountIdentifier__1)> {
+    ledger::with(|ledger| {
+        let mut registry: Vec<(TokenIndexU32, AccountIdentifier__1)> = ledger
+            .tokens
+            .values()
+            .filter_map(|token_metadata| {
+                let index = TokenRef::try_from(&token_metadata.token_identifier).ok()?.index();
+                Some((index, token_metadata.owner?.to_hex()))
+            })
+            .collect();
+        registry.sort_unstable_by_key(|(index, _)| *index);
+        registry
+    })
+}
+
+pub fn ext_get_tokens() -> Vec<(TokenIndexU32, TokenMetaDataExt)> {
+    ext_get_registry()
+        .into_iter()
+        .map(|(index, _)| {
+            let metadata = TokenMetaDataExt::nonfungible(MetaDataNonFungibleDetails {
+                metadata: None,
+            });
+            (index, metadata)
+        })
+        .collect()
+}
+
+pub fn ext_tokens(aid: AccountIdentifier) -> Result_1 {
+    match ext_token_indexes(aid) {
+        Ok(indexes) => Result_1::ok(indexes),
+        Err(e) => Result_1::err(e),
+    }
+}
+
+pub fn ext_tokens_ext(aid: AccountIdentifier) -> NFTResult {
+    match ext_token_indexes(aid) {
+        Ok(indexes) => NFTResult::ok(
+            indexes
+                .into_iter()
+                .map(|index| ResultDetail(index, None, None))
+                .collect(),
+        ),
+        Err(e) => NFTResult::err(e),
+    }
+}
+
+/// Transfers `request.token`. With `created_at_time`, a retry of the same
+/// request, memo included, within the ICRC deduplication window succeeds
+/// without transferring again; see [`ledger::Ledger::deduplicate`].
+pub fn ext_transfer(request: TransferRequest, created_at_time: Option<u64>) -> TransferResponse {
+    let transfer = || -> Result<Balance, ShikuNftError> {
+        if request.amount != 1 {
+            return Err(ShikuNftError::Other("Must use amount of 1".to_string()));
+        }
+        let now = ledger::now();
+        check_tx_args(None, created_at_time, now)?;
+        let token_id = ext_token_id(&request.token)?;
+        let from = request.from.to_account()?;
+        let to = request.to.to_account()?;
+        let caller = ledger::caller();
+        let key = created_at_time.map(|created_at_time| {
+            TxKey::new(&caller, "transfer", &request, created_at_time)
+        });
+        let caller = pid2aid(&caller)?;
+        ledger::with_mut(|ledger| {
+            ledger.deduplicate(key, now, Ok, |ledger| {
+                if ledger.owner_of(&token_id)? != Some(from) {
+                    return Err(ShikuNftError::Unauthorized(from.to_hex()));
+                }
+                if caller != from && !ledger.is_approved(&token_id, &caller, now) {
+                    return Err(ShikuNftError::Unauthorized(caller.to_hex()));
+                }
+                let accounts = principal_accounts(&[&request.from, &request.to]);
+                ledger.linking(&accounts, |ledger| ledger.transfer(caller, &token_id, Some(to)))?;
+                Ok(ledger.tx_index())
+            })
+        })?;
+        Ok(request.amount.clone())
+    };
+    match transfer() {
+        Ok(balance) => TransferResponse::ok(balance),
+        Err(e) => TransferResponse::err(e.into()),
+    }
+}
+
+/// Moves every token of `token_ids` from `from` to `to`, all or nothing.
+/// The transfers are logged as one contiguous group of blocks.
+fn transfer_tokens(
+    from: &User,
+    to: &User,
+    amount: &Balance,
+    token_ids: Vec<TokenId>,
+) -> Result<Balance, ShikuNftError> {
+    if token_ids.is_empty() || *amount != token_ids.len() {
+        return Err(ShikuNftError::Other(
+            "Must use an amount equal to the number of tokens".to_string(),
+        ));
+    }
+    let (from_aid, to_aid) = (from.to_account()?, to.to_account()?);
+    let caller = pid2aid(&ledger::caller())?;
+    let now = ledger::now();
+    ledger::with_mut(|ledger| {
+        for token_id in &token_ids {
+            if ledger.owner_of(token_id)? != Some(from_aid) {
+                return Err(ShikuNftError::Unauthorized(from_aid.to_hex()));
+            }
+            if caller != from_aid && !ledger.is_approved(token_id, &caller, now) {
+                return Err(ShikuNftError::Unauthorized(caller.to_hex()));
+            }
+        }
+        let transfers = token_ids.iter().map(|token_id| (token_id.clone(), Some(to_aid)));
+        ledger
+            .linking(&principal_accounts(&[from, to]), |ledger| {
+                ledger.transfer_batch(caller, transfers.collect())
+            })
+            .map_err(|(_, e)| e)?;
+        ledger.tx_indexes(token_ids.len());
+        Ok(amount.clone())
+    })
+}
+
+/// Transfers every token of `request.token_list`, all or nothing.
+pub fn ext_batch_transfer(request: TransferRequestV2) -> TransferResponse {
+    let token_ids = request
+        .token_list
+        .iter()
+        .map(|token| Ok(ext_token_id(token)?))
+        .collect::<Result<Vec<_>, ShikuNftError>>();
+    let transferred = token_ids.and_then(|token_ids| {
+        transfer_tokens(&request.from, &request.to, &request.amount, token_ids)
+    });
+    match transferred {
+        Ok(balance) => TransferResponse::ok(balance),
+        Err(e) => TransferResponse::err(e.into()),
+    }
+}
+
+/// Transfers the tokens at positions `request.start..request.end` of
+/// `request.class`, all or nothing.
+pub fn ext_transfer_range(request: TransferRequestV1) -> TransferResponse {
+    let token_ids =
+        ledger::with(|ledger| ledger.class_range(&request.class, request.start, request.end));
+    let transferred = token_ids.and_then(|token_ids| {
+        transfer_tokens(&request.from, &request.to, &request.amount, token_ids)
+    });
+    match transferred {
+        Ok(balance) => TransferResponse::ok(balance),
+        Err(e) => TransferResponse::err(e.into()),
+    }
+}
+
+pub fn ext_allowance(request: AllowanceRequest) -> Result_2 {
+    let allowance = ext_token_id(&request.token).and_then(|token_id| {
+        let owner = request.owner.to_account()?;
+        dip721_allowance(&owner, &pid2aid(&request.spender)?, &token_id)
+    });
+    match allowance {
+        Ok(allowance) => Result_2::ok(allowance),
+        Err(e) => Result_2::err(e),
+    }
+}
+
+pub fn ext_approve(request: ApproveRequest) -> Result<Nat, CommonError> {
+    let token_id = ext_token_id(&request.token)?;
+    let caller = pid2aid(&ledger::caller())?;
+    let spender = pid2aid(&request.spender)?;
+    ledger::with_mut(|ledger| {
+        if ledger.owner_of(&token_id)? != Some(caller) {
+            return Err(ShikuNftError::UnauthorizedOwner.into());
+        }
+        ledger.approve(caller, &token_id, Some(spender))?;
+        Ok(ledger.tx_index())
+    })
+}
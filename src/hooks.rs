@@ -0,0 +1,243 @@
+use std::cell::RefCell;
+
+use crate::error::ShikuNftError;
+use crate::ledger::Ledger;
+use crate::status::{TokenAction, TokenStatus};
+use crate::types::{AccountIdentifier_shiku, TokenId};
+
+thread_local! {
+    static HOOKS: RefCell<Vec<Box<dyn LedgerHooks>>> = RefCell::new(Vec::new());
+    static COMMITTED: RefCell<Vec<LedgerEvent>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A change applied to the ledger.
+#[derive(Debug, Clone)]
+pub enum LedgerEvent {
+    Mint {
+        token_identifier: TokenId,
+        minted_by: AccountIdentifier_shiku,
+        to: Option<AccountIdentifier_shiku>,
+    },
+    Transfer {
+        token_identifier: TokenId,
+        transferred_by: AccountIdentifier_shiku,
+        from: Option<AccountIdentifier_shiku>,
+        to: Option<AccountIdentifier_shiku>,
+    },
+    Approve {
+        token_identifier: TokenId,
+        approved_by: AccountIdentifier_shiku,
+        operator: Option<AccountIdentifier_shiku>,
+    },
+    Burn {
+        token_identifier: TokenId,
+        burned_by: AccountIdentifier_shiku,
+        owner: Option<AccountIdentifier_shiku>,
+    },
+    /// An approval for every token of `approved_by`, or without an operator
+    /// the revocation of such approvals.
+    ApproveCollection {
+        approved_by: AccountIdentifier_shiku,
+        operator: Option<AccountIdentifier_shiku>,
+    },
+    /// An action that took the token to `status` without moving it.
+    ChangeStatus {
+        token_identifier: TokenId,
+        changed_by: AccountIdentifier_shiku,
+        action: TokenAction,
+        status: TokenStatus,
+    },
+}
+
+/// Side effects of ledger mutations.
+///
+/// `before_*` callbacks run once the operation has been validated and before
+/// anything is written; returning an error vetoes the operation and leaves the
+/// ledger untouched. They get read access to the ledger, which is borrowed
+/// mutably at that point.
+///
+/// `after_*` callbacks run once the ledger borrow has been released, so they
+/// are free to call [`crate::ledger::with`] or [`crate::ledger::with_mut`].
+///
+/// Hooks live on the heap: register them again in `post_upgrade`.
+pub trait LedgerHooks {
+    fn before_mint(
+        &self,
+        _ledger: &Ledger,
+        _token_identifier: &TokenId,
+        _minted_by: &AccountIdentifier_shiku,
+        _to: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        Ok(())
+    }
+
+    fn after_mint(
+        &self,
+        _token_identifier: &TokenId,
+        _minted_by: &AccountIdentifier_shiku,
+        _to: Option<&AccountIdentifier_shiku>,
+    ) {
+    }
+
+    fn before_transfer(
+        &self,
+        _ledger: &Ledger,
+        _token_identifier: &TokenId,
+        _transferred_by: &AccountIdentifier_shiku,
+        _from: Option<&AccountIdentifier_shiku>,
+        _to: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        Ok(())
+    }
+
+    fn after_transfer(
+        &self,
+        _token_identifier: &TokenId,
+        _transferred_by: &AccountIdentifier_shiku,
+        _from: Option<&AccountIdentifier_shiku>,
+        _to: Option<&AccountIdentifier_shiku>,
+    ) {
+    }
+
+    fn before_approve(
+        &self,
+        _ledger: &Ledger,
+        _token_identifier: &TokenId,
+        _approved_by: &AccountIdentifier_shiku,
+        _operator: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        Ok(())
+    }
+
+    fn after_approve(
+        &self,
+        _token_identifier: &TokenId,
+        _approved_by: &AccountIdentifier_shiku,
+        _operator: Option<&AccountIdentifier_shiku>,
+    ) {
+    }
+
+    fn before_burn(
+        &self,
+        _ledger: &Ledger,
+        _token_identifier: &TokenId,
+        _burned_by: &AccountIdentifier_shiku,
+        _owner: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        Ok(())
+    }
+
+    fn after_burn(
+        &self,
+        _token_identifier: &TokenId,
+        _burned_by: &AccountIdentifier_shiku,
+        _owner: Option<&AccountIdentifier_shiku>,
+    ) {
+    }
+
+    fn before_approve_collection(
+        &self,
+        _ledger: &Ledger,
+        _approved_by: &AccountIdentifier_shiku,
+        _operator: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        Ok(())
+    }
+
+    fn after_approve_collection(
+        &self,
+        _approved_by: &AccountIdentifier_shiku,
+        _operator: Option<&AccountIdentifier_shiku>,
+    ) {
+    }
+
+    fn before_change_status(
+        &self,
+        _ledger: &Ledger,
+        _token_identifier: &TokenId,
+        _changed_by: &AccountIdentifier_shiku,
+        _action: TokenAction,
+        _status: TokenStatus,
+    ) -> Result<(), ShikuNftError> {
+        Ok(())
+    }
+
+    fn after_change_status(
+        &self,
+        _token_identifier: &TokenId,
+        _changed_by: &AccountIdentifier_shiku,
+        _action: TokenAction,
+        _status: TokenStatus,
+    ) {
+    }
+}
+
+pub fn register_hooks<H: LedgerHooks + 'static>(hooks: H) {
+    HOOKS.with(|registered| registered.borrow_mut().push(Box::new(hooks)));
+}
+
+pub fn clear_hooks() {
+    HOOKS.with(|registered| registered.borrow_mut().clear());
+}
+
+/// Runs every `before_*` hook for `event`, stopping at the first veto.
+pub(crate) fn before(ledger: &Ledger, event: &LedgerEvent) -> Result<(), ShikuNftError> {
+    HOOKS.with(|registered| {
+        registered.borrow().iter().try_for_each(|hooks| match event {
+            LedgerEvent::Mint { token_identifier, minted_by, to } => {
+                hooks.before_mint(ledger, token_identifier, minted_by, to.as_ref())
+            }
+            LedgerEvent::Transfer { token_identifier, transferred_by, from, to } => hooks
+                .before_transfer(ledger, token_identifier, transferred_by, from.as_ref(), to.as_ref()),
+            LedgerEvent::Approve { token_identifier, approved_by, operator } => {
+                hooks.before_approve(ledger, token_identifier, approved_by, operator.as_ref())
+            }
+            LedgerEvent::Burn { token_identifier, burned_by, owner } => {
+                hooks.before_burn(ledger, token_identifier, burned_by, owner.as_ref())
+            }
+            LedgerEvent::ApproveCollection { approved_by, operator } => {
+                hooks.before_approve_collection(ledger, approved_by, operator.as_ref())
+            }
+            LedgerEvent::ChangeStatus { token_identifier, changed_by, action, status } => {
+                hooks.before_change_status(ledger, token_identifier, changed_by, *action, *status)
+            }
+        })
+    })
+}
+
+/// Queues `event` for the `after_*` hooks.
+pub(crate) fn committed(event: LedgerEvent) {
+    COMMITTED.with(|committed| committed.borrow_mut().push(event));
+}
+
+/// Runs the `after_*` hooks for every queued event.
+pub(crate) fn run_after_hooks() {
+    while let Some(event) = COMMITTED.with(|committed| {
+        let mut committed = committed.borrow_mut();
+        (!committed.is_empty()).then(|| committed.remove(0))
+    }) {
+        HOOKS.with(|registered| {
+            for hooks in registered.borrow().iter() {
+                match &event {
+                    LedgerEvent::Mint { token_identifier, minted_by, to } => {
+                        hooks.after_mint(token_identifier, minted_by, to.as_ref())
+                    }
+                    LedgerEvent::Transfer { token_identifier, transferred_by, from, to } => hooks
+                        .after_transfer(token_identifier, transferred_by, from.as_ref(), to.as_ref()),
+                    LedgerEvent::Approve { token_identifier, approved_by, operator } => {
+                        hooks.after_approve(token_identifier, approved_by, operator.as_ref())
+                    }
+                    LedgerEvent::Burn { token_identifier, burned_by, owner } => {
+                        hooks.after_burn(token_identifier, burned_by, owner.as_ref())
+                    }
+                    LedgerEvent::ApproveCollection { approved_by, operator } => {
+                        hooks.after_approve_collection(approved_by, operator.as_ref())
+                    }
+                    LedgerEvent::ChangeStatus { token_identifier, changed_by, action, status } => {
+                        hooks.after_change_status(token_identifier, changed_by, *action, *status)
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,353 @@
+use candid::parser::types::FuncMode;
+use candid::types::{Function, Serializer, Type};
+use candid::Func;
+use ic_cdk::export::candid::{CandidType, Deserialize, Nat};
+use ic_cdk::export::Principal;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+
+use crate::certification;
+use crate::hooks::LedgerEvent;
+use crate::icrc7::Value;
+use crate::ledger::{self, Ledger};
+use crate::types::{AccountIdentifier_shiku, GeneralValue, TokenId, TxEvent};
+
+/// The most blocks a single `icrc3_get_blocks` call returns.
+pub const MAX_BLOCKS_PER_RESPONSE: u64 = 1_000;
+
+pub const SUPPORTED_BLOCK_TYPES: [(&str, &str); 9] = [
+    ("7mint", "https://github.com/dfinity/ICRC/ICRCs/ICRC-7"),
+    ("7burn", "https://github.com/dfinity/ICRC/ICRCs/ICRC-7"),
+    ("7xfer", "https://github.com/dfinity/ICRC/ICRCs/ICRC-7"),
+    ("37approve", "https://github.com/dfinity/ICRC/ICRCs/ICRC-37"),
+    ("37revoke", "https://github.com/dfinity/ICRC/ICRCs/ICRC-37"),
+    ("37approve_coll", "https://github.com/dfinity/ICRC/ICRCs/ICRC-37"),
+    ("37revoke_coll", "https://github.com/dfinity/ICRC/ICRCs/ICRC-37"),
+    ("37xfer", "https://github.com/dfinity/ICRC/ICRCs/ICRC-37"),
+    ("dip721status", "https://github.com/Psychedelic/DIP721"),
+];
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: Value,
+}
+
+/// `func (vec GetBlocksArgs) -> (GetBlocksResult) query`
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetBlocksCallback(pub Func);
+
+impl CandidType for GetBlocksCallback {
+    fn _ty() -> Type {
+        Type::Func(Function {
+            modes: vec![FuncMode::Query],
+            args: vec![Vec::<GetBlocksArgs>::ty()],
+            rets: vec![GetBlocksResult::ty()],
+        })
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.0.idl_serialize(serializer)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: GetBlocksCallback,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetArchivesArgs {
+    pub from: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ICRC3ArchiveInfo {
+    pub canister_id: Principal,
+    pub start: Nat,
+    pub end: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ICRC3DataCertificate {
+    /// The system certificate, whose `certified_data` is the tree's root hash.
+    pub certificate: Vec<u8>,
+    /// The CBOR-encoded witness, see [`crate::certification`].
+    pub hash_tree: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SupportedBlockType {
+    pub block_type: String,
+    pub url: String,
+}
+
+/// The representation-independent hash of `value`, as specified by ICRC-3.
+pub fn hash_value(value: &Value) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    match value {
+        Value::Blob(bytes) => hasher.update(bytes),
+        Value::Text(text) => hasher.update(text.as_bytes()),
+        Value::Nat(nat) => {
+            let mut leb = Vec::new();
+            nat.encode(&mut leb).expect("writing to a Vec cannot fail");
+            hasher.update(leb);
+        }
+        Value::Int(int) => {
+            let mut sleb = Vec::new();
+            int.encode(&mut sleb).expect("writing to a Vec cannot fail");
+            hasher.update(sleb);
+        }
+        Value::Array(values) => {
+            for value in values {
+                hasher.update(hash_value(value));
+            }
+        }
+        Value::Map(entries) => {
+            let mut pairs: Vec<Vec<u8>> = entries
+                .iter()
+                .map(|(key, value)| {
+                    let mut pair = Sha256::digest(key.as_bytes()).to_vec();
+                    pair.extend_from_slice(&hash_value(value));
+                    pair
+                })
+                .collect();
+            pairs.sort();
+            for pair in pairs {
+                hasher.update(pair);
+            }
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Encodes an account as the `[owner, subaccount]` array ICRC-3 expects.
+///
+/// Accounts the ledger only knows by their identifier, such as DIP721 owners,
+/// are encoded as the 32-byte account identifier blob instead.
+fn account_value(ledger: &Ledger, aid: &AccountIdentifier_shiku) -> Value {
+    match ledger.account_of(aid) {
+        Some(account) => {
+            let mut parts = vec![Value::Blob(account.owner.as_slice().to_vec())];
+            if let Some(subaccount) = account.subaccount {
+                parts.push(Value::Blob(subaccount.0.to_vec()));
+            }
+            Value::Array(parts)
+        }
+        None => Value::Blob(aid.to_address().to_vec()),
+    }
+}
+
+/// Decodes an account encoded by [`account_value`].
+fn value_account(value: &Value) -> Option<AccountIdentifier_shiku> {
+    match value {
+        Value::Blob(address) => {
+            AccountIdentifier_shiku::from_address(address.as_slice().try_into().ok()?).ok()
+        }
+        Value::Array(parts) => {
+            let owner = match parts.first()? {
+                Value::Blob(owner) => Principal::try_from_slice(owner).ok()?,
+                _ => return None,
+            };
+            let subaccount = match parts.get(1) {
+                Some(Value::Blob(subaccount)) => {
+                    ic_ledger_types::Subaccount(subaccount.as_slice().try_into().ok()?)
+                }
+                Some(_) => return None,
+                None => ic_ledger_types::DEFAULT_SUBACCOUNT,
+            };
+            Some(AccountIdentifier_shiku::new(&owner, &subaccount))
+        }
+        _ => None,
+    }
+}
+
+impl From<&Value> for GeneralValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Blob(bytes) => Self::BlobContent(bytes.clone()),
+            Value::Text(text) => Self::TextContent(text.clone()),
+            Value::Nat(nat) => Self::NatContent(nat.clone()),
+            Value::Int(int) => Self::IntContent(int.clone()),
+            Value::Array(values) => Self::NestedContent(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(position, value)| (position.to_string(), Self::from(value)))
+                    .collect(),
+            ),
+            Value::Map(entries) => Self::NestedContent(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Reads a block built by [`event_block`] back as a DIP721 transaction.
+pub(crate) fn block_tx_event(block: &Value) -> Option<TxEvent> {
+    fn field<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+        match map {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    let operation = match field(block, "btype")? {
+        Value::Text(btype) => btype.clone(),
+        _ => return None,
+    };
+    let time = match field(block, "ts")? {
+        Value::Nat(ts) => u64::try_from(&ts.0).ok()?,
+        _ => return None,
+    };
+    let tx = field(block, "tx")?;
+    let caller = match operation.as_str() {
+        "37xfer" => field(tx, "spender"),
+        _ => field(tx, "from"),
+    };
+    let caller = caller.and_then(value_account)?;
+    let details = match tx {
+        Value::Map(entries) => entries
+            .iter()
+            .map(|(key, value)| (key.clone(), GeneralValue::from(value)))
+            .collect(),
+        _ => return None,
+    };
+    Some(TxEvent { time, operation, details, caller })
+}
+
+/// Builds the block recording `event`, chained to the block hashed `phash`.
+pub(crate) fn event_block(
+    ledger: &Ledger,
+    event: &LedgerEvent,
+    phash: Option<[u8; 32]>,
+    ts: u64,
+) -> Value {
+    let account = |aid: &AccountIdentifier_shiku| account_value(ledger, aid);
+    let tid = |token_identifier: &TokenId| ("tid".to_string(), Value::Nat(token_identifier.clone()));
+    let (btype, mut tx) = match event {
+        LedgerEvent::Mint { token_identifier, minted_by, to } => {
+            let mut tx = vec![tid(token_identifier), ("from".to_string(), account(minted_by))];
+            tx.extend(to.iter().map(|to| ("to".to_string(), account(to))));
+            ("7mint", tx)
+        }
+        LedgerEvent::Transfer { token_identifier, transferred_by, from, to } => {
+            let mut tx = vec![tid(token_identifier)];
+            tx.extend(from.iter().map(|from| ("from".to_string(), account(from))));
+            tx.extend(to.iter().map(|to| ("to".to_string(), account(to))));
+            if from.as_ref() == Some(transferred_by) {
+                ("7xfer", tx)
+            } else {
+                tx.push(("spender".to_string(), account(transferred_by)));
+                ("37xfer", tx)
+            }
+        }
+        LedgerEvent::Approve { token_identifier, approved_by, operator } => {
+            let mut tx = vec![tid(token_identifier), ("from".to_string(), account(approved_by))];
+            match operator {
+                Some(operator) => {
+                    tx.push(("spender".to_string(), account(operator)));
+                    ("37approve", tx)
+                }
+                None => ("37revoke", tx),
+            }
+        }
+        LedgerEvent::Burn { token_identifier, owner, .. } => {
+            let mut tx = vec![tid(token_identifier)];
+            tx.extend(owner.iter().map(|owner| ("from".to_string(), account(owner))));
+            ("7burn", tx)
+        }
+        LedgerEvent::ApproveCollection { approved_by, operator } => {
+            let mut tx = vec![("from".to_string(), account(approved_by))];
+            match operator {
+                Some(operator) => {
+                    tx.push(("spender".to_string(), account(operator)));
+                    ("37approve_coll", tx)
+                }
+                None => ("37revoke_coll", tx),
+            }
+        }
+        LedgerEvent::ChangeStatus { token_identifier, changed_by, action, status } => {
+            let tx = vec![
+                tid(token_identifier),
+                ("from".to_string(), account(changed_by)),
+                ("action".to_string(), Value::Text(action.to_string())),
+                ("status".to_string(), Value::Text(status.to_string())),
+            ];
+            ("dip721status", tx)
+        }
+    };
+    tx.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut block = Vec::new();
+    if let Some(phash) = phash {
+        block.push(("phash".to_string(), Value::Blob(phash.to_vec())));
+    }
+    block.push(("btype".to_string(), Value::Text(btype.to_string())));
+    block.push(("ts".to_string(), Value::Nat(ts.into())));
+    block.push(("tx".to_string(), Value::Map(tx)));
+    Value::Map(block)
+}
+
+/// Returns the requested ranges of the block log, at most
+/// [`MAX_BLOCKS_PER_RESPONSE`] blocks in total. Nothing is ever archived, and
+/// blocks from before [`Ledger::log_start`] were never kept.
+pub fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    ledger::with(|ledger| {
+        let log_length = ledger.log_length();
+        let mut budget = MAX_BLOCKS_PER_RESPONSE;
+        let mut blocks = Vec::new();
+        for arg in args {
+            let start = u64::try_from(&arg.start.0).unwrap_or(u64::MAX).min(log_length);
+            let length = u64::try_from(&arg.length.0).unwrap_or(u64::MAX).min(budget);
+            let end = start.saturating_add(length).min(log_length);
+            let start = start.max(ledger.log_start).min(end);
+            blocks.extend((start..end).filter_map(|id| {
+                let block = ledger.block(id)?.clone();
+                Some(BlockWithId { id: Nat::from(id), block })
+            }));
+            budget -= end - start;
+        }
+        GetBlocksResult {
+            log_length: Nat::from(log_length),
+            blocks,
+            archived_blocks: Vec::new(),
+        }
+    })
+}
+
+pub fn icrc3_get_archives(_args: GetArchivesArgs) -> Vec<ICRC3ArchiveInfo> {
+    Vec::new()
+}
+
+/// Certifies the index and hash of the last block. Only available in query
+/// calls; returns `None` otherwise.
+pub fn icrc3_get_tip_certificate() -> Option<ICRC3DataCertificate> {
+    certification::certificate(ledger::with(certification::tip_witness))
+}
+
+pub fn icrc3_supported_block_types() -> Vec<SupportedBlockType> {
+    SUPPORTED_BLOCK_TYPES
+        .iter()
+        .map(|(block_type, url)| SupportedBlockType {
+            block_type: block_type.to_string(),
+            url: url.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,480 @@
+use ic_cdk::export::candid::{CandidType, Deserialize, Nat};
+
+use crate::account_identifier::Account;
+use crate::error::ShikuNftError;
+use crate::icrc7::{check_tx_args, icrc_error, take_value, Value};
+use crate::ledger::{self, TxKey};
+use crate::types::{AccountIdentifier_shiku, TokenId};
+
+pub const MAX_APPROVALS_PER_TOKEN_OR_COLLECTION: u64 = 10;
+pub const MAX_REVOKE_APPROVALS: u64 = 20;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ApprovalInfo {
+    pub spender: Account,
+    pub from_subaccount: Option<ic_ledger_types::Subaccount>,
+    pub expires_at: Option<u64>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: u64,
+}
+
+impl ApprovalInfo {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    pub fn spender_aid(&self) -> AccountIdentifier_shiku {
+        AccountIdentifier_shiku::from(self.spender)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokenApproval {
+    pub token_id: TokenId,
+    pub approval_info: ApprovalInfo,
+}
+
+pub type CollectionApproval = ApprovalInfo;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ApproveTokenArg {
+    pub token_id: TokenId,
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ApproveTokenError {
+    InvalidSpender,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+pub type ApproveTokenResult = Result<Nat, ApproveTokenError>;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ApproveCollectionArg {
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ApproveCollectionError {
+    InvalidSpender,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+pub type ApproveCollectionResult = Result<Nat, ApproveCollectionError>;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RevokeTokenApprovalArg {
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<ic_ledger_types::Subaccount>,
+    pub token_id: TokenId,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RevokeTokenApprovalError {
+    ApprovalDoesNotExist,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+pub type RevokeTokenApprovalResponse = Result<Nat, RevokeTokenApprovalError>;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RevokeCollectionApprovalArg {
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<ic_ledger_types::Subaccount>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RevokeCollectionApprovalError {
+    ApprovalDoesNotExist,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+pub type RevokeCollectionApprovalResult = Result<Nat, RevokeCollectionApprovalError>;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct IsApprovedArg {
+    pub spender: Account,
+    pub from_subaccount: Option<ic_ledger_types::Subaccount>,
+    pub token_id: TokenId,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferFromArg {
+    pub spender_subaccount: Option<ic_ledger_types::Subaccount>,
+    pub from: Account,
+    pub to: Account,
+    pub token_id: TokenId,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TransferFromError {
+    InvalidRecipient,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+pub type TransferFromResult = Result<Nat, TransferFromError>;
+
+icrc_error!(
+    ApproveTokenError,
+    ApproveCollectionError,
+    RevokeTokenApprovalError,
+    RevokeCollectionApprovalError,
+    TransferFromError,
+);
+
+/// Sorts `approvals` by spender and keeps the page after `prev`.
+fn paginate_approvals<T: Clone>(
+    mut approvals: Vec<T>,
+    spender_of: impl Fn(&T) -> AccountIdentifier_shiku,
+    prev: Option<AccountIdentifier_shiku>,
+    take: Option<Nat>,
+) -> Vec<T> {
+    approvals.sort_unstable_by_key(|approval| spender_of(approval).to_hex());
+    approvals
+        .into_iter()
+        .filter(|approval| prev.is_none_or(|prev| spender_of(approval).to_hex() > prev.to_hex()))
+        .take(take_value(take))
+        .collect()
+}
+
+pub fn icrc37_metadata() -> Vec<(String, Value)> {
+    vec![
+        (
+            "icrc37:max_approvals_per_token_or_collection".to_string(),
+            Value::Nat(MAX_APPROVALS_PER_TOKEN_OR_COLLECTION.into()),
+        ),
+        (
+            "icrc37:max_revoke_approvals".to_string(),
+            Value::Nat(MAX_REVOKE_APPROVALS.into()),
+        ),
+    ]
+}
+
+pub fn icrc37_max_approvals_per_token_or_collection() -> Option<Nat> {
+    Some(MAX_APPROVALS_PER_TOKEN_OR_COLLECTION.into())
+}
+
+pub fn icrc37_max_revoke_approvals() -> Option<Nat> {
+    Some(MAX_REVOKE_APPROVALS.into())
+}
+
+fn approve_token(approver: Account, arg: ApproveTokenArg, now: u64) -> ApproveTokenResult {
+    let approval = arg.approval_info;
+    check_tx_args(approval.memo.as_ref(), Some(approval.created_at_time), now)?;
+    let approved_by = AccountIdentifier_shiku::from(approver);
+    if approval.spender_aid() == approved_by {
+        return Err(ApproveTokenError::InvalidSpender);
+    }
+    ledger::with_mut(|ledger| {
+        let owner = ledger
+            .token_metadata(&arg.token_id)
+            .ok()
+            .filter(|token_metadata| !token_metadata.is_burned)
+            .ok_or(ApproveTokenError::NonExistingTokenId)?
+            .owner;
+        if owner != Some(approved_by) {
+            return Err(ApproveTokenError::Unauthorized);
+        }
+        ledger
+            .linking(&[approver, approval.spender], |ledger| {
+                ledger.approve_token(approved_by, &arg.token_id, approval, now)
+            })
+            .map_err(|e| ApproveTokenError::generic(1, e))?;
+        Ok(ledger.tx_index())
+    })
+}
+
+/// Approves each spender for one token held by the caller's `from_subaccount`.
+pub fn icrc37_approve_tokens(args: Vec<ApproveTokenArg>) -> Vec<Option<ApproveTokenResult>> {
+    if args.len() as u64 > crate::icrc7::MAX_UPDATE_BATCH_SIZE {
+        let err = ApproveTokenError::batch_too_large(crate::icrc7::MAX_UPDATE_BATCH_SIZE);
+        return vec![Some(Err(err))];
+    }
+    let (caller, now) = (ledger::caller(), ledger::now());
+    args.into_iter()
+        .map(|arg| {
+            let approver = Account {
+                owner: caller,
+                subaccount: arg.approval_info.from_subaccount,
+            };
+            Some(approve_token(approver, arg, now))
+        })
+        .collect()
+}
+
+fn approve_collection(
+    approver: Account,
+    arg: ApproveCollectionArg,
+    now: u64,
+) -> ApproveCollectionResult {
+    let approval = arg.approval_info;
+    check_tx_args(approval.memo.as_ref(), Some(approval.created_at_time), now)?;
+    let approved_by = AccountIdentifier_shiku::from(approver);
+    if approval.spender_aid() == approved_by {
+        return Err(ApproveCollectionError::InvalidSpender);
+    }
+    ledger::with_mut(|ledger| {
+        ledger
+            .linking(&[approver, approval.spender], |ledger| {
+                ledger.approve_collection(approved_by, approval, now)
+            })
+            .map_err(|e| ApproveCollectionError::generic(1, e))?;
+        Ok(ledger.tx_index())
+    })
+}
+
+/// Approves each spender for every token held by the caller's
+/// `from_subaccount`, now and in the future.
+pub fn icrc37_approve_collection(
+    args: Vec<ApproveCollectionArg>,
+) -> Vec<Option<ApproveCollectionResult>> {
+    if args.len() as u64 > crate::icrc7::MAX_UPDATE_BATCH_SIZE {
+        let err = ApproveCollectionError::batch_too_large(crate::icrc7::MAX_UPDATE_BATCH_SIZE);
+        return vec![Some(Err(err))];
+    }
+    let (caller, now) = (ledger::caller(), ledger::now());
+    args.into_iter()
+        .map(|arg| {
+            let approver = Account {
+                owner: caller,
+                subaccount: arg.approval_info.from_subaccount,
+            };
+            Some(approve_collection(approver, arg, now))
+        })
+        .collect()
+}
+
+fn revoke_token_approvals(
+    revoker: Account,
+    arg: RevokeTokenApprovalArg,
+    now: u64,
+) -> RevokeTokenApprovalResponse {
+    check_tx_args(arg.memo.as_ref(), arg.created_at_time, now)?;
+    let revoked_by = AccountIdentifier_shiku::from(revoker);
+    ledger::with_mut(|ledger| {
+        let owner = ledger
+            .token_metadata(&arg.token_id)
+            .ok()
+            .filter(|token_metadata| !token_metadata.is_burned)
+            .ok_or(RevokeTokenApprovalError::NonExistingTokenId)?
+            .owner;
+        if owner != Some(revoked_by) {
+            return Err(RevokeTokenApprovalError::Unauthorized);
+        }
+        let spender = arg.spender.map(AccountIdentifier_shiku::from);
+        match ledger.revoke_token_approvals(revoked_by, &arg.token_id, spender, now) {
+            Ok(()) => Ok(ledger.tx_index()),
+            Err(ShikuNftError::ApprovalNotFound) => {
+                Err(RevokeTokenApprovalError::ApprovalDoesNotExist)
+            }
+            Err(e) => Err(RevokeTokenApprovalError::generic(1, e)),
+        }
+    })
+}
+
+/// Revokes the approvals given for tokens held by the caller's
+/// `from_subaccount`, either for one spender or for all of them.
+pub fn icrc37_revoke_token_approvals(
+    args: Vec<RevokeTokenApprovalArg>,
+) -> Vec<Option<RevokeTokenApprovalResponse>> {
+    if args.len() as u64 > MAX_REVOKE_APPROVALS {
+        let err = RevokeTokenApprovalError::batch_too_large(MAX_REVOKE_APPROVALS);
+        return vec![Some(Err(err))];
+    }
+    let (caller, now) = (ledger::caller(), ledger::now());
+    args.into_iter()
+        .map(|arg| {
+            let revoker = Account {
+                owner: caller,
+                subaccount: arg.from_subaccount,
+            };
+            Some(revoke_token_approvals(revoker, arg, now))
+        })
+        .collect()
+}
+
+fn revoke_collection_approvals(
+    revoker: Account,
+    arg: RevokeCollectionApprovalArg,
+    now: u64,
+) -> RevokeCollectionApprovalResult {
+    check_tx_args(arg.memo.as_ref(), arg.created_at_time, now)?;
+    let revoked_by = AccountIdentifier_shiku::from(revoker);
+    let spender = arg.spender.map(AccountIdentifier_shiku::from);
+    ledger::with_mut(
+        |ledger| match ledger.revoke_collection_approvals(revoked_by, spender, now) {
+            Ok(()) => Ok(ledger.tx_index()),
+            Err(ShikuNftError::ApprovalNotFound) => {
+                Err(RevokeCollectionApprovalError::ApprovalDoesNotExist)
+            }
+            Err(e) => Err(RevokeCollectionApprovalError::generic(1, e)),
+        },
+    )
+}
+
+pub fn icrc37_revoke_collection_approvals(
+    args: Vec<RevokeCollectionApprovalArg>,
+) -> Vec<Option<RevokeCollectionApprovalResult>> {
+    if args.len() as u64 > MAX_REVOKE_APPROVALS {
+        let err = RevokeCollectionApprovalError::batch_too_large(MAX_REVOKE_APPROVALS);
+        return vec![Some(Err(err))];
+    }
+    let (caller, now) = (ledger::caller(), ledger::now());
+    args.into_iter()
+        .map(|arg| {
+            let revoker = Account {
+                owner: caller,
+                subaccount: arg.from_subaccount,
+            };
+            Some(revoke_collection_approvals(revoker, arg, now))
+        })
+        .collect()
+}
+
+/// Whether each spender may transfer the token from the `from_subaccount` of
+/// its owner, through an ICRC-37 approval or as the token's DIP721 operator.
+/// A token held in another subaccount is never approved.
+pub fn icrc37_is_approved(args: Vec<IsApprovedArg>) -> Vec<bool> {
+    let now = ledger::now();
+    ledger::with(|ledger| {
+        args.iter()
+            .map(|arg| {
+                let owner = ledger.owner_of(&arg.token_id).ok().flatten();
+                let from = owner.and_then(|owner| ledger.account_of(&owner)).map(|owner| {
+                    AccountIdentifier_shiku::from(Account {
+                        owner: owner.owner,
+                        subaccount: arg.from_subaccount,
+                    })
+                });
+                let spender = AccountIdentifier_shiku::from(arg.spender);
+                from.is_some()
+                    && from == owner
+                    && ledger.is_approved(&arg.token_id, &spender, now)
+            })
+            .collect()
+    })
+}
+
+pub fn icrc37_get_token_approvals(
+    token_id: TokenId,
+    prev: Option<TokenApproval>,
+    take: Option<Nat>,
+) -> Vec<TokenApproval> {
+    let now = ledger::now();
+    let approvals = ledger::with(|ledger| {
+        ledger
+            .token_approvals(&token_id, now)
+            .map(|approval_info| TokenApproval {
+                token_id: token_id.clone(),
+                approval_info: approval_info.clone(),
+            })
+            .collect()
+    });
+    let prev = prev.map(|prev| prev.approval_info.spender_aid());
+    paginate_approvals(
+        approvals,
+        |approval| approval.approval_info.spender_aid(),
+        prev,
+        take,
+    )
+}
+
+pub fn icrc37_get_collection_approvals(
+    owner: Account,
+    prev: Option<CollectionApproval>,
+    take: Option<Nat>,
+) -> Vec<CollectionApproval> {
+    let now = ledger::now();
+    let owner = AccountIdentifier_shiku::from(owner);
+    let approvals =
+        ledger::with(|ledger| ledger.collection_approvals(&owner, now).cloned().collect());
+    let prev = prev.map(|prev| prev.spender_aid());
+    paginate_approvals(approvals, ApprovalInfo::spender_aid, prev, take)
+}
+
+fn transfer_from(spender: Account, arg: TransferFromArg, now: u64) -> TransferFromResult {
+    check_tx_args(arg.memo.as_ref(), arg.created_at_time, now)?;
+    if arg.to == arg.from {
+        return Err(TransferFromError::InvalidRecipient);
+    }
+    let key = arg.created_at_time.map(|created_at_time| {
+        TxKey::new(&spender.owner, "icrc37_transfer_from", &arg, created_at_time)
+    });
+    let spender = AccountIdentifier_shiku::from(spender);
+    let (from, to) = (
+        AccountIdentifier_shiku::from(arg.from),
+        AccountIdentifier_shiku::from(arg.to),
+    );
+    let duplicate = |duplicate_of| Err(TransferFromError::Duplicate { duplicate_of });
+    ledger::with_mut(|ledger| {
+        ledger.deduplicate(key, now, duplicate, |ledger| {
+            let owner = ledger
+                .token_metadata(&arg.token_id)
+                .ok()
+                .filter(|token_metadata| !token_metadata.is_burned)
+                .ok_or(TransferFromError::NonExistingTokenId)?
+                .owner;
+            if owner != Some(from) {
+                return Err(TransferFromError::Unauthorized);
+            }
+            if spender != from && !ledger.is_approved(&arg.token_id, &spender, now) {
+                return Err(TransferFromError::Unauthorized);
+            }
+            ledger
+                .linking(&[arg.from, arg.to], |ledger| {
+                    ledger.transfer(spender, &arg.token_id, Some(to))
+                })
+                .map_err(|e| TransferFromError::generic(1, e))?;
+            Ok(ledger.tx_index())
+        })
+    })
+}
+
+/// Transfers each token on behalf of its owner, using the caller's
+/// `spender_subaccount` approval. A retry within the deduplication window
+/// fails with `Duplicate`; see [`ledger::Ledger::deduplicate`].
+pub fn icrc37_transfer_from(args: Vec<TransferFromArg>) -> Vec<Option<TransferFromResult>> {
+    if args.len() as u64 > crate::icrc7::MAX_UPDATE_BATCH_SIZE {
+        let err = TransferFromError::batch_too_large(crate::icrc7::MAX_UPDATE_BATCH_SIZE);
+        return vec![Some(Err(err))];
+    }
+    let (caller, now) = (ledger::caller(), ledger::now());
+    args.into_iter()
+        .map(|arg| {
+            let spender = Account {
+                owner: caller,
+                subaccount: arg.spender_subaccount,
+            };
+            Some(transfer_from(spender, arg, now))
+        })
+        .collect()
+}
@@ -0,0 +1,430 @@
+use ic_cdk::export::candid::{CandidType, Deserialize, Int, Nat};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::ops::Bound;
+
+use crate::account_identifier::Account;
+use crate::error::ShikuNftError;
+use crate::ledger::{self, TxKey};
+use crate::types::{AccountIdentifier_shiku, TokenId, TokenMetadata};
+
+pub const MAX_QUERY_BATCH_SIZE: u64 = 100;
+pub const MAX_UPDATE_BATCH_SIZE: u64 = 20;
+pub const DEFAULT_TAKE_VALUE: u64 = 100;
+pub const MAX_TAKE_VALUE: u64 = 1_000;
+pub const MAX_MEMO_SIZE: u64 = 32;
+/// 24 hours, in nanoseconds.
+pub const TX_WINDOW: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// 2 minutes, in nanoseconds.
+pub const PERMITTED_DRIFT: u64 = 2 * 60 * 1_000_000_000;
+
+pub const SUPPORTED_STANDARDS: [(&str, &str); 4] = [
+    ("ICRC-7", "https://github.com/dfinity/ICRC/ICRCs/ICRC-7"),
+    ("ICRC-37", "https://github.com/dfinity/ICRC/ICRCs/ICRC-37"),
+    ("ICRC-3", "https://github.com/dfinity/ICRC/ICRCs/ICRC-3"),
+    ("ICRC-10", "https://github.com/dfinity/ICRC/ICRCs/ICRC-10"),
+];
+
+/// The generic metadata value shared by the ICRC standards.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Blob(Vec<u8>),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SupportedStandard {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Icrc7TransferArg {
+    pub from_subaccount: Option<ic_ledger_types::Subaccount>,
+    pub to: Account,
+    pub token_id: TokenId,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Icrc7TransferError {
+    NonExistingTokenId,
+    InvalidRecipient,
+    Unauthorized,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+pub type Icrc7TransferResult = Result<Nat, Icrc7TransferError>;
+
+/// Rejections shared by every ICRC update, decided before the ledger is read.
+pub(crate) enum TxArgError {
+    MemoTooLong,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+}
+
+pub(crate) fn check_tx_args(
+    memo: Option<&Vec<u8>>,
+    created_at_time: Option<u64>,
+    now: u64,
+) -> Result<(), TxArgError> {
+    if memo.map_or(0, |memo| memo.len() as u64) > MAX_MEMO_SIZE {
+        return Err(TxArgError::MemoTooLong);
+    }
+    if let Some(created_at_time) = created_at_time {
+        if created_at_time.saturating_add(TX_WINDOW + PERMITTED_DRIFT) < now {
+            return Err(TxArgError::TooOld);
+        }
+        if created_at_time > now.saturating_add(PERMITTED_DRIFT) {
+            return Err(TxArgError::CreatedInFuture { ledger_time: now });
+        }
+    }
+    Ok(())
+}
+
+/// Implements the constructors and conversions shared by the ICRC update
+/// error variants. Error code 0 flags a bad argument, 1 a ledger failure.
+macro_rules! icrc_error {
+    ($($error:ident),* $(,)?) => {$(
+        impl $error {
+            pub(crate) fn generic(error_code: u32, message: impl ToString) -> Self {
+                Self::GenericError {
+                    error_code: Nat::from(error_code),
+                    message: message.to_string(),
+                }
+            }
+
+            pub(crate) fn batch_too_large(max: u64) -> Self {
+                Self::GenericBatchError {
+                    error_code: Nat::from(0u32),
+                    message: format!("A batch may hold at most {} entries", max),
+                }
+            }
+        }
+
+        impl From<$crate::icrc7::TxArgError> for $error {
+            fn from(err: $crate::icrc7::TxArgError) -> Self {
+                match err {
+                    $crate::icrc7::TxArgError::MemoTooLong => Self::generic(
+                        0,
+                        format!("The memo may be at most {} bytes", $crate::icrc7::MAX_MEMO_SIZE),
+                    ),
+                    $crate::icrc7::TxArgError::TooOld => Self::TooOld,
+                    $crate::icrc7::TxArgError::CreatedInFuture { ledger_time } => {
+                        Self::CreatedInFuture { ledger_time }
+                    }
+                }
+            }
+        }
+    )*};
+}
+pub(crate) use icrc_error;
+
+impl From<TxArgError> for ShikuNftError {
+    fn from(err: TxArgError) -> Self {
+        match err {
+            TxArgError::MemoTooLong => {
+                Self::Other(format!("The memo may be at most {} bytes", MAX_MEMO_SIZE))
+            }
+            TxArgError::TooOld => Self::TooOld,
+            TxArgError::CreatedInFuture { ledger_time } => Self::CreatedInFuture { ledger_time },
+        }
+    }
+}
+
+icrc_error!(Icrc7TransferError);
+
+fn live_token<'a>(ledger: &'a ledger::Ledger, token_id: &TokenId) -> Option<&'a TokenMetadata> {
+    ledger
+        .tokens
+        .get(token_id)
+        .filter(|token_metadata| !token_metadata.is_burned)
+}
+
+/// The page size for `take`, within [`MAX_TAKE_VALUE`].
+pub(crate) fn take_value(take: Option<Nat>) -> usize {
+    take.map(|take| u64::try_from(&take.0).unwrap_or(u64::MAX))
+        .unwrap_or(DEFAULT_TAKE_VALUE)
+        .min(MAX_TAKE_VALUE) as usize
+}
+
+/// The keys of an ordered index after `prev`, so that a page is
+/// `index.range(after(prev)).take(take_value(take))`.
+pub(crate) fn after<T>(prev: Option<&T>) -> (Bound<&T>, Bound<&T>) {
+    (prev.map_or(Bound::Unbounded, Bound::Excluded), Bound::Unbounded)
+}
+
+/// The items of an ordered set after `prev`, at most `take` of them.
+pub(crate) fn page<T: Ord + Clone>(
+    items: &BTreeSet<T>,
+    prev: Option<T>,
+    take: Option<Nat>,
+) -> Vec<T> {
+    items.range(after(prev.as_ref())).take(take_value(take)).cloned().collect()
+}
+
+pub fn icrc7_collection_metadata() -> Vec<(String, Value)> {
+    let mut metadata = vec![
+        ("icrc7:symbol".to_string(), Value::Text(icrc7_symbol())),
+        ("icrc7:name".to_string(), Value::Text(icrc7_name())),
+    ];
+    if let Some(logo) = icrc7_logo() {
+        metadata.push(("icrc7:logo".to_string(), Value::Text(logo)));
+    }
+    metadata.extend([
+        (
+            "icrc7:total_supply".to_string(),
+            Value::Nat(icrc7_total_supply()),
+        ),
+    ]);
+    if let Some(supply_cap) = icrc7_supply_cap() {
+        metadata.push(("icrc7:supply_cap".to_string(), Value::Nat(supply_cap)));
+    }
+    metadata.extend([
+        (
+            "icrc7:max_query_batch_size".to_string(),
+            Value::Nat(MAX_QUERY_BATCH_SIZE.into()),
+        ),
+        (
+            "icrc7:max_update_batch_size".to_string(),
+            Value::Nat(MAX_UPDATE_BATCH_SIZE.into()),
+        ),
+        (
+            "icrc7:default_take_value".to_string(),
+            Value::Nat(DEFAULT_TAKE_VALUE.into()),
+        ),
+        (
+            "icrc7:max_take_value".to_string(),
+            Value::Nat(MAX_TAKE_VALUE.into()),
+        ),
+        (
+            "icrc7:max_memo_size".to_string(),
+            Value::Nat(MAX_MEMO_SIZE.into()),
+        ),
+        ("icrc7:tx_window".to_string(), Value::Nat(TX_WINDOW.into())),
+        (
+            "icrc7:permitted_drift".to_string(),
+            Value::Nat(PERMITTED_DRIFT.into()),
+        ),
+    ]);
+    metadata
+}
+
+pub fn icrc7_symbol() -> String {
+    ledger::with(|ledger| ledger.metadata().symbol.clone().unwrap_or_default())
+}
+
+pub fn icrc7_name() -> String {
+    ledger::with(|ledger| ledger.metadata().name.clone().unwrap_or_default())
+}
+
+pub fn icrc7_description() -> Option<String> {
+    None
+}
+
+pub fn icrc7_logo() -> Option<String> {
+    ledger::with(|ledger| ledger.metadata().logo.clone())
+}
+
+pub fn icrc7_total_supply() -> Nat {
+    ledger::with(|ledger| {
+        let supply = ledger
+            .tokens
+            .values()
+            .filter(|token| !token.is_burned)
+            .count();
+        Nat::from(supply)
+    })
+}
+
+pub fn icrc7_supply_cap() -> Option<Nat> {
+    ledger::with(|ledger| ledger.mint_policy.max_supply.map(Nat::from))
+}
+
+pub fn icrc7_max_query_batch_size() -> Option<Nat> {
+    Some(MAX_QUERY_BATCH_SIZE.into())
+}
+
+pub fn icrc7_max_update_batch_size() -> Option<Nat> {
+    Some(MAX_UPDATE_BATCH_SIZE.into())
+}
+
+pub fn icrc7_default_take_value() -> Option<Nat> {
+    Some(DEFAULT_TAKE_VALUE.into())
+}
+
+pub fn icrc7_max_take_value() -> Option<Nat> {
+    Some(MAX_TAKE_VALUE.into())
+}
+
+pub fn icrc7_max_memo_size() -> Option<Nat> {
+    Some(MAX_MEMO_SIZE.into())
+}
+
+/// Transfers are applied one by one: a failing entry does not undo the others.
+pub fn icrc7_atomic_batch_transfers() -> Option<bool> {
+    Some(false)
+}
+
+pub fn icrc7_tx_window() -> Option<Nat> {
+    Some(TX_WINDOW.into())
+}
+
+pub fn icrc7_permitted_drift() -> Option<Nat> {
+    Some(PERMITTED_DRIFT.into())
+}
+
+pub fn icrc7_token_metadata(token_ids: Vec<TokenId>) -> Vec<Option<Vec<(String, Value)>>> {
+    ledger::with(|ledger| {
+        token_ids
+            .iter()
+            .map(|token_id| {
+                let token_metadata = live_token(ledger, token_id)?;
+                let mut metadata = vec![(
+                    "minted_at".to_string(),
+                    Value::Nat(token_metadata.minted_at.into()),
+                )];
+                if let Some(properties) = &token_metadata.properties {
+                    let fields = [
+                        ("name", &properties.name),
+                        ("symbol", &properties.symbol),
+                        ("logo", &properties.logo),
+                    ];
+                    for (key, value) in fields {
+                        if let Some(value) = value {
+                            metadata.push((key.to_string(), Value::Text(value.clone())));
+                        }
+                    }
+                }
+                if let Some(class) = ledger.class_of(token_id) {
+                    metadata.push(("class".to_string(), Value::Text(class.to_string())));
+                }
+                Some(metadata)
+            })
+            .collect()
+    })
+}
+
+/// Owners are reported as the ICRC-1 accounts the ledger has seen them as.
+/// Tokens held by a bare account identifier whose principal the ledger never
+/// saw, such as ones moved through DIP721 or EXT, or held since before
+/// accounts were tracked, are reported as `None`.
+pub fn icrc7_owner_of(token_ids: Vec<TokenId>) -> Vec<Option<Account>> {
+    ledger::with(|ledger| {
+        token_ids
+            .iter()
+            .map(|token_id| {
+                let owner = live_token(ledger, token_id)?.owner?;
+                ledger.account_of(&owner)
+            })
+            .collect()
+    })
+}
+
+pub fn icrc7_balance_of(accounts: Vec<Account>) -> Vec<Nat> {
+    ledger::with(|ledger| {
+        accounts
+            .iter()
+            .map(|account| {
+                let owner = AccountIdentifier_shiku::from(account);
+                let balance = ledger
+                    .owner_token_identifiers(&owner)
+                    .map_or(0, |tokens| tokens.len());
+                Nat::from(balance)
+            })
+            .collect()
+    })
+}
+
+pub fn icrc7_tokens(prev: Option<Nat>, take: Option<Nat>) -> Vec<TokenId> {
+    ledger::with(|ledger| {
+        ledger
+            .tokens
+            .range(after(prev.as_ref()))
+            .filter(|(_, token)| !token.is_burned)
+            .map(|(token_id, _)| token_id.clone())
+            .take(take_value(take))
+            .collect()
+    })
+}
+
+pub fn icrc7_tokens_of(account: Account, prev: Option<Nat>, take: Option<Nat>) -> Vec<TokenId> {
+    ledger::with(|ledger| {
+        let owner = AccountIdentifier_shiku::from(account);
+        match ledger.owner_token_identifiers(&owner) {
+            Ok(tokens) => page(tokens, prev, take),
+            Err(_) => Vec::new(),
+        }
+    })
+}
+
+fn icrc7_transfer_one(from: Account, arg: Icrc7TransferArg) -> Icrc7TransferResult {
+    let now = ledger::now();
+    check_tx_args(arg.memo.as_ref(), arg.created_at_time, now)?;
+    if arg.to == from {
+        return Err(Icrc7TransferError::InvalidRecipient);
+    }
+
+    let (from_aid, to_aid) = (
+        AccountIdentifier_shiku::from(from),
+        AccountIdentifier_shiku::from(arg.to),
+    );
+    let key = arg
+        .created_at_time
+        .map(|created_at_time| TxKey::new(&from.owner, "icrc7_transfer", &arg, created_at_time));
+    let duplicate = |duplicate_of| Err(Icrc7TransferError::Duplicate { duplicate_of });
+    ledger::with_mut(|ledger| {
+        ledger.deduplicate(key, now, duplicate, |ledger| {
+            let owner = live_token(ledger, &arg.token_id)
+                .ok_or(Icrc7TransferError::NonExistingTokenId)?
+                .owner;
+            if owner != Some(from_aid) {
+                return Err(Icrc7TransferError::Unauthorized);
+            }
+            ledger
+                .linking(&[from, arg.to], |ledger| {
+                    ledger.transfer(from_aid, &arg.token_id, Some(to_aid))
+                })
+                .map_err(|e| Icrc7TransferError::generic(1, e))?;
+            Ok(ledger.tx_index())
+        })
+    })
+}
+
+/// Transfers each token from the caller's `from_subaccount` to `to`. A retry
+/// within the deduplication window fails with `Duplicate`; see
+/// [`ledger::Ledger::deduplicate`].
+pub fn icrc7_transfer(args: Vec<Icrc7TransferArg>) -> Vec<Option<Icrc7TransferResult>> {
+    if args.len() as u64 > MAX_UPDATE_BATCH_SIZE {
+        return vec![Some(Err(Icrc7TransferError::batch_too_large(MAX_UPDATE_BATCH_SIZE)))];
+    }
+    let caller = ledger::caller();
+    args.into_iter()
+        .map(|arg| {
+            let from = Account {
+                owner: caller,
+                subaccount: arg.from_subaccount,
+            };
+            Some(icrc7_transfer_one(from, arg))
+        })
+        .collect()
+}
+
+pub fn icrc10_supported_standards() -> Vec<SupportedStandard> {
+    SUPPORTED_STANDARDS
+        .iter()
+        .map(|(name, url)| SupportedStandard {
+            name: name.to_string(),
+            url: url.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,233 @@
+use candid::parser::types::FuncMode;
+use candid::types::internal::TypeContainer;
+use candid::types::{CandidType, Field, Function, Type};
+use ic_cdk::export::candid::Nat;
+use ic_cdk::export::Principal;
+use std::collections::HashSet;
+
+use crate::account_identifier::Account;
+use crate::dip721::MintBatchItem;
+use crate::icrc3::*;
+use crate::icrc37::*;
+use crate::icrc7::*;
+use crate::status::{TokenAction, TokenStatus};
+use crate::types::*;
+
+macro_rules! method {
+    (@mode query) => { vec![FuncMode::Query] };
+    (@mode update) => { vec![] };
+    ($env:ident, $name:expr, ($($arg:ty),*), ($($ret:ty),*), $mode:ident) => {
+        (
+            $name.to_string(),
+            Type::Func(Function {
+                args: vec![$($env.add::<$arg>()),*],
+                rets: vec![$($env.add::<$ret>()),*],
+                modes: method!(@mode $mode),
+            }),
+        )
+    };
+}
+
+/// A [`TypeContainer`] that keeps distinct types under distinct names.
+///
+/// `TypeContainer` numbers the instances of generic Rust types (`Result`,
+/// `Result_1`, ...), which can land on the name of a type such as EXT's
+/// `Result_1` and silently replace its definition. The type added last gets
+/// a fresh name instead.
+#[derive(Default)]
+struct Types {
+    container: TypeContainer,
+    /// The name, definition and fresh name of each clash seen so far.
+    renamed: Vec<(String, Type, String)>,
+}
+
+impl Types {
+    fn add<T: CandidType>(&mut self) -> Type {
+        let env = &mut self.container.env.0;
+        let before = env.clone();
+        let mut ty = self.container.add::<T>();
+        let env = &mut self.container.env.0;
+        for (name, old) in before.iter() {
+            if env[name] == *old {
+                continue;
+            }
+            let new = env.insert(name.clone(), old.clone()).unwrap();
+            let known = self.renamed.iter().find(|(n, def, _)| n == name && *def == new);
+            let fresh = match known {
+                Some((_, _, fresh)) => fresh.clone(),
+                None => {
+                    let fresh = (1..)
+                        .map(|n| format!("{}_{}", name, n))
+                        .find(|fresh| !env.contains_key(fresh))
+                        .unwrap();
+                    self.renamed.push((name.clone(), new.clone(), fresh.clone()));
+                    fresh
+                }
+            };
+            let added: Vec<String> = env
+                .keys()
+                .filter(|added| !before.contains_key(*added))
+                .cloned()
+                .collect();
+            for added in added {
+                let def = rename(&env[&added], name, &fresh);
+                env.insert(added, def);
+            }
+            env.insert(fresh.clone(), rename(&new, name, &fresh));
+            ty = rename(&ty, name, &fresh);
+        }
+        ty
+    }
+}
+
+/// Points the references to `from` in `ty` to `to`.
+fn rename(ty: &Type, from: &str, to: &str) -> Type {
+    let fields = |fields: &[Field]| {
+        fields
+            .iter()
+            .map(|field| Field { id: field.id.clone(), ty: rename(&field.ty, from, to) })
+            .collect()
+    };
+    let types = |types: &[Type]| types.iter().map(|ty| rename(ty, from, to)).collect();
+    match ty {
+        Type::Var(name) if name == from => Type::Var(to.to_string()),
+        Type::Opt(ty) => Type::Opt(Box::new(rename(ty, from, to))),
+        Type::Vec(ty) => Type::Vec(Box::new(rename(ty, from, to))),
+        Type::Record(fs) => Type::Record(fields(fs)),
+        Type::Variant(fs) => Type::Variant(fields(fs)),
+        Type::Func(func) => Type::Func(Function {
+            modes: func.modes.clone(),
+            args: types(&func.args),
+            rets: types(&func.rets),
+        }),
+        ty => ty.clone(),
+    }
+}
+
+/// Returns the Candid service description of the endpoints exported by this
+/// crate, in the same format `candid::export_service!` would produce.
+pub fn candid_interface() -> String {
+    candid_interface_excluding(&[])
+}
+
+/// Same as [`candid_interface`], leaving out the methods named in `disabled`.
+pub fn candid_interface_excluding(disabled: &[&str]) -> String {
+    let mut env = Types::default();
+    let mut service = vec![
+        method!(env, "dip721_total_supply", (), (Nat), query),
+        method!(env, "dip721_balance_of", (AccountIdentifier_shiku), (Result<Nat, NftError>), query),
+        method!(env, "dip721_transfer_from", (AccountIdentifier_shiku, AccountIdentifier_shiku, TokenId, Option<u64>), (Result<Nat, NftError>), update),
+        method!(env, "dip721_transfer_batch", (AccountIdentifier_shiku, Vec<(AccountIdentifier_shiku, TokenId)>), (Result<Vec<Nat>, NftError>), update),
+        method!(env, "dip721_custodians", (), (HashSet<Principal>), query),
+        method!(env, "dip721_set_paused", (bool), (Result<(), NftError>), update),
+        method!(env, "dip721_is_paused", (), (bool), query),
+        method!(env, "dip721_freeze_metadata", (), (Result<(), NftError>), update),
+        method!(env, "dip721_is_metadata_frozen", (), (bool), query),
+        method!(env, "dip721_set_name", (String), (Result<(), NftError>), update),
+        method!(env, "dip721_set_logo", (String), (Result<(), NftError>), update),
+        method!(env, "dip721_set_symbol", (String), (Result<(), NftError>), update),
+        method!(env, "dip721_set_locked", (TokenId, bool), (Result<(), NftError>), update),
+        method!(env, "dip721_is_locked", (TokenId), (bool), query),
+        method!(env, "dip721_mint_policy", (), (MintPolicy), query),
+        method!(env, "dip721_set_mint_policy", (MintPolicy), (Result<(), NftError>), update),
+        method!(env, "dip721_change_status", (TokenId, TokenAction), (Result<TokenStatus, NftError>), update),
+        method!(env, "dip721_status_of", (TokenId), (Result<TokenStatus, NftError>), query),
+        method!(env, "dip721_tokens_with_status", (TokenStatus, Option<TokenId>, Option<Nat>), (Vec<TokenId>), query),
+        method!(env, "dip721_mint", (AccountIdentifier_shiku, TokenId, Option<u64>, Option<bool>), (Result<Nat, NftError>), update),
+        method!(env, "dip721_mint_batch", (Vec<MintBatchItem>), (Result<Vec<Nat>, NftError>), update),
+        method!(env, "dip721_burn", (TokenId), (Result<Nat, NftError>), update),
+        method!(env, "dip721_approve", (AccountIdentifier_shiku, TokenId), (Result<Nat, NftError>), update),
+        method!(env, "dip721_get_registry", (Option<TokenIndexU32>, Option<Nat>), (Vec<(TokenIndexU32, AccountIdentifier__1)>), query),
+        method!(env, "dip721_token_metadata", (TokenId), (Result<TokenMetadata, NftError>), query),
+        method!(env, "dip721_owner_token_identifiers", (AccountIdentifier_shiku, Option<TokenId>, Option<Nat>), (Result<Vec<TokenId>, NftError>), query),
+        method!(env, "dip721_operator_token_identifiers", (AccountIdentifier_shiku, Option<TokenId>, Option<Nat>), (Result<Vec<TokenId>, NftError>), query),
+        method!(env, "dip721_owner_of", (TokenId), (Result<Option<AccountIdentifier_shiku>, NftError>), query),
+        method!(env, "dip721_owner_of_batch", (Vec<TokenId>), (Result<Vec<Result<Option<AccountIdentifier_shiku>, NftError>>, NftError>), query),
+        method!(env, "dip721_token_metadata_batch", (Vec<TokenId>), (Result<Vec<Result<TokenMetadata, NftError>>, NftError>), query),
+        method!(env, "dip721_owner_of_certified", (TokenId), (Option<ICRC3DataCertificate>), query),
+        method!(env, "dip721_balance_of_certified", (AccountIdentifier_shiku), (Option<ICRC3DataCertificate>), query),
+        method!(env, "dip721_allowance", (AccountIdentifier_shiku, AccountIdentifier_shiku, TokenId), (Result<Nat, CommonError>), query),
+        method!(env, "dip721_token_identitfier_operator", (AccountIdentifier_shiku, Option<TokenId>, Option<Nat>), (Result<Vec<TokenId>, NftError>), query),
+        method!(env, "dip721_owner_counts", (), (usize), query),
+        method!(env, "dip721_set_minter", (Principal), (Result<(), CommonError>), update),
+        method!(env, "dip721_minted_info", (Option<Nat>, Option<Nat>), (Vec<Nat>), query),
+        method!(env, "dip721_history", (Option<Nat>, Option<Nat>), (Vec<BlockWithId>), query),
+        method!(env, "dip721_set_class", (String, Vec<TokenId>), (Result<(), NftError>), update),
+        method!(env, "dip721_class_supply", (String), (Nat), query),
+        method!(env, "dip721_class_balance_of", (String, AccountIdentifier_shiku), (Nat), query),
+        method!(env, "dip721_operator_of", (TokenId), (Result<Option<AccountIdentifier_shiku>, NftError>), query),
+        method!(env, "dip721_owner_token_metadata", (AccountIdentifier_shiku, Option<TokenId>, Option<Nat>), (Result<Vec<TokenMetadata>, NftError>), query),
+        method!(env, "dip721_operator_token_metadata", (AccountIdentifier_shiku, Option<TokenId>, Option<Nat>), (Result<Vec<TokenMetadata>, NftError>), query),
+        method!(env, "dip721_set_custodians", (HashSet<Principal>), (Result<(), NftError>), update),
+        method!(env, "dip721_metadata", (), (MetaData), query),
+        method!(env, "dip721_name", (), (Option<String>), query),
+        method!(env, "dip721_logo", (), (Option<String>), query),
+        method!(env, "dip721_symbol", (), (Option<String>), query),
+        method!(env, "dip721_total_unique_holders", (), (Nat), query),
+        method!(env, "dip721_total_transactions", (), (Nat), query),
+        method!(env, "dip721_transaction", (Nat), (Result<TxEvent, NftError>), query),
+        method!(env, "dip721_cycles", (), (Nat), query),
+        method!(env, "dip721_stats", (), (Status), query),
+        method!(env, "dip721_supported_interfaces", (), (Vec<SupportedInterface>), query),
+        method!(env, "dip721_transfer", (AccountIdentifier_shiku, TokenId), (Result<Nat, NftError>), update),
+        method!(env, "dip721_set_approval_for_all", (Principal, bool), (Result<Nat, NftError>), update),
+        method!(env, "dip721_is_approved_for_all", (Principal, Principal), (Result<bool, NftError>), query),
+        method!(env, "extensions", (), (Vec<String>), query),
+        method!(env, "balance", (BalanceRequest), (BalanceResponse), query),
+        method!(env, "bearer", (TokenIdentifier__1), (Result__1_1), query),
+        method!(env, "metadata", (TokenIdentifier__1), (Result__1), query),
+        method!(env, "supply", (TokenIdentifier__1), (Result_2), query),
+        method!(env, "getRegistry", (), (Vec<(TokenIndexU32, AccountIdentifier__1)>), query),
+        method!(env, "getTokens", (), (Vec<(TokenIndexU32, TokenMetaDataExt)>), query),
+        method!(env, "tokens", (AccountIdentifier), (Result_1), query),
+        method!(env, "tokens_ext", (AccountIdentifier), (NFTResult), query),
+        method!(env, "transfer", (TransferRequest, Option<u64>), (TransferResponse), update),
+        method!(env, "batch_transfer", (TransferRequestV2), (TransferResponse), update),
+        method!(env, "transfer_range", (TransferRequestV1), (TransferResponse), update),
+        method!(env, "allowance", (AllowanceRequest), (Result_2), query),
+        method!(env, "approve", (ApproveRequest), (Result_2), update),
+        method!(env, "icrc7_collection_metadata", (), (Vec<(String, Value)>), query),
+        method!(env, "icrc7_symbol", (), (String), query),
+        method!(env, "icrc7_name", (), (String), query),
+        method!(env, "icrc7_description", (), (Option<String>), query),
+        method!(env, "icrc7_logo", (), (Option<String>), query),
+        method!(env, "icrc7_total_supply", (), (Nat), query),
+        method!(env, "icrc7_supply_cap", (), (Option<Nat>), query),
+        method!(env, "icrc7_max_query_batch_size", (), (Option<Nat>), query),
+        method!(env, "icrc7_max_update_batch_size", (), (Option<Nat>), query),
+        method!(env, "icrc7_default_take_value", (), (Option<Nat>), query),
+        method!(env, "icrc7_max_take_value", (), (Option<Nat>), query),
+        method!(env, "icrc7_max_memo_size", (), (Option<Nat>), query),
+        method!(env, "icrc7_atomic_batch_transfers", (), (Option<bool>), query),
+        method!(env, "icrc7_tx_window", (), (Option<Nat>), query),
+        method!(env, "icrc7_permitted_drift", (), (Option<Nat>), query),
+        method!(env, "icrc7_token_metadata", (Vec<TokenId>), (Vec<Option<Vec<(String, Value)>>>), query),
+        method!(env, "icrc7_owner_of", (Vec<TokenId>), (Vec<Option<Account>>), query),
+        method!(env, "icrc7_balance_of", (Vec<Account>), (Vec<Nat>), query),
+        method!(env, "icrc7_tokens", (Option<Nat>, Option<Nat>), (Vec<TokenId>), query),
+        method!(env, "icrc7_tokens_of", (Account, Option<Nat>, Option<Nat>), (Vec<TokenId>), query),
+        method!(env, "icrc7_transfer", (Vec<Icrc7TransferArg>), (Vec<Option<Icrc7TransferResult>>), update),
+        method!(env, "icrc10_supported_standards", (), (Vec<SupportedStandard>), query),
+        method!(env, "icrc37_metadata", (), (Vec<(String, Value)>), query),
+        method!(env, "icrc37_max_approvals_per_token_or_collection", (), (Option<Nat>), query),
+        method!(env, "icrc37_max_revoke_approvals", (), (Option<Nat>), query),
+        method!(env, "icrc37_approve_tokens", (Vec<ApproveTokenArg>), (Vec<Option<ApproveTokenResult>>), update),
+        method!(env, "icrc37_approve_collection", (Vec<ApproveCollectionArg>), (Vec<Option<ApproveCollectionResult>>), update),
+        method!(env, "icrc37_revoke_token_approvals", (Vec<RevokeTokenApprovalArg>), (Vec<Option<RevokeTokenApprovalResponse>>), update),
+        method!(env, "icrc37_revoke_collection_approvals", (Vec<RevokeCollectionApprovalArg>), (Vec<Option<RevokeCollectionApprovalResult>>), update),
+        method!(env, "icrc37_is_approved", (Vec<IsApprovedArg>), (Vec<bool>), query),
+        method!(env, "icrc37_get_token_approvals", (TokenId, Option<TokenApproval>, Option<Nat>), (Vec<TokenApproval>), query),
+        method!(env, "icrc37_get_collection_approvals", (Account, Option<CollectionApproval>, Option<Nat>), (Vec<CollectionApproval>), query),
+        method!(env, "icrc37_transfer_from", (Vec<TransferFromArg>), (Vec<Option<TransferFromResult>>), update),
+        method!(env, "icrc3_get_blocks", (Vec<GetBlocksArgs>), (GetBlocksResult), query),
+        method!(env, "icrc3_get_archives", (GetArchivesArgs), (Vec<ICRC3ArchiveInfo>), query),
+        method!(env, "icrc3_get_tip_certificate", (), (Option<ICRC3DataCertificate>), query),
+        method!(env, "icrc3_supported_block_types", (), (Vec<SupportedBlockType>), query),
+    ];
+
+    service.retain(|(name, _)| !disabled.contains(&name.as_str()));
+    service.sort_unstable_by_key(|(name, _)| name.clone());
+    let init_args = vec![env.add::<Option<InitArgs>>()];
+    let actor = Some(Type::Class(init_args, Box::new(Type::Service(service))));
+    candid::bindings::candid::compile(&env.container.env, &actor)
+}
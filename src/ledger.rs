@@ -1,39 +1,190 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-use ic_cdk::api::{time};
-use ic_cdk::export::candid::{CandidType, Deserialize, Nat};
+use ic_cdk::export::candid::{self, CandidType, Deserialize, Nat};
 use ic_cdk::export::Principal;
 
+use crate::account_identifier::Account;
+use crate::certification::{self, CertifiedState};
+use crate::error::ShikuNftError;
+use crate::hooks::{self, LedgerEvent};
+use crate::icrc3;
+use crate::icrc37::{ApprovalInfo, MAX_APPROVALS_PER_TOKEN_OR_COLLECTION};
+use crate::icrc7::{after, Value, PERMITTED_DRIFT, TX_WINDOW};
+use crate::status::{TokenAction, TokenStatus};
 use crate::types::*;
+use sha2::{Digest, Sha256};
 
 
 thread_local! {
     static LEDGER: RefCell<Ledger> = RefCell::new(Ledger::default());
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static CALLER: RefCell<Principal> = const { RefCell::new(Principal::anonymous()) };
+}
+
 pub fn with<T, F: FnOnce(&Ledger) -> T>(f: F) -> T {
     LEDGER.with(|ledger| f(&ledger.borrow()))
 }
 
+/// Moves the ledger out, leaving an empty one behind, such as to save it
+/// before an upgrade.
+pub fn take() -> Ledger {
+    LEDGER.with(|ledger| ledger.take())
+}
+
+/// Runs `f` against the ledger, then the `after_*` hooks of whatever it
+/// committed. Recertifies the ledger when `f` appended blocks.
 pub fn with_mut<T, F: FnOnce(&mut Ledger) -> T>(f: F) -> T {
-    LEDGER.with(|ledger| f(&mut ledger.borrow_mut()))
+    let (result, appended) = LEDGER.with(|ledger| {
+        let mut ledger = ledger.borrow_mut();
+        let log_length = ledger.blocks.len();
+        let result = f(&mut ledger);
+        (result, ledger.blocks.len() != log_length)
+    });
+    if appended {
+        certification::certify();
+    }
+    hooks::run_after_hooks();
+    result
+}
+
+fn mint_event(token_identifier: &TokenId, token_metadata: &TokenMetadata) -> LedgerEvent {
+    LedgerEvent::Mint {
+        token_identifier: token_identifier.clone(),
+        minted_by: token_metadata.minted_by,
+        to: token_metadata.owner,
+    }
+}
+
+/// The IC time in nanoseconds, or the system time when running off-chain so
+/// the ledger logic can be exercised in tests.
+pub fn now() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::time()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    }
+}
+
+/// The caller of the current message, or the principal last given to
+/// [`set_caller`] when running off-chain so access checks can be exercised
+/// in tests.
+pub fn caller() -> Principal {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::caller()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        CALLER.with(|caller| *caller.borrow())
+    }
+}
+
+/// This canister's id, or a fixed one when running off-chain so EXT token
+/// identifiers can be exercised in tests.
+pub fn canister_id() -> Principal {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::id()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1])
+    }
+}
+
+/// Sets the principal [`caller`] returns off-chain. Starts out anonymous.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_caller(caller: Principal) {
+    CALLER.with(|current| *current.borrow_mut() = caller);
 }
 
 
 #[derive(CandidType, Default, Deserialize)]
 pub struct Ledger {
     pub metadata: MetaData,
-    pub tokens: HashMap<TokenId, TokenMetadata>,
-    pub owners: HashMap<AccountIdentifier_shiku, HashSet<TokenId>>,
-    pub operators: HashMap<AccountIdentifier_shiku, HashSet<TokenId>>,
+    pub tokens: BTreeMap<TokenId, TokenMetadata>,
+    pub owners: HashMap<AccountIdentifier_shiku, BTreeSet<TokenId>>,
+    pub operators: HashMap<AccountIdentifier_shiku, BTreeSet<TokenId>>,
     pub custodians: HashSet<AccountIdentifier_shiku>,
-    pub idx2aid: HashMap<TokenIndexU32, AccountIdentifier__1>,
+    pub idx2aid: BTreeMap<TokenIndexU32, AccountIdentifier__1>,
     pub tx_count: Nat,
+    pub accounts: HashMap<AccountIdentifier_shiku, Account>,
+    pub token_approvals: HashMap<TokenId, Vec<ApprovalInfo>>,
+    pub collection_approvals: HashMap<AccountIdentifier_shiku, Vec<ApprovalInfo>>,
+    /// The ICRC-3 log of every mint, transfer, approval and burn.
+    pub blocks: Vec<Value>,
+    /// The index of the first block of [`Ledger::blocks`]. A ledger migrated
+    /// from before the log counts the earlier transactions here, whose
+    /// blocks were never kept.
+    pub log_start: u64,
+    /// The transaction index of every deduplicated call still in the window.
+    pub recent_txs: BTreeMap<TxKey, Nat>,
+    /// The ids of the tokens of each class, such as a tier or a series.
+    pub classes: HashMap<String, BTreeSet<TokenId>>,
+    pub token_classes: HashMap<TokenId, String>,
+    /// Stops every mint, transfer, burn and approval until lifted.
+    pub paused: bool,
+    /// Once set, the collection and token metadata can no longer change.
+    pub metadata_frozen: bool,
+    /// Mints every token soulbound.
+    pub soulbound: bool,
+    pub mint_policy: MintPolicy,
+    /// How many tokens were minted to each account, for
+    /// [`MintPolicy::max_per_account`].
+    pub mints_per_account: HashMap<AccountIdentifier_shiku, u64>,
+    /// The owners and balances as the canister certifies them.
+    pub certified: CertifiedState,
+}
+
+/// Identifies a call that set `created_at_time`, so that a retry within the
+/// deduplication window is recognised instead of executed again.
+///
+/// Keys order by `created_at_time` first, which lets expired ones be pruned
+/// from the front of [`Ledger::recent_txs`].
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TxKey {
+    pub created_at_time: u64,
+    /// Hash of the caller, the operation name and the Candid-encoded arguments.
+    pub digest: Vec<u8>,
+}
+
+impl TxKey {
+    pub fn new<A: CandidType>(
+        caller: &Principal,
+        operation: &str,
+        args: &A,
+        created_at_time: u64,
+    ) -> TxKey {
+        let args = candid::encode_one(args).expect("arguments encode to Candid");
+        let mut hasher = Sha256::new();
+        hasher.update([caller.as_slice().len() as u8]);
+        hasher.update(caller.as_slice());
+        hasher.update([operation.len() as u8]);
+        hasher.update(operation.as_bytes());
+        hasher.update(args);
+        TxKey {
+            created_at_time,
+            digest: hasher.finalize().to_vec(),
+        }
+    }
 }
 
 impl Ledger {
     pub fn init_metadata(&mut self, default_custodian: Principal, args: Option<InitArgs>) {
+        self.soulbound = args.as_ref().and_then(|args| args.soulbound).unwrap_or(false);
+        self.mint_policy = args
+            .as_ref()
+            .and_then(|args| args.mint_policy.clone())
+            .unwrap_or_default();
         let metadata = self.metadata_mut();
         metadata.custodians.insert(default_custodian);
         if let Some(args) = args {
@@ -50,11 +201,11 @@ impl Ledger {
         } else {
             // handshake(1_000_000_000_000, None);
         }
-        metadata.created_at = time();
-        metadata.upgraded_at = time();
+        metadata.created_at = now();
+        metadata.upgraded_at = now();
     }
 
-    pub fn get_registry(&self) -> HashMap<TokenIndexU32, AccountIdentifier__1> {
+    pub fn get_registry(&self) -> BTreeMap<TokenIndexU32, AccountIdentifier__1> {
         self.idx2aid.clone()
     }
 
@@ -77,17 +228,17 @@ impl Ledger {
     pub fn owner_token_identifiers(
         &self,
         owner: &AccountIdentifier_shiku,
-    ) -> Result<&HashSet<TokenId>, NftError> {
-        self.owners.get(owner).ok_or(NftError::OwnerNotFound)
+    ) -> Result<&BTreeSet<TokenId>, ShikuNftError> {
+        self.owners.get(owner).ok_or(ShikuNftError::OwnerNotFound)
     }
 
     pub fn token_metadata(
         &self,
         token_identifier: &TokenId,
-    ) -> Result<&TokenMetadata, NftError> {
+    ) -> Result<&TokenMetadata, ShikuNftError> {
         self.tokens
             .get(token_identifier)
-            .ok_or(NftError::TokenNotFound)
+            .ok_or(ShikuNftError::TokenNotFound)
     }
 
     pub fn add_token_metadata(
@@ -102,10 +253,45 @@ impl Ledger {
         self.owners.len()
     }
 
+    /// Remembers which ICRC-1 account `account`'s identifier stands for.
+    pub fn link_account(&mut self, account: Account) -> AccountIdentifier_shiku {
+        let aid = AccountIdentifier_shiku::from(account);
+        self.accounts.insert(aid, account);
+        aid
+    }
+
+    /// Links `accounts` for the length of `operation`, which logs them, and
+    /// unlinks the ones that were new if it fails.
+    pub fn linking<T, E>(
+        &mut self,
+        accounts: &[Account],
+        operation: impl FnOnce(&mut Ledger) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let new: Vec<_> = accounts
+            .iter()
+            .map(|account| AccountIdentifier_shiku::from(*account))
+            .filter(|aid| !self.accounts.contains_key(aid))
+            .collect();
+        for account in accounts {
+            self.link_account(*account);
+        }
+        let result = operation(self);
+        if result.is_err() {
+            for aid in new {
+                self.accounts.remove(&aid);
+            }
+        }
+        result
+    }
+
+    pub fn account_of(&self, aid: &AccountIdentifier_shiku) -> Option<Account> {
+        self.accounts.get(aid).copied()
+    }
+
     pub fn owner_of(
         &self,
         token_identifier: &TokenId
-    ) -> Result<Option<AccountIdentifier_shiku>, NftError> {
+    ) -> Result<Option<AccountIdentifier_shiku>, ShikuNftError> {
         self.token_metadata(token_identifier)
             .map(|token_metadata| token_metadata.owner)
     }
@@ -115,39 +301,43 @@ impl Ledger {
         token_identifier: &TokenId,
         old_owner: Option<AccountIdentifier_shiku>,
         new_owner: Option<AccountIdentifier_shiku>,
-    ) {
+    ) -> Result<(), ShikuNftError> {
         if let Some(old_owner) = old_owner {
             let old_owner_token_identifier = self
                 .owners
                 .get_mut(&old_owner)
-                .expect("couldn't find owner");
+                .ok_or(ShikuNftError::OwnerNotFound)?;
             
             old_owner_token_identifier.remove(token_identifier);
+            let balance = old_owner_token_identifier.len();
             if old_owner_token_identifier.is_empty() {
                 self.owners.remove(&old_owner);
             }
+            self.certified.set_balance(&old_owner, balance);
         }
         if let Some(new_owner) = new_owner {
-            self.owners
-                .entry(new_owner)
-                .or_insert_with(HashSet::new)
-                .insert(token_identifier.clone());
+            let new_owner_token_identifiers = self.owners.entry(new_owner).or_default();
+            new_owner_token_identifiers.insert(token_identifier.clone());
+            let balance = new_owner_token_identifiers.len();
+            self.certified.set_balance(&new_owner, balance);
         }
+        self.certified.set_owner(token_identifier, new_owner.as_ref());
+        Ok(())
     }
 
     pub fn operator_token_identifier(
         &self,
         operator: &AccountIdentifier_shiku,
-    ) -> Result<&HashSet<TokenId>, NftError> {
+    ) -> Result<&BTreeSet<TokenId>, ShikuNftError> {
         self.operators
             .get(operator)
-            .ok_or(NftError::OperatorNotFound)
+            .ok_or(ShikuNftError::OperatorNotFound)
     }
 
     pub fn operator_of(
         &self,
         token_identifier: &TokenId,
-    ) -> Result<Option<AccountIdentifier_shiku>, NftError> {
+    ) -> Result<Option<AccountIdentifier_shiku>, ShikuNftError> {
         self.token_metadata(token_identifier)
             .map(|token_metadata| token_metadata.operator)
     }
@@ -157,12 +347,12 @@ impl Ledger {
         token_identifier: &TokenId,
         old_operator: Option<AccountIdentifier_shiku>,
         new_operator: Option<AccountIdentifier_shiku>,
-    ) {
+    ) -> Result<(), ShikuNftError> {
         if let Some(old_operator) = old_operator {
             let old_operator_token_identifiers = self
                 .operators
                 .get_mut(&old_operator)
-                .expect("couldn't find operator");
+                .ok_or(ShikuNftError::OperatorNotFound)?;
             old_operator_token_identifiers.remove(token_identifier);
             if old_operator_token_identifiers.is_empty() {
                 self.operators.remove(&old_operator);
@@ -171,9 +361,96 @@ impl Ledger {
         if let Some(operator) = new_operator {
             self.operators
                 .entry(operator)
-                .or_insert_with(HashSet::new)
+                .or_default()
                 .insert(token_identifier.clone());
         }
+        Ok(())
+    }
+
+    /// Checks that the owner and operator caches agree with the token's
+    /// metadata, so a mutation can be applied without failing half-way.
+    fn check_caches(
+        &self,
+        token_identifier: &TokenId,
+        owner: Option<AccountIdentifier_shiku>,
+        operator: Option<AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        if let Some(owner) = owner {
+            self.owners
+                .get(&owner)
+                .filter(|tokens| tokens.contains(token_identifier))
+                .ok_or(ShikuNftError::OwnerNotFound)?;
+        }
+        if let Some(operator) = operator {
+            self.operators
+                .get(&operator)
+                .filter(|tokens| tokens.contains(token_identifier))
+                .ok_or(ShikuNftError::OperatorNotFound)?;
+        }
+        Ok(())
+    }
+
+    pub fn mint(
+        &mut self,
+        token_identifier: &TokenId,
+        token_metadata: TokenMetadata,
+    ) -> Result<(), ShikuNftError> {
+        self.check_not_paused()?;
+        if self.is_token_existed(token_identifier) {
+            return Err(ShikuNftError::ExistedNFT);
+        }
+        self.check_mint_policy([token_metadata.owner]).map_err(|(_, e)| e)?;
+        let event = mint_event(token_identifier, &token_metadata);
+        hooks::before(self, &event)?;
+
+        self.apply_mint(token_identifier, token_metadata, event);
+        Ok(())
+    }
+
+    /// Mints every token or none of them: the whole batch is validated, and
+    /// every `before_mint` hook consulted against the ledger as it was before
+    /// the batch, ahead of the first write. Errors carry the position of the
+    /// offending entry.
+    pub fn mint_batch(
+        &mut self,
+        tokens: Vec<(TokenId, TokenMetadata)>,
+    ) -> Result<(), (usize, ShikuNftError)> {
+        self.check_not_paused().map_err(|e| (0, e))?;
+        self.check_mint_policy(tokens.iter().map(|(_, token_metadata)| token_metadata.owner))?;
+        let mut seen = HashSet::with_capacity(tokens.len());
+        let mut events = Vec::with_capacity(tokens.len());
+        for (position, (token_identifier, token_metadata)) in tokens.iter().enumerate() {
+            if self.is_token_existed(token_identifier) || !seen.insert(token_identifier) {
+                return Err((position, ShikuNftError::ExistedNFT));
+            }
+            let event = mint_event(token_identifier, token_metadata);
+            hooks::before(self, &event).map_err(|e| (position, e))?;
+            events.push(event);
+        }
+
+        for ((token_identifier, token_metadata), event) in tokens.into_iter().zip(events) {
+            self.apply_mint(&token_identifier, token_metadata, event);
+        }
+        Ok(())
+    }
+
+    fn apply_mint(
+        &mut self,
+        token_identifier: &TokenId,
+        mut token_metadata: TokenMetadata,
+        event: LedgerEvent,
+    ) {
+        token_metadata.soulbound |= self.soulbound;
+        let (owner, operator) = (token_metadata.owner, token_metadata.operator);
+        if let Some(owner) = owner {
+            *self.mints_per_account.entry(owner).or_default() += 1;
+        }
+        self.add_token_metadata(token_identifier, token_metadata);
+        // A new token has no previous owner or operator to remove from the
+        // caches, which is the only way updating them fails.
+        let _ = self.update_owner_cache(token_identifier, None, owner);
+        let _ = self.update_operator_cache(token_identifier, None, operator);
+        self.commit(event);
     }
 
     pub fn approve(
@@ -181,14 +458,29 @@ impl Ledger {
         approved_by: AccountIdentifier_shiku,
         token_identifier: &TokenId,
         new_operator: Option<AccountIdentifier_shiku>,
-    ) {
+    ) -> Result<(), ShikuNftError> {
+        self.check_not_paused()?;
+        self.check_not_soulbound(token_identifier)?;
+        let old_operator = self.operator_of(token_identifier)?;
+        self.check_caches(token_identifier, None, old_operator)?;
+        let event = LedgerEvent::Approve {
+            token_identifier: token_identifier.clone(),
+            approved_by,
+            operator: new_operator,
+        };
+        hooks::before(self, &event)?;
+
+        self.update_operator_cache(token_identifier, old_operator, new_operator)?;
+
         let token_metadata = self
             .tokens
             .get_mut(token_identifier)
-            .expect("couldn't find token metadata");
+            .ok_or(ShikuNftError::TokenNotFound)?;
         token_metadata.operator = new_operator;
         token_metadata.approved_by = Some(approved_by);
-        token_metadata.approved_at = Some(time());
+        token_metadata.approved_at = Some(now());
+        self.commit(event);
+        Ok(())
     }
 
     pub fn transfer(
@@ -196,33 +488,654 @@ impl Ledger {
         transferred_by: AccountIdentifier_shiku,
         token_identifier: &TokenId,
         new_owner: Option<AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        let event = self.transfer_event(transferred_by, token_identifier, new_owner)?;
+        hooks::before(self, &event)?;
+
+        self.apply_transfer(transferred_by, token_identifier, new_owner, event);
+        Ok(())
+    }
+
+    /// Transfers every token or none of them, like [`Ledger::mint_batch`].
+    /// The transfers are committed back to back, so their blocks form one
+    /// contiguous group in the log.
+    pub fn transfer_batch(
+        &mut self,
+        transferred_by: AccountIdentifier_shiku,
+        transfers: Vec<(TokenId, Option<AccountIdentifier_shiku>)>,
+    ) -> Result<(), (usize, ShikuNftError)> {
+        let mut seen = HashSet::with_capacity(transfers.len());
+        let mut events = Vec::with_capacity(transfers.len());
+        for (position, (token_identifier, new_owner)) in transfers.iter().enumerate() {
+            if !seen.insert(token_identifier) {
+                let e = ShikuNftError::Other(format!("token {} is repeated", token_identifier));
+                return Err((position, e));
+            }
+            let event = self
+                .transfer_event(transferred_by, token_identifier, *new_owner)
+                .and_then(|event| hooks::before(self, &event).map(|()| event))
+                .map_err(|e| (position, e))?;
+            events.push(event);
+        }
+
+        for ((token_identifier, new_owner), event) in transfers.into_iter().zip(events) {
+            self.apply_transfer(transferred_by, &token_identifier, new_owner, event);
+        }
+        Ok(())
+    }
+
+    fn transfer_event(
+        &self,
+        transferred_by: AccountIdentifier_shiku,
+        token_identifier: &TokenId,
+        new_owner: Option<AccountIdentifier_shiku>,
+    ) -> Result<LedgerEvent, ShikuNftError> {
+        self.check_movable(token_identifier, TokenAction::Transfer)?;
+        self.check_not_soulbound(token_identifier)?;
+        let old_owner = self.owner_of(token_identifier)?;
+        let old_operator = self.operator_of(token_identifier)?;
+        self.check_caches(token_identifier, old_owner, old_operator)?;
+        Ok(LedgerEvent::Transfer {
+            token_identifier: token_identifier.clone(),
+            transferred_by,
+            from: old_owner,
+            to: new_owner,
+        })
+    }
+
+    /// Applies a transfer validated by [`Ledger::transfer_event`].
+    fn apply_transfer(
+        &mut self,
+        transferred_by: AccountIdentifier_shiku,
+        token_identifier: &TokenId,
+        new_owner: Option<AccountIdentifier_shiku>,
+        event: LedgerEvent,
     ) {
-        let token_metadata = self
-            .tokens
-            .get_mut(token_identifier)
-            .expect("could not find token metadata");
+        let token_metadata = match self.tokens.get_mut(token_identifier) {
+            Some(token_metadata) => token_metadata,
+            None => return,
+        };
+        let (old_owner, old_operator) = (token_metadata.owner, token_metadata.operator);
         token_metadata.owner = new_owner;
         token_metadata.transferred_by = Some(transferred_by);
-        token_metadata.transferred_at = Some(time());
+        token_metadata.transferred_at = Some(now());
         token_metadata.operator = new_owner;
+        // `transfer_event` checked the transition.
+        if let Ok(status) = token_metadata.status.after(TokenAction::Transfer) {
+            token_metadata.status = status;
+        }
+        // `check_caches` vouched for both caches, the only way updating them
+        // fails.
+        let _ = self.update_owner_cache(token_identifier, old_owner, new_owner);
+        let _ = self.update_operator_cache(token_identifier, old_operator, new_owner);
+        self.token_approvals.remove(token_identifier);
+        self.commit(event);
+    }
+
+    pub fn burn(
+        &mut self,
+        burned_by: AccountIdentifier_shiku,
+        token_identifier: &TokenId,
+    ) -> Result<(), ShikuNftError> {
+        let status = self.check_movable(token_identifier, TokenAction::Burn)?;
+        let old_owner = self.owner_of(token_identifier)?;
+        let old_operator = self.operator_of(token_identifier)?;
+        self.check_caches(token_identifier, old_owner, old_operator)?;
+        let event = LedgerEvent::Burn {
+            token_identifier: token_identifier.clone(),
+            burned_by,
+            owner: old_owner,
+        };
+        hooks::before(self, &event)?;
+
+        self.update_owner_cache(token_identifier, old_owner, None)?;
+        self.update_operator_cache(token_identifier, old_operator, None)?;
+
+        let token_metadata = self
+            .tokens
+            .get_mut(token_identifier)
+            .ok_or(ShikuNftError::TokenNotFound)?;
+        token_metadata.owner = None;
+        token_metadata.operator = None;
+        token_metadata.is_burned = true;
+        token_metadata.status = status;
+        token_metadata.burned_by = Some(burned_by);
+        token_metadata.burned_at = Some(now());
+        self.token_approvals.remove(token_identifier);
+        self.commit(event);
+        Ok(())
     }
 
-    pub fn burn(&mut self,
-        burned_by: AccountIdentifier_shiku, 
-        token_identifier: &TokenId) {
-            let token_metadata = self.
-                tokens
-                .get_mut(token_identifier)
-                .expect("could not find token metadata");
-            token_metadata.owner = None;
-            token_metadata.operator = None;
-            token_metadata.is_burned = true;
-            token_metadata.burned_by = Some(burned_by);
-            token_metadata.burned_at = Some(time());
+    /// Whether `spender` may move the token on its owner's behalf at `now`: it
+    /// is the token's DIP721 operator, or holds an unexpired ICRC-37 approval
+    /// for the token or for the owner's whole collection.
+    pub fn is_approved(
+        &self,
+        token_identifier: &TokenId,
+        spender: &AccountIdentifier_shiku,
+        now: u64,
+    ) -> bool {
+        let token_metadata = match self.tokens.get(token_identifier) {
+            Some(token_metadata) if !token_metadata.is_burned => token_metadata,
+            _ => return false,
+        };
+        let owner = match token_metadata.owner {
+            Some(owner) => owner,
+            None => return false,
+        };
+        if token_metadata.operator == Some(*spender) && *spender != owner {
+            return true;
         }
+        self.token_approvals(token_identifier, now)
+            .chain(self.collection_approvals(&owner, now))
+            .any(|approval| approval.spender_aid() == *spender)
+    }
+
+    /// The ICRC-37 approvals for a token that are still active at `now`.
+    pub fn token_approvals(
+        &self,
+        token_identifier: &TokenId,
+        now: u64,
+    ) -> impl Iterator<Item = &ApprovalInfo> {
+        self.token_approvals
+            .get(token_identifier)
+            .into_iter()
+            .flatten()
+            .filter(move |approval| approval.is_active(now))
+    }
+
+    /// The ICRC-37 collection approvals of `owner` that are still active at
+    /// `now`.
+    pub fn collection_approvals(
+        &self,
+        owner: &AccountIdentifier_shiku,
+        now: u64,
+    ) -> impl Iterator<Item = &ApprovalInfo> {
+        self.collection_approvals
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .filter(move |approval| approval.is_active(now))
+    }
+
+    /// Adds `approval` to the active ones, replacing any for the same spender.
+    fn with_approval(
+        approvals: Option<&Vec<ApprovalInfo>>,
+        approval: ApprovalInfo,
+        now: u64,
+    ) -> Result<Vec<ApprovalInfo>, ShikuNftError> {
+        let spender = approval.spender_aid();
+        let mut approvals: Vec<ApprovalInfo> = approvals
+            .into_iter()
+            .flatten()
+            .filter(|existing| existing.is_active(now) && existing.spender_aid() != spender)
+            .cloned()
+            .collect();
+        if approvals.len() as u64 >= MAX_APPROVALS_PER_TOKEN_OR_COLLECTION {
+            return Err(ShikuNftError::Other(format!(
+                "At most {} approvals are allowed",
+                MAX_APPROVALS_PER_TOKEN_OR_COLLECTION
+            )));
+        }
+        approvals.push(approval);
+        Ok(approvals)
+    }
+
+    /// Removes the active approvals for `spender`, or all of them, failing
+    /// with `ApprovalNotFound` when nothing would be removed.
+    fn without_approvals(
+        approvals: Option<&Vec<ApprovalInfo>>,
+        spender: Option<AccountIdentifier_shiku>,
+        now: u64,
+    ) -> Result<Vec<ApprovalInfo>, ShikuNftError> {
+        let active: Vec<&ApprovalInfo> = approvals
+            .into_iter()
+            .flatten()
+            .filter(|approval| approval.is_active(now))
+            .collect();
+        let kept: Vec<ApprovalInfo> = active
+            .iter()
+            .filter(|approval| spender.is_some_and(|spender| approval.spender_aid() != spender))
+            .map(|approval| (*approval).clone())
+            .collect();
+        if kept.len() == active.len() {
+            return Err(ShikuNftError::ApprovalNotFound);
+        }
+        Ok(kept)
+    }
+
+    /// Grants `approval` on a token owned by `approved_by`. Fires the
+    /// `Approve` hooks with the spender as operator.
+    pub fn approve_token(
+        &mut self,
+        approved_by: AccountIdentifier_shiku,
+        token_identifier: &TokenId,
+        approval: ApprovalInfo,
+        now: u64,
+    ) -> Result<(), ShikuNftError> {
+        self.check_not_paused()?;
+        if self.owner_of(token_identifier)? != Some(approved_by) {
+            return Err(ShikuNftError::UnauthorizedOwner);
+        }
+        self.check_not_soulbound(token_identifier)?;
+        let spender = approval.spender_aid();
+        if spender == approved_by {
+            return Err(ShikuNftError::SelfApprove);
+        }
+        let approvals =
+            Self::with_approval(self.token_approvals.get(token_identifier), approval, now)?;
+        let event = LedgerEvent::Approve {
+            token_identifier: token_identifier.clone(),
+            approved_by,
+            operator: Some(spender),
+        };
+        hooks::before(self, &event)?;
+
+        self.token_approvals.insert(token_identifier.clone(), approvals);
+        self.commit(event);
+        Ok(())
+    }
+
+    /// Revokes the token approvals for `spender`, or all of them. Fires the
+    /// `Approve` hooks with no operator.
+    pub fn revoke_token_approvals(
+        &mut self,
+        revoked_by: AccountIdentifier_shiku,
+        token_identifier: &TokenId,
+        spender: Option<AccountIdentifier_shiku>,
+        now: u64,
+    ) -> Result<(), ShikuNftError> {
+        self.check_not_paused()?;
+        if self.owner_of(token_identifier)? != Some(revoked_by) {
+            return Err(ShikuNftError::UnauthorizedOwner);
+        }
+        let approvals =
+            Self::without_approvals(self.token_approvals.get(token_identifier), spender, now)?;
+        let event = LedgerEvent::Approve {
+            token_identifier: token_identifier.clone(),
+            approved_by: revoked_by,
+            operator: None,
+        };
+        hooks::before(self, &event)?;
+
+        if approvals.is_empty() {
+            self.token_approvals.remove(token_identifier);
+        } else {
+            self.token_approvals.insert(token_identifier.clone(), approvals);
+        }
+        self.commit(event);
+        Ok(())
+    }
+
+    /// Grants `approval` on every token `owner` holds, now or later. Fires
+    /// the `ApproveCollection` hooks with the spender as operator.
+    pub fn approve_collection(
+        &mut self,
+        owner: AccountIdentifier_shiku,
+        approval: ApprovalInfo,
+        now: u64,
+    ) -> Result<(), ShikuNftError> {
+        self.check_not_paused()?;
+        if self.soulbound {
+            return Err(ShikuNftError::Soulbound);
+        }
+        if approval.spender_aid() == owner {
+            return Err(ShikuNftError::SelfApprove);
+        }
+        let event = LedgerEvent::ApproveCollection {
+            approved_by: owner,
+            operator: Some(approval.spender_aid()),
+        };
+        let approvals = Self::with_approval(self.collection_approvals.get(&owner), approval, now)?;
+        hooks::before(self, &event)?;
+
+        self.collection_approvals.insert(owner, approvals);
+        self.commit(event);
+        Ok(())
+    }
+
+    /// Revokes the collection approvals of `owner` for `spender`, or all of
+    /// them. Fires the `ApproveCollection` hooks with no operator.
+    pub fn revoke_collection_approvals(
+        &mut self,
+        owner: AccountIdentifier_shiku,
+        spender: Option<AccountIdentifier_shiku>,
+        now: u64,
+    ) -> Result<(), ShikuNftError> {
+        self.check_not_paused()?;
+        let approvals =
+            Self::without_approvals(self.collection_approvals.get(&owner), spender, now)?;
+        let event = LedgerEvent::ApproveCollection {
+            approved_by: owner,
+            operator: None,
+        };
+        hooks::before(self, &event)?;
+
+        if approvals.is_empty() {
+            self.collection_approvals.remove(&owner);
+        } else {
+            self.collection_approvals.insert(owner, approvals);
+        }
+        self.commit(event);
+        Ok(())
+    }
+
+    fn check_not_paused(&self) -> Result<(), ShikuNftError> {
+        if self.paused {
+            return Err(ShikuNftError::Paused);
+        }
+        Ok(())
+    }
+
+    fn check_not_frozen(&self) -> Result<(), ShikuNftError> {
+        if self.metadata_frozen {
+            return Err(ShikuNftError::MetadataFrozen);
+        }
+        Ok(())
+    }
+
+    /// Checks a run of mints to `recipients` against the mint policy, as if
+    /// they happened one after the other. Errors carry the position of the
+    /// first mint the policy refuses.
+    fn check_mint_policy(
+        &self,
+        recipients: impl IntoIterator<Item = Option<AccountIdentifier_shiku>>,
+    ) -> Result<(), (usize, ShikuNftError)> {
+        let policy = &self.mint_policy;
+        let now = now();
+        if policy.mint_start.is_some_and(|start| now < start)
+            || policy.mint_end.is_some_and(|end| now >= end)
+        {
+            return Err((0, ShikuNftError::MintClosed));
+        }
+        let mut minted: HashMap<AccountIdentifier_shiku, u64> = HashMap::new();
+        for (position, recipient) in recipients.into_iter().enumerate() {
+            if let Some(max_supply) = policy.max_supply {
+                if (self.tokens.len() + position) as u64 >= max_supply {
+                    return Err((position, ShikuNftError::SupplyCapReached { max_supply }));
+                }
+            }
+            if let (Some(max_per_account), Some(recipient)) = (policy.max_per_account, recipient) {
+                let count = minted.entry(recipient).or_insert_with(|| {
+                    self.mints_per_account.get(&recipient).copied().unwrap_or(0)
+                });
+                if *count >= max_per_account {
+                    return Err((position, ShikuNftError::MintLimitReached { max_per_account }));
+                }
+                *count += 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_mint_policy(&mut self, mint_policy: MintPolicy) {
+        self.mint_policy = mint_policy;
+    }
+
+    fn check_not_soulbound(&self, token_identifier: &TokenId) -> Result<(), ShikuNftError> {
+        if self.token_metadata(token_identifier)?.soulbound {
+            return Err(ShikuNftError::Soulbound);
+        }
+        Ok(())
+    }
+
+    /// The status a transfer or burn of the token leads to, if it may happen
+    /// right now.
+    fn check_movable(
+        &self,
+        token_identifier: &TokenId,
+        action: TokenAction,
+    ) -> Result<TokenStatus, ShikuNftError> {
+        self.check_not_paused()?;
+        self.status_of(token_identifier)?.after(action)
+    }
+
+    /// Pauses or resumes every mint, transfer, burn, approval and
+    /// revocation.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Freezes the collection and token metadata for good.
+    pub fn freeze_metadata(&mut self) {
+        self.metadata_frozen = true;
+    }
+
+    /// Applies `edit` to the collection metadata, unless it is frozen.
+    pub fn edit_metadata(&mut self, edit: impl FnOnce(&mut MetaData)) -> Result<(), ShikuNftError> {
+        self.check_not_frozen()?;
+        edit(&mut self.metadata);
+        Ok(())
+    }
+
+    pub fn status_of(&self, token_identifier: &TokenId) -> Result<TokenStatus, ShikuNftError> {
+        self.token_metadata(token_identifier)
+            .map(|token_metadata| token_metadata.status)
+    }
+
+    /// Applies an action that changes the token's status without moving it,
+    /// such as locking or listing it. Transfers and burns go through
+    /// [`Ledger::transfer`] and [`Ledger::burn`].
+    pub fn change_status(
+        &mut self,
+        changed_by: AccountIdentifier_shiku,
+        token_identifier: &TokenId,
+        action: TokenAction,
+    ) -> Result<TokenStatus, ShikuNftError> {
+        self.check_not_paused()?;
+        if matches!(action, TokenAction::Transfer | TokenAction::Burn) {
+            return Err(ShikuNftError::Other(format!(
+                "{} changes the owner, not only the status",
+                action
+            )));
+        }
+        if action == TokenAction::List {
+            self.check_not_soulbound(token_identifier)?;
+        }
+        let status = self.status_of(token_identifier)?.after(action)?;
+        let event = LedgerEvent::ChangeStatus {
+            token_identifier: token_identifier.clone(),
+            changed_by,
+            action,
+            status,
+        };
+        hooks::before(self, &event)?;
+
+        if let Some(token_metadata) = self.tokens.get_mut(token_identifier) {
+            token_metadata.status = status;
+        }
+        self.commit(event);
+        Ok(status)
+    }
+
+    /// A locked token can still be queried and approved, but neither
+    /// transferred nor burned.
+    pub fn is_locked(&self, token_identifier: &TokenId) -> bool {
+        self.status_of(token_identifier) == Ok(TokenStatus::Locked)
+    }
+
+    /// The ids of the tokens in `status` after `prev`, in ascending order.
+    pub fn tokens_with_status<'a>(
+        &'a self,
+        status: TokenStatus,
+        prev: Option<&'a TokenId>,
+    ) -> impl Iterator<Item = &'a TokenId> {
+        self.tokens
+            .range(after(prev))
+            .filter(move |(_, token_metadata)| token_metadata.status == status)
+            .map(|(token_identifier, _)| token_identifier)
+    }
+
+    /// The number of transactions logged, counting those from before
+    /// [`Ledger::log_start`].
+    pub fn log_length(&self) -> u64 {
+        self.log_start.saturating_add(self.blocks.len() as u64)
+    }
+
+    /// The block at `index` of the log, unless it is past the end or from
+    /// before [`Ledger::log_start`].
+    pub fn block(&self, index: u64) -> Option<&Value> {
+        let position = index.checked_sub(self.log_start)?;
+        self.blocks.get(usize::try_from(position).ok()?)
+    }
+
+    /// The index and hash of the last block, if any.
+    pub fn tip(&self) -> Option<(Nat, [u8; 32])> {
+        let block = self.blocks.last()?;
+        Some((Nat::from(self.log_length() - 1), icrc3::hash_value(block)))
+    }
+
+    /// Appends the block for `event` to the log and queues it for the
+    /// `after_*` hooks.
+    fn commit(&mut self, event: LedgerEvent) {
+        let phash = self.tip().map(|(_, hash)| hash);
+        let block = icrc3::event_block(self, &event, phash, now());
+        self.blocks.push(block);
+        hooks::committed(event);
+    }
+
+    /// Puts each token into `class`, all or none of them. A token's class is
+    /// set once and for all, which keeps positions within a class stable.
+    pub fn set_class(&mut self, class: &str, tokens: &[TokenId]) -> Result<(), ShikuNftError> {
+        self.check_not_frozen()?;
+        for token_identifier in tokens {
+            self.token_metadata(token_identifier)?;
+            match self.class_of(token_identifier) {
+                Some(current) if current != class => {
+                    return Err(ShikuNftError::Other(format!(
+                        "token {} already belongs to class {}",
+                        token_identifier, current
+                    )));
+                }
+                _ => {}
+            }
+        }
+        for token_identifier in tokens {
+            self.token_classes
+                .insert(token_identifier.clone(), class.to_string());
+            self.classes
+                .entry(class.to_string())
+                .or_default()
+                .insert(token_identifier.clone());
+        }
+        Ok(())
+    }
+
+    pub fn class_of(&self, token_identifier: &TokenId) -> Option<&str> {
+        self.token_classes.get(token_identifier).map(String::as_str)
+    }
+
+    /// The ids at positions `start..end` of `class`, whose tokens are ordered
+    /// by id. Burned tokens keep their position.
+    pub fn class_range(
+        &self,
+        class: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<TokenId>, ShikuNftError> {
+        let tokens = self
+            .classes
+            .get(class)
+            .ok_or_else(|| ShikuNftError::Other(format!("unknown class {}", class)))?;
+        if start >= end || end > tokens.len() {
+            return Err(ShikuNftError::Other(format!(
+                "range {}..{} is not within class {} of {} tokens",
+                start,
+                end,
+                class,
+                tokens.len()
+            )));
+        }
+        Ok(tokens.iter().skip(start).take(end - start).cloned().collect())
+    }
+
+    fn class_tokens<'a>(&'a self, class: &str) -> impl Iterator<Item = &'a TokenMetadata> + 'a {
+        self.classes
+            .get(class)
+            .into_iter()
+            .flatten()
+            .filter_map(move |token_identifier| self.tokens.get(token_identifier))
+            .filter(|token_metadata| !token_metadata.is_burned)
+    }
+
+    /// The number of live tokens in `class`.
+    pub fn class_supply(&self, class: &str) -> usize {
+        self.class_tokens(class).count()
+    }
+
+    /// The number of tokens of `class` held by `owner`.
+    pub fn class_balance(&self, class: &str, owner: &AccountIdentifier_shiku) -> usize {
+        self.class_tokens(class)
+            .filter(|token_metadata| token_metadata.owner.as_ref() == Some(owner))
+            .count()
+    }
+
+    /// The transaction index recorded for `key`, if the call already ran.
+    pub fn duplicate_of(&self, key: &TxKey) -> Option<Nat> {
+        self.recent_txs.get(key).cloned()
+    }
+
+    /// Records that the call identified by `key` produced transaction `index`,
+    /// and forgets the calls that fell out of the window at `now`.
+    pub fn record_tx(&mut self, key: TxKey, index: Nat, now: u64) {
+        let oldest = now.saturating_sub(TX_WINDOW + PERMITTED_DRIFT);
+        self.recent_txs = self.recent_txs.split_off(&TxKey {
+            created_at_time: oldest,
+            digest: Vec::new(),
+        });
+        self.recent_txs.insert(key, index);
+    }
+
+    /// Runs `f`, which returns the index of the transaction it committed,
+    /// unless the call identified by `key` already ran within the window: then
+    /// `duplicate` gets the original index instead. Calls without a key are
+    /// never deduplicated.
+    ///
+    /// What a retry returns follows each standard:
+    ///
+    /// - `dip721_mint`, `dip721_transfer_from` and the EXT `transfer` succeed
+    ///   again, with the original index or balance, as DIP-721 and EXT have
+    ///   no duplicate error;
+    /// - `icrc7_transfer` and `icrc37_transfer_from` fail with
+    ///   `Duplicate { duplicate_of }`, as ICRC-7 and ICRC-37 require.
+    pub fn deduplicate<E>(
+        &mut self,
+        key: Option<TxKey>,
+        now: u64,
+        duplicate: impl FnOnce(Nat) -> Result<Nat, E>,
+        f: impl FnOnce(&mut Ledger) -> Result<Nat, E>,
+    ) -> Result<Nat, E> {
+        if let Some(index) = key.as_ref().and_then(|key| self.duplicate_of(key)) {
+            return duplicate(index);
+        }
+        let index = f(self)?;
+        if let Some(key) = key {
+            self.record_tx(key, index.clone(), now);
+        }
+        Ok(index)
+    }
 
     pub fn inc_tx(&mut self) -> Nat {
         self.tx_count += 1;
         self.tx_count.clone()
     }
-}
\ No newline at end of file
+
+    /// Counts the transaction committed last and returns its index, the
+    /// index of its block in the log.
+    pub fn tx_index(&mut self) -> Nat {
+        self.inc_tx();
+        Nat::from(self.log_length().saturating_sub(1))
+    }
+
+    /// [`Ledger::tx_index`] for the `count` transactions committed last, in
+    /// the order they were committed.
+    pub fn tx_indexes(&mut self, count: usize) -> Vec<Nat> {
+        let end = self.log_length();
+        (end.saturating_sub(count as u64)..end)
+            .map(|index| {
+                self.inc_tx();
+                Nat::from(index)
+            })
+            .collect()
+    }
+}
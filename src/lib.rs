@@ -4,15 +4,40 @@
 
 pub mod token_identifier;
 pub mod dip721;
+pub mod ext;
+pub mod certification;
+pub mod icrc3;
+pub mod icrc7;
+pub mod icrc37;
 pub mod account_identifier;
 pub mod types;
 pub mod ledger;
+pub mod status;
+pub mod hooks;
+pub mod stable;
+pub mod error;
+pub mod interface;
+pub mod canister;
 
 pub use token_identifier::*;
 pub use dip721::*;
+pub use ext::*;
+pub use certification::*;
+pub use icrc3::*;
+pub use icrc7::*;
+pub use icrc37::*;
 pub use types::*;
 pub use account_identifier::*;
 pub use ledger::*;
+pub use status::*;
+pub use hooks::*;
+pub use error::*;
+pub use interface::*;
+
+#[doc(hidden)]
+pub use ic_cdk;
+#[doc(hidden)]
+pub use ic_cdk_macros;
 
 // #[doc(hidden)]
 // pub mod prelude {
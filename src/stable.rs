@@ -0,0 +1,246 @@
+use candid::de::IDLDeserialize;
+use candid::utils::ArgumentDecoder;
+use ic_cdk::export::candid::{CandidType, Deserialize, Nat};
+use ic_cdk::export::Principal;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::account_identifier::Account;
+use crate::certification::CertifiedState;
+use crate::icrc37::ApprovalInfo;
+use crate::icrc7::Value;
+use crate::ledger::{Ledger, TxKey};
+use crate::status::TokenStatus;
+use crate::types::{
+    AccountIdentifier_shiku, AccountIdentifier__1, MetaData, MintPolicy, TokenId, TokenIndexU32,
+    TokenMetadata,
+};
+
+/// The ledger as saved to stable memory. A layout is never changed once
+/// released: a new one gets a new variant, and the old one a frozen copy of
+/// its types to migrate from.
+#[derive(CandidType, Deserialize)]
+pub enum StableLedger {
+    V1(LedgerV1),
+}
+
+/// Everything saved across upgrades: the ledger, the token id counter, the
+/// minted ids and the minter.
+pub type StableState = (Ledger, u32, Vec<Nat>, Principal);
+
+/// The ledger as saved in [`StableLedger::V1`]. The certified state is left
+/// out, as it is rebuilt from the owners after loading.
+#[derive(CandidType, Deserialize)]
+pub struct LedgerV1 {
+    pub metadata: MetaData,
+    pub tokens: BTreeMap<TokenId, TokenMetadata>,
+    pub owners: HashMap<AccountIdentifier_shiku, BTreeSet<TokenId>>,
+    pub operators: HashMap<AccountIdentifier_shiku, BTreeSet<TokenId>>,
+    pub custodians: HashSet<AccountIdentifier_shiku>,
+    pub idx2aid: BTreeMap<TokenIndexU32, AccountIdentifier__1>,
+    pub tx_count: Nat,
+    pub accounts: HashMap<AccountIdentifier_shiku, Account>,
+    pub token_approvals: HashMap<TokenId, Vec<ApprovalInfo>>,
+    pub collection_approvals: HashMap<AccountIdentifier_shiku, Vec<ApprovalInfo>>,
+    pub blocks: Vec<Value>,
+    pub log_start: u64,
+    pub recent_txs: BTreeMap<TxKey, Nat>,
+    pub classes: HashMap<String, BTreeSet<TokenId>>,
+    pub token_classes: HashMap<TokenId, String>,
+    pub paused: bool,
+    pub metadata_frozen: bool,
+    pub soulbound: bool,
+    pub mint_policy: MintPolicy,
+    pub mints_per_account: HashMap<AccountIdentifier_shiku, u64>,
+}
+
+impl From<Ledger> for LedgerV1 {
+    fn from(ledger: Ledger) -> LedgerV1 {
+        LedgerV1 {
+            metadata: ledger.metadata,
+            tokens: ledger.tokens,
+            owners: ledger.owners,
+            operators: ledger.operators,
+            custodians: ledger.custodians,
+            idx2aid: ledger.idx2aid,
+            tx_count: ledger.tx_count,
+            accounts: ledger.accounts,
+            token_approvals: ledger.token_approvals,
+            collection_approvals: ledger.collection_approvals,
+            blocks: ledger.blocks,
+            log_start: ledger.log_start,
+            recent_txs: ledger.recent_txs,
+            classes: ledger.classes,
+            token_classes: ledger.token_classes,
+            paused: ledger.paused,
+            metadata_frozen: ledger.metadata_frozen,
+            soulbound: ledger.soulbound,
+            mint_policy: ledger.mint_policy,
+            mints_per_account: ledger.mints_per_account,
+        }
+    }
+}
+
+impl From<LedgerV1> for Ledger {
+    fn from(saved: LedgerV1) -> Ledger {
+        let mut ledger = Ledger {
+            metadata: saved.metadata,
+            tokens: saved.tokens,
+            owners: saved.owners,
+            operators: saved.operators,
+            custodians: saved.custodians,
+            idx2aid: saved.idx2aid,
+            tx_count: saved.tx_count,
+            accounts: saved.accounts,
+            token_approvals: saved.token_approvals,
+            collection_approvals: saved.collection_approvals,
+            blocks: saved.blocks,
+            log_start: saved.log_start,
+            recent_txs: saved.recent_txs,
+            classes: saved.classes,
+            token_classes: saved.token_classes,
+            paused: saved.paused,
+            metadata_frozen: saved.metadata_frozen,
+            soulbound: saved.soulbound,
+            mint_policy: saved.mint_policy,
+            mints_per_account: saved.mints_per_account,
+            certified: CertifiedState::default(),
+        };
+        ledger.certified = CertifiedState::of(&ledger);
+        ledger
+    }
+}
+
+/// The ledger as saved before [`StableLedger`], with neither accounts,
+/// approvals, blocks nor any later field.
+#[derive(CandidType, Deserialize)]
+pub struct LedgerV0 {
+    pub metadata: MetaData,
+    pub tokens: HashMap<TokenId, TokenMetadataV0>,
+    pub owners: HashMap<AccountIdentifier_shiku, HashSet<TokenId>>,
+    pub operators: HashMap<AccountIdentifier_shiku, HashSet<TokenId>>,
+    pub custodians: HashSet<AccountIdentifier_shiku>,
+    pub idx2aid: HashMap<TokenIndexU32, AccountIdentifier__1>,
+    pub tx_count: Nat,
+}
+
+/// A token as saved in a [`LedgerV0`], whose status was a number that was
+/// never anything but 1.
+#[derive(CandidType, Deserialize)]
+pub struct TokenMetadataV0 {
+    pub token_identifier: Nat,
+    pub owner: Option<AccountIdentifier_shiku>,
+    pub operator: Option<AccountIdentifier_shiku>,
+    pub is_burned: bool,
+    pub properties: Option<MetaData>,
+    pub minted_at: u64,
+    pub minted_by: AccountIdentifier_shiku,
+    pub transferred_at: Option<u64>,
+    pub transferred_by: Option<AccountIdentifier_shiku>,
+    pub approved_at: Option<u64>,
+    pub approved_by: Option<AccountIdentifier_shiku>,
+    pub burned_at: Option<u64>,
+    pub burned_by: Option<AccountIdentifier_shiku>,
+    pub status: u32,
+}
+
+impl From<TokenMetadataV0> for TokenMetadata {
+    fn from(token: TokenMetadataV0) -> TokenMetadata {
+        TokenMetadata {
+            token_identifier: token.token_identifier,
+            owner: token.owner,
+            operator: token.operator,
+            is_burned: token.is_burned,
+            properties: token.properties,
+            minted_at: token.minted_at,
+            minted_by: token.minted_by,
+            transferred_at: token.transferred_at,
+            transferred_by: token.transferred_by,
+            approved_at: token.approved_at,
+            approved_by: token.approved_by,
+            burned_at: token.burned_at,
+            burned_by: token.burned_by,
+            status: if token.is_burned { TokenStatus::Burned } else { TokenStatus::Active },
+            soulbound: false,
+        }
+    }
+}
+
+/// Starts the approvals and the policies afresh, and the log after the
+/// transactions already counted, whose indexes were handed out. Tokens were
+/// always minted by their first owner, which gives back the mints per account.
+impl From<LedgerV0> for LedgerV1 {
+    fn from(saved: LedgerV0) -> LedgerV1 {
+        let log_start = u64::try_from(&saved.tx_count.0).unwrap_or(u64::MAX);
+        let mut ledger = LedgerV1 {
+            metadata: saved.metadata,
+            tokens: BTreeMap::new(),
+            owners: saved
+                .owners
+                .into_iter()
+                .map(|(owner, tokens)| (owner, tokens.into_iter().collect()))
+                .collect(),
+            operators: saved
+                .operators
+                .into_iter()
+                .map(|(operator, tokens)| (operator, tokens.into_iter().collect()))
+                .collect(),
+            custodians: saved.custodians,
+            idx2aid: saved.idx2aid.into_iter().collect(),
+            tx_count: saved.tx_count,
+            accounts: HashMap::new(),
+            token_approvals: HashMap::new(),
+            collection_approvals: HashMap::new(),
+            blocks: Vec::new(),
+            log_start,
+            recent_txs: BTreeMap::new(),
+            classes: HashMap::new(),
+            token_classes: HashMap::new(),
+            paused: false,
+            metadata_frozen: false,
+            soulbound: false,
+            mint_policy: MintPolicy::default(),
+            mints_per_account: HashMap::new(),
+        };
+        for (token_identifier, token) in saved.tokens {
+            *ledger.mints_per_account.entry(token.minted_by).or_default() += 1;
+            ledger.tokens.insert(token_identifier, token.into());
+        }
+        ledger
+    }
+}
+
+fn decode_as<L>(bytes: &[u8]) -> Result<(L, u32, Vec<Nat>, Principal), String>
+where
+    L: CandidType + for<'de> Deserialize<'de>,
+{
+    let mut de = IDLDeserialize::new(bytes).map_err(|e| e.to_string())?;
+    ArgumentDecoder::decode(&mut de).map_err(|e| e.to_string())
+}
+
+/// Decodes the state [`encode`] wrote, or the unversioned one saved before
+/// [`StableLedger`], which is migrated. Bytes after the state, such as the
+/// rest of a stable memory page, are ignored.
+pub fn decode(bytes: &[u8]) -> Result<StableState, String> {
+    match decode_as::<StableLedger>(bytes) {
+        Ok((StableLedger::V1(ledger), tid, minted, minter)) => {
+            Ok((ledger.into(), tid, minted, minter))
+        }
+        Err(e) => match decode_as::<LedgerV0>(bytes) {
+            Ok((ledger, tid, minted, minter)) => {
+                Ok((LedgerV1::from(ledger).into(), tid, minted, minter))
+            }
+            Err(_) => Err(e),
+        },
+    }
+}
+
+/// Encodes the state in the current layout, as [`decode`] reads it back.
+/// Takes the ledger by value, which saves it without a copy.
+pub fn encode(
+    ledger: Ledger,
+    tid: u32,
+    minted: &[Nat],
+    minter: Principal,
+) -> Result<Vec<u8>, candid::Error> {
+    candid::encode_args((StableLedger::V1(ledger.into()), tid, minted, minter))
+}
@@ -0,0 +1,70 @@
+use std::fmt::{Display, Formatter};
+
+use ic_cdk::export::candid::{CandidType, Deserialize};
+
+use crate::error::ShikuNftError;
+
+/// Where a token stands. Tokens are minted `Active`, and only move between
+/// statuses through the actions of [`TRANSITIONS`].
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TokenStatus {
+    #[default]
+    Active,
+    /// Held in place by a custodian.
+    Locked,
+    Staked,
+    /// Offered for sale. A sale is a transfer, which makes it `Active` again.
+    Listed,
+    Burned,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenAction {
+    Transfer,
+    Burn,
+    Lock,
+    Unlock,
+    Stake,
+    Unstake,
+    List,
+    Delist,
+}
+
+/// Every allowed `(status, action, next status)`. Anything else is refused.
+pub const TRANSITIONS: [(TokenStatus, TokenAction, TokenStatus); 9] = [
+    (TokenStatus::Active, TokenAction::Transfer, TokenStatus::Active),
+    (TokenStatus::Active, TokenAction::Burn, TokenStatus::Burned),
+    (TokenStatus::Active, TokenAction::Lock, TokenStatus::Locked),
+    (TokenStatus::Locked, TokenAction::Unlock, TokenStatus::Active),
+    (TokenStatus::Active, TokenAction::Stake, TokenStatus::Staked),
+    (TokenStatus::Staked, TokenAction::Unstake, TokenStatus::Active),
+    (TokenStatus::Active, TokenAction::List, TokenStatus::Listed),
+    (TokenStatus::Listed, TokenAction::Delist, TokenStatus::Active),
+    (TokenStatus::Listed, TokenAction::Transfer, TokenStatus::Active),
+];
+
+impl TokenStatus {
+    /// The status `action` leads to, if [`TRANSITIONS`] allows it.
+    pub fn after(self, action: TokenAction) -> Result<TokenStatus, ShikuNftError> {
+        TRANSITIONS
+            .iter()
+            .find(|(from, allowed, _)| *from == self && *allowed == action)
+            .map(|(_, _, next)| *next)
+            .ok_or(match self {
+                TokenStatus::Locked => ShikuNftError::TokenLocked,
+                status => ShikuNftError::InvalidTransition { status, action },
+            })
+    }
+}
+
+impl Display for TokenStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl Display for TokenAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
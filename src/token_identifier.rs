@@ -1,7 +1,9 @@
 
-use candid::{CandidType, Deserialize, Principal};
+use candid::{CandidType, Deserialize, Nat, Principal};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
 
-use crate::types::{CommonError};
+use crate::types::{CommonError, TokenId, TokenIndexU32};
 
 pub const CANISTER_ID_HASH_LEN_IN_BYTES: usize = 10;
 const TOKEN_ID_PREFIX: [u8; 4] = [10, 116, 105, 100]; //b"\x0Atid"
@@ -16,6 +18,120 @@ impl TokenIndex {
         self.0
     }
 }
+/// A token, however it was named: the ledger's [`TokenId`], the registry's
+/// [`TokenIndexU32`] or an EXT [`TokenIdentifier`].
+///
+/// Conversions from `Nat` fail for values that do not fit in a `u32`. Text is
+/// accepted either as a decimal index or as an EXT token identifier.
+#[derive(Default, Copy, Clone, Hash, Eq, PartialEq, Debug, Ord, PartialOrd)]
+pub struct TokenRef(pub TokenIndex);
+
+impl TokenRef {
+    pub fn index(&self) -> TokenIndexU32 {
+        self.0.get_value()
+    }
+
+    pub fn to_token_id(&self) -> TokenId {
+        Nat::from(self.index())
+    }
+
+    pub fn to_token_identifier(&self, canister_id: CanisterId) -> TokenIdentifier {
+        encode_token_id(canister_id, self.0)
+    }
+
+    /// Decodes an EXT token identifier, rejecting tokens of other canisters.
+    pub fn from_token_identifier(
+        tid: &TokenIdentifier,
+        canister_id: &CanisterId,
+    ) -> NFTServiceResult<TokenRef> {
+        get_canister_token_index(tid, canister_id).map(TokenRef)
+    }
+}
+
+impl Display for TokenRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.index())
+    }
+}
+
+impl From<TokenIndex> for TokenRef {
+    fn from(index: TokenIndex) -> Self {
+        TokenRef(index)
+    }
+}
+
+impl From<TokenIndexU32> for TokenRef {
+    fn from(index: TokenIndexU32) -> Self {
+        TokenRef(TokenIndex(index))
+    }
+}
+
+impl From<TokenRef> for TokenIndex {
+    fn from(token: TokenRef) -> Self {
+        token.0
+    }
+}
+
+impl From<TokenRef> for TokenIndexU32 {
+    fn from(token: TokenRef) -> Self {
+        token.index()
+    }
+}
+
+impl From<TokenRef> for TokenId {
+    fn from(token: TokenRef) -> Self {
+        token.to_token_id()
+    }
+}
+
+impl TryFrom<&TokenId> for TokenRef {
+    type Error = CommonError;
+
+    fn try_from(token_id: &TokenId) -> Result<Self, Self::Error> {
+        u32::try_from(&token_id.0)
+            .map(TokenRef::from)
+            .map_err(|_| CommonError::InvalidToken(token_id.to_string()))
+    }
+}
+
+impl TryFrom<TokenId> for TokenRef {
+    type Error = CommonError;
+
+    fn try_from(token_id: TokenId) -> Result<Self, Self::Error> {
+        TokenRef::try_from(&token_id)
+    }
+}
+
+impl TryFrom<&str> for TokenRef {
+    type Error = CommonError;
+
+    fn try_from(tid: &str) -> Result<Self, Self::Error> {
+        if !tid.is_empty() && tid.bytes().all(|b| b.is_ascii_digit()) {
+            return tid
+                .parse::<TokenIndexU32>()
+                .map(TokenRef::from)
+                .map_err(|_| CommonError::InvalidToken(tid.to_owned()));
+        }
+        get_token_index(&tid.to_owned()).map(TokenRef)
+    }
+}
+
+impl TryFrom<&TokenIdentifier> for TokenRef {
+    type Error = CommonError;
+
+    fn try_from(tid: &TokenIdentifier) -> Result<Self, Self::Error> {
+        TokenRef::try_from(tid.as_str())
+    }
+}
+
+impl TryFrom<TokenIdentifier> for TokenRef {
+    type Error = CommonError;
+
+    fn try_from(tid: TokenIdentifier) -> Result<Self, Self::Error> {
+        TokenRef::try_from(tid.as_str())
+    }
+}
+
 #[derive(Debug, CandidType, Clone, Deserialize)]
 pub enum ShikuError {
     #[allow(non_camel_case_types)]
@@ -35,47 +151,62 @@ pub struct CanisterId(pub Principal);
 
 pub type TokenIdentifier = String;
 
-// pub fn is_valid_token_id(tid: &TokenIdentifier, p: &CanisterId) -> bool {
-//     let t_parsed = decode_token_id(tid);
-//     match t_parsed {
-//         Ok(t) => t.canister == p.0.as_slice().to_vec(),
-//         Err(_) => false,
-//     }
-// }
+/// Whether `tid` is a well-formed token identifier minted by canister `p`.
+pub fn is_valid_token_id(tid: &TokenIdentifier, p: &CanisterId) -> bool {
+    match decode_token_id(tid) {
+        Ok(t) => t.canister == p.0.as_slice(),
+        Err(_) => false,
+    }
+}
 
-pub fn get_token_index(tid: &TokenIdentifier) -> TokenIndex {
-    let tobj = decode_token_id(tid).unwrap();
-    tobj.index
+pub fn get_token_index(tid: &TokenIdentifier) -> NFTServiceResult<TokenIndex> {
+    decode_token_id(tid).map(|tobj| tobj.index)
 }
-pub type NFTServiceResult<T> = anyhow::Result<T, CommonError>;
 
+/// Same as [`get_token_index`], rejecting tokens minted by any canister but `p`.
+pub fn get_canister_token_index(
+    tid: &TokenIdentifier,
+    p: &CanisterId,
+) -> NFTServiceResult<TokenIndex> {
+    let tobj = decode_token_id(tid)?;
+    if tobj.canister != p.0.as_slice() {
+        return Err(CommonError::InvalidToken(tid.to_owned()));
+    }
+    Ok(tobj.index)
+}
+pub type NFTServiceResult<T> = anyhow::Result<T, CommonError>;
 
+/// Splits a token identifier into its canister id and token index.
+///
+/// The layout is `"\x0Atid" ++ canister ++ index`, with the index as 4
+/// big-endian bytes, so canister ids of any length are accepted.
 pub fn decode_token_id(tid: &TokenIdentifier) -> NFTServiceResult<TokenObj> {
-    let principal_parse_res = Principal::from_text(tid);
-    match principal_parse_res {
-        Ok(principal) => {
-            let bytes = principal.as_slice();
-            if !bytes.starts_with(&TOKEN_ID_PREFIX) {
-                return Err(CommonError::Other(format!("This is not TokenIdentifier")));
-            }
-            let canister: Vec<u8> = bytes[4..(4 + CANISTER_ID_HASH_LEN_IN_BYTES)].to_vec();
-            let mut token_index: [u8; 4] = Default::default();
-            token_index.copy_from_slice(&bytes[14..]);
-
-            return Ok(TokenObj {
-                index: TokenIndex(u32::from_be_bytes(token_index)),
-                canister,
-            });
-        }
-        Err(_) => return Err(CommonError::InvalidToken(tid.to_owned())),
+    let principal =
+        Principal::from_text(tid).map_err(|_| CommonError::InvalidToken(tid.to_owned()))?;
+    let bytes = principal.as_slice();
+    if !bytes.starts_with(&TOKEN_ID_PREFIX) {
+        return Err(CommonError::Other("This is not TokenIdentifier".to_string()));
     }
-}
+    let rest = &bytes[TOKEN_ID_PREFIX.len()..];
+    if rest.len() < 4 {
+        return Err(CommonError::InvalidToken(tid.to_owned()));
+    }
+    let (canister, token_index) = rest.split_at(rest.len() - 4);
+    let mut index: [u8; 4] = Default::default();
+    index.copy_from_slice(token_index);
 
+    Ok(TokenObj {
+        index: TokenIndex(u32::from_be_bytes(index)),
+        canister: canister.to_vec(),
+    })
+}
 
+/// Panics if the canister id is longer than 21 bytes, as the result would not
+/// fit in a principal.
 pub fn encode_token_id(canister_id: CanisterId, token_index: TokenIndex) -> TokenIdentifier {
     let mut blob: Vec<u8> = Vec::new();
     blob.extend_from_slice(&TOKEN_ID_PREFIX);
     blob.extend_from_slice(canister_id.0.as_slice());
     blob.extend_from_slice(&token_index.0.to_be_bytes());
     Principal::from_slice(blob.as_slice()).to_text()
-}
\ No newline at end of file
+}
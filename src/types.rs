@@ -1,8 +1,10 @@
+use crate::error::ShikuNftError;
+use crate::status::TokenStatus;
 use crate::token_identifier;
 use ic_cdk::export::candid::{CandidType, Deserialize, Int, Nat};
 use ic_cdk::export::Principal;
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
 #[derive(CandidType, Deserialize)]
 pub struct InitArgs {
@@ -11,6 +13,32 @@ pub struct InitArgs {
     pub symbol: Option<String>,
     pub custodians: Option<HashSet<Principal>>,
     pub cap: Option<Principal>,
+    /// Makes every token of the collection soulbound.
+    pub soulbound: Option<bool>,
+    pub mint_policy: Option<MintPolicy>,
+}
+
+/// Who may mint.
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MintPhase {
+    /// Only the minter and the custodians.
+    #[default]
+    Custodians,
+    /// Anyone, to their own account, on top of the minter and custodians.
+    Public,
+}
+
+/// Limits every mint, whoever makes it. Times are in nanoseconds since the
+/// epoch.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct MintPolicy {
+    /// The most tokens ever minted, burned ones included.
+    pub max_supply: Option<u64>,
+    /// The most tokens minted to a single account.
+    pub max_per_account: Option<u64>,
+    pub mint_start: Option<u64>,
+    pub mint_end: Option<u64>,
+    pub phase: MintPhase,
 }
 
 
@@ -36,10 +64,10 @@ pub enum SetMinterResponse {
 use std::cell::RefCell;
 thread_local! {
     //minted token
-    static MINTEDID: RefCell<Vec<Nat>> = RefCell::new(Vec::new());
+    static MINTEDID: RefCell<BTreeSet<Nat>> = const { RefCell::new(BTreeSet::new()) };
 
-    //minter
-    static MINTER: RefCell<Principal> = RefCell::new(ic_cdk::api::caller());
+    //minter, unset until a custodian sets one
+    static MINTER: RefCell<Principal> = const { RefCell::new(Principal::anonymous()) };
 
 }
 
@@ -51,12 +79,12 @@ pub fn change_minter_state<T, F: FnOnce(&mut Principal) -> T>(f: F) -> T {
     MINTER.with(|minter| f(&mut minter.borrow_mut()))
 }
 
-pub fn read_minted_state<T, F: FnOnce(&Vec<Nat>) ->T>(f: F) -> T {
+pub fn read_minted_state<T, F: FnOnce(&BTreeSet<Nat>) ->T>(f: F) -> T {
     MINTEDID.with(|minted| f(&minted.borrow()))
 }
 
-pub fn change_minted_state<T, F: FnOnce(&Vec<Nat>) -> T >(f: F) -> T {
-    MINTEDID.with(|minted| f(&minted.borrow_mut()))
+pub fn change_minted_state<T, F: FnOnce(&mut BTreeSet<Nat>) -> T >(f: F) -> T {
+    MINTEDID.with(|minted| f(&mut minted.borrow_mut()))
 }
 
 #[derive(CandidType, Debug, Clone, Deserialize)]
@@ -66,6 +94,24 @@ pub struct Status {
     pub cycles: Nat,
     pub total_unique_holders: Nat,
 }
+
+#[derive(CandidType, Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum SupportedInterface {
+    Approval,
+    Mint,
+    Burn,
+    TransactionHistory,
+}
+
+/// A DIP721 view of an ICRC-3 block. `caller` is the account that made the
+/// change: the spender of a delegated transfer, the `from` account otherwise.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct TxEvent {
+    pub time: u64,
+    pub operation: String,
+    pub details: Vec<(String, GeneralValue)>,
+    pub caller: AccountIdentifier_shiku,
+}
 #[warn(non_camel_case_types)]
 pub type TokenId = Nat;
 
@@ -103,7 +149,9 @@ pub struct TokenMetadata {
     pub approved_by: Option<AccountIdentifier_shiku>,
     pub burned_at: Option<u64>,
     pub burned_by: Option<AccountIdentifier_shiku>,
-    pub status: u32,
+    pub status: TokenStatus,
+    /// A soulbound token can be burned, but never transferred nor approved.
+    pub soulbound: bool,
 }
 
 #[derive(Debug, CandidType)]
@@ -115,6 +163,8 @@ pub enum NftError {
     TokenNotFound,
     ExistedNFT,
     SelfApprove,
+    TxNotFound,
+    Other(String),
 }
 
 /////////////// YUMI TYPES ////////////
@@ -134,10 +184,35 @@ pub enum User {
 }
 
 impl User {
+    /// Resolves the user to an account, validating the length and checksum of
+    /// textual addresses. Hex digits may be given in either case. The
+    /// anonymous principal has no account.
+    pub fn to_account(&self) -> Result<AccountIdentifier_shiku, CommonError> {
+        match self {
+            Self::address(aid) => {
+                let aid = aid.to_lowercase();
+                if aid.len() != 64 {
+                    return Err(CommonError::Other(format!(
+                        "Invalid account identifier {}: expected 64 hex characters but got {}",
+                        aid,
+                        aid.len()
+                    )));
+                }
+                AccountIdentifier_shiku::from_hex(&aid).map_err(|e| {
+                    CommonError::Other(format!("Invalid account identifier {}: {}", aid, e))
+                })
+            }
+            Self::principal(pid) => Ok(pid2aid(pid)?),
+        }
+    }
+
+    /// Returns addresses as given, without validation; see [`User::to_account`].
     pub fn aid(user: User) -> AccountIdentifier {
         match user {
             Self::address(aid) => aid.clone(),
-            Self::principal(pid) => pid2aid(&pid).to_hex(),
+            Self::principal(pid) => {
+                AccountIdentifier_shiku::new(&pid, &ic_ledger_types::DEFAULT_SUBACCOUNT).to_hex()
+            }
         }
     }
 }
@@ -258,29 +333,33 @@ pub struct TransferRequest {
     pub token: token_identifier::TokenIdentifier,
 }
 
-// #[derive(Debug, Clone, CandidType, Deserialize)]
-// pub struct TransferRequestV1 {
-//     pub amount: Balance,
-//     pub from: User,
-//     pub memo: Memo,
-//     pub notify: bool,
-//     pub subaccount: Option<SubAccount>,
-//     pub to: User,
-//     pub class: String,
-//     pub start: usize,
-//     pub end: usize,
-// }
-
-// #[derive(Debug, Clone, CandidType, Deserialize)]
-// pub struct TransferRequestV2 {
-//     pub amount: Balance,
-//     pub from: User,
-//     pub memo: Memo,
-//     pub notify: bool,
-//     pub subaccount: Option<SubAccount>,
-//     pub to: User,
-//     pub token_list: Vec<TokenId>,
-// }
+/// Moves the tokens at positions `start..end` of `class` from `from` to `to`;
+/// `amount` must be the number of tokens.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct TransferRequestV1 {
+    pub amount: Balance,
+    pub from: User,
+    pub memo: Memo,
+    pub notify: bool,
+    pub subaccount: Option<SubAccount>,
+    pub to: User,
+    pub class: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Moves every token of `token_list` from `from` to `to`; `amount` must be
+/// the number of tokens.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct TransferRequestV2 {
+    pub amount: Balance,
+    pub from: User,
+    pub memo: Memo,
+    pub notify: bool,
+    pub subaccount: Option<SubAccount>,
+    pub to: User,
+    pub token_list: Vec<token_identifier::TokenIdentifier>,
+}
 
 pub type Memo = Vec<u8>;
 
@@ -342,17 +421,17 @@ pub enum BalanceResponse {
 }
 #[derive(Debug, Clone, CandidType, Deserialize)]
 pub struct BalanceRequest {
-    token: u64,
-    user: User,
+    pub token: TokenIdentifier__1,
+    pub user: User,
 }
 
-pub fn pid2aid(pid: &Principal) -> AccountIdentifier_shiku {
-    let sub_acc = ic_ledger_types::Subaccount([0u8; 32]);
-    let account_id = ic_ledger_types::AccountIdentifier::new(pid, &sub_acc);
-     match AccountIdentifier_shiku::from_hex(&account_id.to_string()) {
-         Ok(shiku) => shiku,
-         Err(_) => AccountIdentifier_shiku::default(),
-     }
+/// The default-subaccount account of `pid`. Fails for the anonymous
+/// principal, which must never act as a token holder.
+pub fn pid2aid(pid: &Principal) -> Result<AccountIdentifier_shiku, ShikuNftError> {
+    if *pid == Principal::anonymous() {
+        return Err(ShikuNftError::Other("The anonymous principal has no account".to_string()));
+    }
+    Ok(AccountIdentifier_shiku::new(pid, &ic_ledger_types::DEFAULT_SUBACCOUNT))
 }
 
 
@@ -0,0 +1,251 @@
+use candid::Principal;
+use ext_based_dip721_lib::{
+    pid2aid, Account, AccountIdParseError, CommonError, ShikuAccountIdentifier, User,
+};
+use ic_ledger_types::{AccountIdentifier, Subaccount, DEFAULT_SUBACCOUNT};
+use proptest::prelude::*;
+
+fn principal() -> impl Strategy<Value = Principal> {
+    prop::collection::vec(any::<u8>(), 0..=29).prop_map(|bytes| Principal::from_slice(&bytes))
+}
+
+fn subaccount() -> impl Strategy<Value = Subaccount> {
+    prop_oneof![Just([0u8; 32]), any::<[u8; 32]>()].prop_map(Subaccount)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn address_matches_ledger_account_id(pid in principal(), sub in subaccount()) {
+        let expected = AccountIdentifier::new(&pid, &sub);
+        let aid = ShikuAccountIdentifier(expected);
+
+        prop_assert_eq!(&aid.to_address()[..], expected.as_ref());
+        prop_assert_eq!(aid.to_hex(), expected.to_string());
+        prop_assert_eq!(&aid.hash()[..], &expected.as_ref()[4..]);
+        prop_assert_eq!(ShikuAccountIdentifier::from_address(aid.to_address()), Ok(aid));
+        prop_assert_eq!(ShikuAccountIdentifier::from_hash(aid.hash()), aid);
+    }
+
+    #[test]
+    fn slices_and_hex_round_trip(pid in principal(), sub in subaccount()) {
+        let aid = ShikuAccountIdentifier(AccountIdentifier::new(&pid, &sub));
+
+        prop_assert_eq!(ShikuAccountIdentifier::from_slice(&aid.to_address()), Ok(aid));
+        prop_assert_eq!(ShikuAccountIdentifier::from_slice(&aid.hash()), Ok(aid));
+        prop_assert_eq!(ShikuAccountIdentifier::from_hex(&aid.to_hex()), Ok(aid));
+        prop_assert_eq!(ShikuAccountIdentifier::from_hex(&hex::encode(aid.hash())), Ok(aid));
+    }
+
+    #[test]
+    fn corrupted_checksum_is_rejected(pid in principal(), sub in subaccount(), flip in 0usize..32) {
+        let aid = ShikuAccountIdentifier(AccountIdentifier::new(&pid, &sub));
+        let mut address = aid.to_address();
+        address[flip] ^= 0x01;
+
+        prop_assert!(ShikuAccountIdentifier::from_address(address).is_err());
+        prop_assert!(matches!(
+            ShikuAccountIdentifier::from_slice(&address),
+            Err(AccountIdParseError::InvalidChecksum(_))
+        ));
+    }
+}
+
+#[test]
+fn other_lengths_are_rejected() {
+    for len in [0, 27, 29, 31, 33] {
+        assert_eq!(
+            ShikuAccountIdentifier::from_slice(&vec![0u8; len]),
+            Err(AccountIdParseError::InvalidLength(vec![0u8; len]))
+        );
+    }
+}
+
+const ICRC1_OWNER: &str = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+
+#[test]
+fn icrc1_textual_encoding() {
+    let owner = Principal::from_text(ICRC1_OWNER).unwrap();
+    let mut sub = [0u8; 32];
+    sub.iter_mut()
+        .enumerate()
+        .for_each(|(i, b)| *b = i as u8 + 1);
+    let mut one = [0u8; 32];
+    one[31] = 1;
+
+    let cases = [
+        (
+            Account {
+                owner,
+                subaccount: None,
+            },
+            ICRC1_OWNER.to_string(),
+        ),
+        (
+            Account {
+                owner,
+                subaccount: Some(Subaccount([0u8; 32])),
+            },
+            ICRC1_OWNER.to_string(),
+        ),
+        (
+            Account {
+                owner,
+                subaccount: Some(Subaccount(one)),
+            },
+            format!("{}-6cc627i.1", ICRC1_OWNER),
+        ),
+        (
+            Account {
+                owner,
+                subaccount: Some(Subaccount(sub)),
+            },
+            format!(
+                "{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+                ICRC1_OWNER
+            ),
+        ),
+    ];
+    for (account, text) in cases {
+        assert_eq!(account.to_string(), text);
+        assert_eq!(text.parse::<Account>(), Ok(account));
+        assert_eq!(
+            ShikuAccountIdentifier::from(account),
+            ShikuAccountIdentifier(AccountIdentifier::new(
+                &owner,
+                &account.subaccount.unwrap_or(Subaccount([0u8; 32]))
+            ))
+        );
+    }
+}
+
+#[test]
+fn icrc1_textual_encoding_rejects_bad_input() {
+    for text in [
+        format!("{}-6cc627j.1", ICRC1_OWNER),
+        format!("{}-6cc627i.01", ICRC1_OWNER),
+        format!("{}.1", ICRC1_OWNER),
+        format!("{}-6cc627i.", ICRC1_OWNER),
+    ] {
+        assert!(
+            text.parse::<Account>().is_err(),
+            "{} should not parse",
+            text
+        );
+    }
+}
+
+#[test]
+fn pid2aid_rejects_the_anonymous_principal() {
+    assert!(pid2aid(&Principal::anonymous()).is_err());
+    let pid = Principal::from_text(ICRC1_OWNER).unwrap();
+    assert_eq!(
+        pid2aid(&pid),
+        Ok(ShikuAccountIdentifier(AccountIdentifier::new(
+            &pid,
+            &Subaccount([0u8; 32])
+        )))
+    );
+}
+
+#[test]
+fn account_candid_round_trip() {
+    let account = Account {
+        owner: Principal::from_text(ICRC1_OWNER).unwrap(),
+        subaccount: Some(Subaccount([7u8; 32])),
+    };
+    let bytes = candid::encode_one(account).unwrap();
+    assert_eq!(candid::decode_one::<Account>(&bytes).unwrap(), account);
+}
+
+proptest! {
+    #[test]
+    fn candid_and_json_round_trip(pid in principal(), sub in subaccount()) {
+        let aid = ShikuAccountIdentifier::new(&pid, &sub);
+
+        let bytes = candid::encode_one(aid).unwrap();
+        prop_assert_eq!(candid::decode_one::<String>(&bytes).unwrap(), aid.to_hex());
+        prop_assert_eq!(candid::decode_one::<ShikuAccountIdentifier>(&bytes).unwrap(), aid);
+
+        let json = serde_json::to_string(&aid).unwrap();
+        prop_assert_eq!(&json, &format!("\"{}\"", aid.to_hex()));
+        prop_assert_eq!(serde_json::from_str::<ShikuAccountIdentifier>(&json).unwrap(), aid);
+    }
+
+    #[test]
+    fn blob_and_hash_forms_decode(pid in principal(), sub in subaccount()) {
+        let aid = ShikuAccountIdentifier::new(&pid, &sub);
+
+        let address = serde_json::to_string(&aid.to_address().to_vec()).unwrap();
+        prop_assert_eq!(serde_json::from_str::<ShikuAccountIdentifier>(&address).unwrap(), aid);
+
+        let hash = format!("\"{}\"", hex::encode(aid.hash()));
+        prop_assert_eq!(serde_json::from_str::<ShikuAccountIdentifier>(&hash).unwrap(), aid);
+
+        let text = candid::encode_one(hex::encode(aid.hash())).unwrap();
+        prop_assert_eq!(candid::decode_one::<ShikuAccountIdentifier>(&text).unwrap(), aid);
+    }
+}
+
+#[test]
+fn decoding_errors_are_descriptive() {
+    let aid = ShikuAccountIdentifier::default();
+    let mut address = aid.to_address();
+    address[0] ^= 0xff;
+
+    let bad_checksum = candid::encode_one(hex::encode(address)).unwrap();
+    let err = candid::decode_one::<ShikuAccountIdentifier>(&bad_checksum).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("Checksum failed"),
+        "{:#}",
+        err
+    );
+
+    let err = serde_json::from_str::<ShikuAccountIdentifier>("\"abcd\"").unwrap_err();
+    assert!(
+        err.to_string().contains("expected a length of 64 or 56"),
+        "{}",
+        err
+    );
+
+    let err = serde_json::from_str::<ShikuAccountIdentifier>("[1, 2, 3]").unwrap_err();
+    assert!(err.to_string().contains("length 3 bytes"), "{}", err);
+
+    let err = serde_json::from_str::<ShikuAccountIdentifier>("\"not hex\"").unwrap_err();
+    assert!(
+        err.to_string().contains("invalid account identifier"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn user_to_account_validates_addresses() {
+    let aid = ShikuAccountIdentifier::new(&Principal::management_canister(), &DEFAULT_SUBACCOUNT);
+
+    let upper = User::address(aid.to_hex().to_uppercase());
+    assert_eq!(upper.to_account().unwrap(), aid);
+    assert_eq!(
+        User::principal(Principal::management_canister())
+            .to_account()
+            .unwrap(),
+        aid
+    );
+
+    let hash = User::address(hex::encode(aid.hash()));
+    assert!(matches!(hash.to_account(), Err(CommonError::Other(e)) if e.contains("expected 64")));
+
+    let mut address = aid.to_address();
+    address[31] ^= 1;
+    let corrupted = User::address(hex::encode(address));
+    assert!(matches!(corrupted.to_account(), Err(CommonError::Other(e)) if e.contains("Checksum")));
+
+    let not_hex = User::address("z".repeat(64));
+    assert!(not_hex.to_account().is_err());
+
+    let anonymous = User::principal(Principal::anonymous());
+    assert!(
+        matches!(anonymous.to_account(), Err(CommonError::Other(e)) if e.contains("anonymous"))
+    );
+}
@@ -0,0 +1,279 @@
+use candid::types::internal::TypeContainer;
+use candid::types::subtype::subtype;
+use candid::types::{CandidType, Field, Function, Label, Type};
+use candid::utils::CandidSource;
+use candid::{Nat, TypeEnv};
+use ext_based_dip721_lib::*;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// DIP721 v2 methods that this crate exports under the same name but keyed
+/// on account identifiers (`text`) instead of principals. Token properties
+/// are also kept as collection-style `MetaData` rather than `GenericValue`
+/// pairs. Both sides of these are compared with [`erase_accounts`].
+const DIP721_ACCOUNT_ID_DEVIATIONS: &[&str] = &[
+    "dip721_approve",
+    "dip721_balance_of",
+    "dip721_mint",
+    "dip721_operator_of",
+    "dip721_operator_token_identifiers",
+    "dip721_operator_token_metadata",
+    "dip721_owner_of",
+    "dip721_owner_token_identifiers",
+    "dip721_owner_token_metadata",
+    "dip721_token_metadata",
+    "dip721_transaction",
+    "dip721_transfer",
+    "dip721_transfer_from",
+];
+
+fn load_reference(name: &str) -> (TypeEnv, Type) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/did")
+        .join(name);
+    let (env, actor) = CandidSource::File(&path).load().unwrap();
+    (env, actor.unwrap())
+}
+
+fn load_interface(env: &mut TypeEnv) -> Type {
+    let (ours, actor) = CandidSource::Text(&candid_interface()).load().unwrap();
+    env.merge_type(ours, actor.unwrap())
+}
+
+fn rust_type<T: CandidType>(env: &mut TypeEnv) -> Type {
+    let mut container = TypeContainer::new();
+    let ty = container.add::<T>();
+    env.merge_type(container.env, ty)
+}
+
+fn is_subtype(env: &TypeEnv, t1: &Type, t2: &Type) -> candid::Result<()> {
+    subtype(&mut HashSet::new(), env, t1, t2)
+}
+
+/// Turns every principal of `ty` into `text` and every token `properties`
+/// field into `reserved`, leaving the rest of the type as it is.
+fn erase_accounts(ty: &Type) -> Type {
+    let fields = |fields: &[Field]| -> Vec<Field> {
+        fields
+            .iter()
+            .map(|field| Field {
+                id: field.id.clone(),
+                ty: match &field.id {
+                    Label::Named(name) if name == "properties" => Type::Reserved,
+                    _ => erase_accounts(&field.ty),
+                },
+            })
+            .collect()
+    };
+    let types = |types: &[Type]| types.iter().map(erase_accounts).collect();
+    match ty {
+        Type::Principal => Type::Text,
+        Type::Opt(ty) => Type::Opt(Box::new(erase_accounts(ty))),
+        Type::Vec(ty) => Type::Vec(Box::new(erase_accounts(ty))),
+        Type::Record(fs) => Type::Record(fields(fs)),
+        Type::Variant(fs) => Type::Variant(fields(fs)),
+        Type::Func(func) => Type::Func(Function {
+            modes: func.modes.clone(),
+            args: types(&func.args),
+            rets: types(&func.rets),
+        }),
+        ty => ty.clone(),
+    }
+}
+
+/// Replies must be subtypes of the reference type.
+fn assert_reply<T: CandidType>(env: &mut TypeEnv, reference: &str) {
+    let ours = rust_type::<T>(env);
+    let theirs = Type::Var(reference.to_string());
+    is_subtype(env, &ours, &theirs).unwrap_or_else(|e| {
+        panic!(
+            "{} is not a subtype of {}: {:#}",
+            std::any::type_name::<T>(),
+            reference,
+            e
+        )
+    });
+}
+
+/// Arguments must accept everything the reference type allows.
+fn assert_arg<T: CandidType>(env: &mut TypeEnv, reference: &str) {
+    let ours = rust_type::<T>(env);
+    let theirs = Type::Var(reference.to_string());
+    is_subtype(env, &theirs, &ours).unwrap_or_else(|e| {
+        panic!(
+            "{} is not a subtype of {}: {:#}",
+            reference,
+            std::any::type_name::<T>(),
+            e
+        )
+    });
+}
+
+#[test]
+fn did_file_is_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("ext_based_dip721_lib.did");
+    let interface = candid_interface();
+    if std::env::var_os("UPDATE_DID").is_some() {
+        std::fs::write(&path, &interface).unwrap();
+    }
+    let did = std::fs::read_to_string(path).unwrap();
+    assert_eq!(
+        did, interface,
+        "ext_based_dip721_lib.did is out of date, rerun the tests with UPDATE_DID=1"
+    );
+}
+
+#[test]
+fn dip721_types_conform_to_v2() {
+    let (mut env, _) = load_reference("dip721v2.did");
+    assert_reply::<NftError>(&mut env, "NftError");
+    assert_reply::<MetaData>(&mut env, "Metadata");
+    assert_reply::<Result<Nat, NftError>>(&mut env, "Result");
+    assert_arg::<InitArgs>(&mut env, "InitArgs");
+}
+
+#[test]
+fn dip721_methods_conform_to_v2() {
+    let (mut env, reference) = load_reference("dip721v2.did");
+    let ours = load_interface(&mut env);
+    let ours = env.as_service(&ours).unwrap().to_vec();
+
+    let erased = TypeEnv(
+        env.0
+            .iter()
+            .map(|(name, ty)| (name.clone(), erase_accounts(ty)))
+            .collect(),
+    );
+
+    for (name, theirs) in env.as_service(&reference).unwrap() {
+        let ours = match ours.iter().find(|(method, _)| method == name) {
+            Some((_, ty)) => ty,
+            None => panic!("DIP721 v2 method {} is not exported", name),
+        };
+        if DIP721_ACCOUNT_ID_DEVIATIONS.contains(&name.as_str()) {
+            is_subtype(&erased, &erase_accounts(ours), &erase_accounts(theirs)).unwrap_or_else(
+                |e| panic!("{} diverges from DIP721 v2 beyond accounts: {:#}", name, e),
+            );
+        } else {
+            is_subtype(&env, ours, theirs)
+                .unwrap_or_else(|e| panic!("{} diverges from DIP721 v2: {:#}", name, e));
+        }
+    }
+}
+
+#[test]
+fn ext_types_conform_to_reference() {
+    let (mut env, _) = load_reference("ext.did");
+    assert_arg::<User>(&mut env, "User");
+    assert_arg::<AllowanceRequest>(&mut env, "AllowanceRequest");
+    assert_arg::<ApproveRequest>(&mut env, "ApproveRequest");
+    assert_arg::<BalanceRequest>(&mut env, "BalanceRequest");
+    assert_arg::<TransferRequest>(&mut env, "TransferRequest");
+    assert_reply::<CommonError>(&mut env, "CommonError");
+    assert_reply::<BalanceResponse>(&mut env, "BalanceResponse");
+    assert_reply::<TransferResponse>(&mut env, "TransferResponse");
+    assert_reply::<TokenMetaDataExt>(&mut env, "Metadata");
+    assert_reply::<Listing>(&mut env, "Listing");
+    assert_reply::<NFTResult>(&mut env, "Result");
+    assert_reply::<Result_1>(&mut env, "Result_1");
+    assert_reply::<Result_2>(&mut env, "Result_2");
+    assert_reply::<Result__1_2>(&mut env, "Result_2");
+    assert_reply::<Result__1>(&mut env, "Result_3");
+    assert_reply::<Result__1_1>(&mut env, "Result_4");
+}
+
+#[test]
+fn ext_methods_conform_to_reference() {
+    let (mut env, reference) = load_reference("ext.did");
+    let ours = load_interface(&mut env);
+    let ours = env.as_service(&ours).unwrap().to_vec();
+
+    for (name, theirs) in env.as_service(&reference).unwrap() {
+        let ours = match ours.iter().find(|(method, _)| method == name) {
+            Some((_, ty)) => ty,
+            None => panic!("EXT method {} is not exported", name),
+        };
+        is_subtype(&env, ours, theirs)
+            .unwrap_or_else(|e| panic!("{} diverges from EXT: {:#}", name, e));
+    }
+}
+
+#[test]
+fn icrc7_types_conform_to_reference() {
+    let (mut env, _) = load_reference("icrc7.did");
+    assert_arg::<Account>(&mut env, "Account");
+    assert_reply::<Account>(&mut env, "Account");
+    assert_arg::<Icrc7TransferArg>(&mut env, "TransferArg");
+    assert_reply::<Icrc7TransferResult>(&mut env, "TransferResult");
+    assert_reply::<Value>(&mut env, "Value");
+    assert_reply::<SupportedStandard>(&mut env, "SupportedStandard");
+}
+
+#[test]
+fn icrc7_methods_conform_to_reference() {
+    let (mut env, reference) = load_reference("icrc7.did");
+    let ours = load_interface(&mut env);
+    let ours = env.as_service(&ours).unwrap().to_vec();
+
+    for (name, theirs) in env.as_service(&reference).unwrap() {
+        let ours = match ours.iter().find(|(method, _)| method == name) {
+            Some((_, ty)) => ty,
+            None => panic!("ICRC-7 method {} is not exported", name),
+        };
+        is_subtype(&env, ours, theirs)
+            .unwrap_or_else(|e| panic!("{} diverges from ICRC-7: {:#}", name, e));
+    }
+}
+
+#[test]
+fn icrc37_types_conform_to_reference() {
+    let (mut env, _) = load_reference("icrc37.did");
+    assert_arg::<ApprovalInfo>(&mut env, "ApprovalInfo");
+    assert_reply::<TokenApproval>(&mut env, "TokenApproval");
+    assert_arg::<TransferFromArg>(&mut env, "TransferFromArg");
+    assert_reply::<TransferFromResult>(&mut env, "TransferFromResult");
+    assert_reply::<ApproveTokenResult>(&mut env, "ApproveTokenResult");
+    assert_reply::<RevokeTokenApprovalResponse>(&mut env, "RevokeTokenApprovalResponse");
+}
+
+#[test]
+fn icrc37_methods_conform_to_reference() {
+    let (mut env, reference) = load_reference("icrc37.did");
+    let ours = load_interface(&mut env);
+    let ours = env.as_service(&ours).unwrap().to_vec();
+
+    for (name, theirs) in env.as_service(&reference).unwrap() {
+        let ours = match ours.iter().find(|(method, _)| method == name) {
+            Some((_, ty)) => ty,
+            None => panic!("ICRC-37 method {} is not exported", name),
+        };
+        is_subtype(&env, ours, theirs)
+            .unwrap_or_else(|e| panic!("{} diverges from ICRC-37: {:#}", name, e));
+    }
+}
+
+#[test]
+fn icrc3_types_conform_to_reference() {
+    let (mut env, _) = load_reference("icrc3.did");
+    assert_arg::<Vec<GetBlocksArgs>>(&mut env, "GetBlocksArgs");
+    assert_reply::<GetBlocksResult>(&mut env, "GetBlocksResult");
+    assert_arg::<GetArchivesArgs>(&mut env, "GetArchivesArgs");
+    assert_reply::<Vec<ICRC3ArchiveInfo>>(&mut env, "GetArchivesResult");
+    assert_reply::<ICRC3DataCertificate>(&mut env, "ICRC3DataCertificate");
+}
+
+#[test]
+fn icrc3_methods_conform_to_reference() {
+    let (mut env, reference) = load_reference("icrc3.did");
+    let ours = load_interface(&mut env);
+    let ours = env.as_service(&ours).unwrap().to_vec();
+
+    for (name, theirs) in env.as_service(&reference).unwrap() {
+        let ours = match ours.iter().find(|(method, _)| method == name) {
+            Some((_, ty)) => ty,
+            None => panic!("ICRC-3 method {} is not exported", name),
+        };
+        is_subtype(&env, ours, theirs)
+            .unwrap_or_else(|e| panic!("{} diverges from ICRC-3: {:#}", name, e));
+    }
+}
@@ -0,0 +1,198 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::*;
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+
+fn labeled(label: &str, subtree: HashTree) -> HashTree {
+    HashTree::Labeled(label.as_bytes().to_vec(), Box::new(subtree))
+}
+
+fn fork(left: HashTree, right: HashTree) -> HashTree {
+    HashTree::Fork(Box::new(left), Box::new(right))
+}
+
+fn leaf(value: &str) -> HashTree {
+    HashTree::Leaf(value.as_bytes().to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn aid(byte: u8) -> AccountIdentifier_shiku {
+    AccountIdentifier_shiku::from(Account {
+        owner: Principal::from_slice(&[byte; 10]),
+        subaccount: None,
+    })
+}
+
+fn token_metadata(token_id: u32, owner: AccountIdentifier_shiku) -> TokenMetadata {
+    TokenMetadata {
+        token_identifier: Nat::from(token_id),
+        owner: Some(owner),
+        operator: Some(owner),
+        is_burned: false,
+        properties: None,
+        minted_at: 0,
+        minted_by: owner,
+        transferred_at: None,
+        transferred_by: None,
+        approved_at: None,
+        approved_by: None,
+        burned_at: None,
+        burned_by: None,
+        status: TokenStatus::Active,
+        soulbound: false,
+    }
+}
+
+/// The example tree of the IC interface specification.
+#[test]
+fn hash_trees_match_the_interface_spec() {
+    let tree = fork(
+        fork(
+            labeled(
+                "a",
+                fork(
+                    fork(labeled("x", leaf("hello")), HashTree::Empty),
+                    labeled("y", leaf("world")),
+                ),
+            ),
+            labeled("b", leaf("good")),
+        ),
+        fork(labeled("c", HashTree::Empty), labeled("d", leaf("morning"))),
+    );
+    assert_eq!(
+        hex(&tree.reconstruct()),
+        "eb5c5b2195e62d996b84c9bcc8259d19a83786a2f59e0878cec84c811f669aa0"
+    );
+    assert_eq!(
+        hex(&tree.to_cbor()),
+        "d9d9f78301830183024161830183018302417882034568656c6c6f810083024179820345776f726c64\
+         83024162820344676f6f648301830241638100830241648203476d6f726e696e67"
+    );
+    assert_eq!(tree.lookup(&[b"a", b"y"]), LookupResult::Found(b"world"));
+    assert_eq!(tree.lookup(&[b"a", b"z"]), LookupResult::Absent);
+    assert_eq!(tree.lookup(&[b"bb"]), LookupResult::Absent);
+}
+
+fn certified_map<'a>(entries: impl IntoIterator<Item = (&'a Vec<u8>, &'a u8)>) -> CertifiedMap {
+    let mut map = CertifiedMap::default();
+    for (label, value) in entries {
+        map.insert(label.clone(), vec![*value]);
+    }
+    map
+}
+
+proptest! {
+    /// Witnesses reconstruct the root and agree with a plain map on every
+    /// lookup, whether the label is present or not.
+    #[test]
+    fn witnesses_agree_with_a_map(
+        map in prop::collection::btree_map(prop::collection::vec(any::<u8>(), 0..4), any::<u8>(), 0..40),
+        queries in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..4), 1..4),
+    ) {
+        let certified = certified_map(&map);
+        let labels: Vec<&[u8]> = queries.iter().map(Vec::as_slice).collect();
+        let witness = certified.witness(&labels);
+
+        prop_assert_eq!(witness.reconstruct(), certified.root_hash());
+        for label in &queries {
+            let expected = match map.get(label) {
+                Some(value) => LookupResult::Found(std::slice::from_ref(value)),
+                None => LookupResult::Absent,
+            };
+            prop_assert_eq!(witness.lookup(&[label]), expected);
+            prop_assert_eq!(certified.get(label), map.get(label).map(std::slice::from_ref));
+        }
+    }
+
+    /// The tree only depends on the entries, not on the order they were
+    /// written and removed in.
+    #[test]
+    fn updates_give_the_tree_a_rebuild_would(
+        map in prop::collection::btree_map(prop::collection::vec(any::<u8>(), 0..4), any::<u8>(), 0..40),
+        removed in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..4), 0..10),
+    ) {
+        let mut updated = certified_map(map.iter().rev());
+        let mut expected = map.clone();
+        for label in &removed {
+            updated.remove(label);
+            expected.remove(label);
+        }
+        prop_assert_eq!(&updated, &certified_map(&expected));
+        prop_assert_eq!(updated.root_hash(), certified_map(&expected).root_hash());
+    }
+}
+
+#[test]
+fn unrequested_entries_are_pruned() {
+    let map: BTreeMap<Vec<u8>, u8> = (0..16u8).map(|i| (vec![i], i)).collect();
+    let witness = certified_map(&map).witness(&[&[7]]);
+    assert_eq!(witness.lookup(&[&[7]]), LookupResult::Found(&[7]));
+    assert_eq!(witness.lookup(&[&[3]]), LookupResult::Unknown);
+    assert_eq!(witness.lookup(&[&[12]]), LookupResult::Unknown);
+}
+
+#[test]
+fn ledger_tree_certifies_owners_balances_and_tip() {
+    let (alice, bob) = (aid(1), aid(2));
+    let mut ledger = Ledger::default();
+    for byte in [1, 2] {
+        ledger.link_account(Account {
+            owner: Principal::from_slice(&[byte; 10]),
+            subaccount: None,
+        });
+    }
+    for (token_id, owner) in [(1, alice), (2, alice), (3, bob)] {
+        ledger
+            .mint(&Nat::from(token_id), token_metadata(token_id, owner))
+            .unwrap();
+    }
+    ledger.burn(bob, &Nat::from(3u32)).unwrap();
+    let root_hash = root_hash(&ledger);
+    assert_eq!(ledger.certified, CertifiedState::of(&ledger));
+
+    let witness = owner_witness(&ledger, &Nat::from(2u32));
+    assert_eq!(witness.reconstruct(), root_hash);
+    assert_eq!(
+        witness.lookup(&[OWNERS, b"2"]),
+        LookupResult::Found(&alice.to_address())
+    );
+    assert_eq!(witness.lookup(&[OWNERS, b"1"]), LookupResult::Unknown);
+
+    let witness = owner_witness(&ledger, &Nat::from(3u32));
+    assert_eq!(witness.reconstruct(), root_hash);
+    assert_eq!(witness.lookup(&[OWNERS, b"3"]), LookupResult::Absent);
+
+    let witness = balance_witness(&ledger, &alice);
+    assert_eq!(witness.reconstruct(), root_hash);
+    assert_eq!(
+        witness.lookup(&[BALANCES, &alice.to_address()]),
+        LookupResult::Found(&[2])
+    );
+    let witness = balance_witness(&ledger, &bob);
+    assert_eq!(
+        witness.lookup(&[BALANCES, &bob.to_address()]),
+        LookupResult::Absent
+    );
+
+    let (index, hash) = ledger.tip().unwrap();
+    assert_eq!(index, Nat::from(3u32));
+    let witness = tip_witness(&ledger);
+    assert_eq!(witness.reconstruct(), root_hash);
+    assert_eq!(
+        witness.lookup(&[LAST_BLOCK_INDEX]),
+        LookupResult::Found(&[3])
+    );
+    assert_eq!(
+        witness.lookup(&[LAST_BLOCK_HASH]),
+        LookupResult::Found(&hash)
+    );
+}
+
+#[test]
+fn certificates_are_only_served_in_queries() {
+    assert_eq!(icrc3_get_tip_certificate(), None);
+    assert_eq!(dip721_owner_of_certified(Nat::from(1u32)), None);
+}
@@ -0,0 +1,143 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::*;
+
+const NOW: u64 = 1_700_000_000_000_000_000;
+
+fn key(caller: u8, operation: &str, token_id: u32, created_at_time: u64) -> TxKey {
+    let caller = Principal::from_slice(&[caller; 10]);
+    TxKey::new(&caller, operation, &Nat::from(token_id), created_at_time)
+}
+
+/// Runs a fake transaction through `deduplicate`, returning its result and
+/// whether it executed.
+fn run(ledger: &mut Ledger, key: Option<TxKey>, now: u64) -> (Result<Nat, Nat>, bool) {
+    let mut executed = false;
+    let result = ledger.deduplicate(key, now, Err, |ledger| {
+        executed = true;
+        Ok(ledger.inc_tx() - 1)
+    });
+    (result, executed)
+}
+
+#[test]
+fn keys_cover_caller_operation_arguments_and_time() {
+    let base = key(1, "icrc7_transfer", 1, NOW);
+    assert_eq!(base, key(1, "icrc7_transfer", 1, NOW));
+    assert_ne!(base, key(2, "icrc7_transfer", 1, NOW));
+    assert_ne!(base, key(1, "icrc37_transfer_from", 1, NOW));
+    assert_ne!(base, key(1, "icrc7_transfer", 2, NOW));
+    assert_ne!(base, key(1, "icrc7_transfer", 1, NOW + 1));
+}
+
+#[test]
+fn retries_return_the_original_index() {
+    let mut ledger = Ledger::default();
+    assert_eq!(
+        run(&mut ledger, Some(key(1, "mint", 1, NOW)), NOW),
+        (Ok(Nat::from(0u32)), true)
+    );
+    assert_eq!(
+        run(&mut ledger, Some(key(1, "mint", 2, NOW)), NOW),
+        (Ok(Nat::from(1u32)), true)
+    );
+    assert_eq!(
+        run(&mut ledger, Some(key(1, "mint", 1, NOW)), NOW),
+        (Err(Nat::from(0u32)), false)
+    );
+    assert_eq!(
+        ledger.duplicate_of(&key(1, "mint", 2, NOW)),
+        Some(Nat::from(1u32))
+    );
+
+    // Calls without created_at_time are never deduplicated.
+    assert_eq!(run(&mut ledger, None, NOW), (Ok(Nat::from(2u32)), true));
+    assert_eq!(run(&mut ledger, None, NOW), (Ok(Nat::from(3u32)), true));
+}
+
+#[test]
+fn failed_calls_are_not_recorded() {
+    let mut ledger = Ledger::default();
+    let failed: Result<Nat, Nat> =
+        ledger.deduplicate(Some(key(1, "mint", 1, NOW)), NOW, Err, |_| {
+            Err(Nat::from(7u32))
+        });
+    assert_eq!(failed, Err(Nat::from(7u32)));
+    assert_eq!(ledger.duplicate_of(&key(1, "mint", 1, NOW)), None);
+}
+
+#[test]
+fn keys_expire_with_the_window() {
+    let mut ledger = Ledger::default();
+    assert!(run(&mut ledger, Some(key(1, "mint", 1, NOW)), NOW).1);
+    let later = NOW + TX_WINDOW + PERMITTED_DRIFT;
+    assert!(run(&mut ledger, Some(key(1, "mint", 2, later)), later).1);
+    assert!(ledger.duplicate_of(&key(1, "mint", 1, NOW)).is_some());
+
+    assert!(run(&mut ledger, Some(key(1, "mint", 3, later + 1)), later + 1).1);
+    assert!(ledger.duplicate_of(&key(1, "mint", 1, NOW)).is_none());
+    assert_eq!(ledger.recent_txs.len(), 2);
+}
+
+#[test]
+fn dip721_mints_reject_stale_created_at_time() {
+    let to = Principal::from_slice(&[1; 10]);
+    ledger::set_caller(to);
+    dip721_init(None);
+    let stale = ledger::now() - TX_WINDOW - PERMITTED_DRIFT - 1;
+    assert!(matches!(
+        dip721_mint(pid2aid(&to).unwrap(), &Nat::from(1u32), Some(stale), None),
+        Err(NftError::Other(_))
+    ));
+    assert!(dip721_mint(pid2aid(&to).unwrap(), &Nat::from(1u32), None, None).is_ok());
+}
+
+#[test]
+fn retries_follow_each_standard() {
+    let (a, b, c) = (
+        Principal::from_slice(&[1; 10]),
+        Principal::from_slice(&[2; 10]),
+        Principal::from_slice(&[3; 10]),
+    );
+    ledger::set_caller(a);
+    dip721_init(None);
+    let now = Some(ledger::now());
+    let token = Nat::from(1u32);
+    let blocks = || ledger::with(|ledger| ledger.blocks.len());
+
+    // DIP-721 retries succeed again with the original index.
+    let (a_aid, b_aid) = (pid2aid(&a).unwrap(), pid2aid(&b).unwrap());
+    let minted = dip721_mint(a_aid, &token, now, None).unwrap();
+    assert_eq!(dip721_mint(a_aid, &token, now, None).unwrap(), minted);
+    assert_eq!(blocks(), 1);
+    let transferred = dip721_transfer_from(a_aid, b_aid, token.clone(), now).unwrap();
+    assert_ne!(transferred, minted);
+    assert_eq!(
+        dip721_transfer_from(a_aid, b_aid, token.clone(), now).unwrap(),
+        transferred
+    );
+    assert_eq!(blocks(), 2);
+
+    // ICRC-7 retries fail with the original index.
+    ledger::set_caller(b);
+    let arg = Icrc7TransferArg {
+        from_subaccount: None,
+        to: Account {
+            owner: c,
+            subaccount: None,
+        },
+        token_id: token,
+        memo: None,
+        created_at_time: now,
+    };
+    let sent = icrc7_transfer(vec![arg.clone()]);
+    let Some(Some(Ok(index))) = sent.first().cloned() else {
+        panic!("transfer failed: {:?}", sent);
+    };
+    assert_eq!(
+        icrc7_transfer(vec![arg]),
+        vec![Some(Err(Icrc7TransferError::Duplicate {
+            duplicate_of: index
+        }))]
+    );
+    assert_eq!(blocks(), 3);
+}
@@ -0,0 +1,797 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::*;
+
+fn pid(byte: u8) -> Principal {
+    Principal::from_slice(&[byte; 10])
+}
+
+/// The default account of `pid(byte)`.
+fn aid(byte: u8) -> AccountIdentifier_shiku {
+    pid2aid(&pid(byte)).unwrap()
+}
+
+/// Makes `pid(0)` a custodian and the caller, which lets it mint.
+fn as_custodian() {
+    ledger::set_caller(pid(0));
+    dip721_init(None);
+}
+
+fn nats(ids: &[u32]) -> Vec<Nat> {
+    ids.iter().map(|id| Nat::from(*id)).collect()
+}
+
+fn items(to: AccountIdentifier_shiku, ids: &[u32]) -> Vec<MintBatchItem> {
+    ids.iter()
+        .map(|id| (to, Nat::from(*id), None, None))
+        .collect()
+}
+
+fn log_length() -> usize {
+    ledger::with(|ledger| ledger.blocks.len())
+}
+
+struct VetoToken(u32);
+
+impl LedgerHooks for VetoToken {
+    fn before_mint(
+        &self,
+        _ledger: &Ledger,
+        token_identifier: &TokenId,
+        _minted_by: &AccountIdentifier_shiku,
+        _to: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        if token_identifier.0 == self.0.into() {
+            return Err(ShikuNftError::Rejected);
+        }
+        Ok(())
+    }
+}
+
+struct VetoTransfer(u32);
+
+impl LedgerHooks for VetoTransfer {
+    fn before_transfer(
+        &self,
+        _ledger: &Ledger,
+        token_identifier: &TokenId,
+        _transferred_by: &AccountIdentifier_shiku,
+        _from: Option<&AccountIdentifier_shiku>,
+        _to: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        if token_identifier.0 == self.0.into() {
+            return Err(ShikuNftError::Rejected);
+        }
+        Ok(())
+    }
+}
+
+struct VetoStatus(TokenAction);
+
+impl LedgerHooks for VetoStatus {
+    fn before_change_status(
+        &self,
+        _ledger: &Ledger,
+        _token_identifier: &TokenId,
+        _changed_by: &AccountIdentifier_shiku,
+        action: TokenAction,
+        _status: TokenStatus,
+    ) -> Result<(), ShikuNftError> {
+        if action == self.0 {
+            return Err(ShikuNftError::Rejected);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn batch_mints_return_one_index_per_token() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    let properties = MetaData {
+        name: Some("First".to_string()),
+        ..MetaData::default()
+    };
+    let mut batch = vec![(alice, Nat::from(1u32), Some(properties), None)];
+    batch.extend(items(aid(2), &[2, 3]));
+
+    assert_eq!(dip721_mint_batch(batch).unwrap(), nats(&[0, 1, 2]));
+    assert_eq!(dip721_mint_batch(items(aid(1), &[4])).unwrap(), nats(&[3]));
+    assert_eq!(dip721_balance_of(bob).unwrap(), Nat::from(2u32));
+    ledger::with(|ledger| {
+        let token = ledger.token_metadata(&Nat::from(1u32)).unwrap();
+        assert_eq!(token.owner, Some(alice));
+        assert_eq!(
+            token.properties.as_ref().unwrap().name.as_deref(),
+            Some("First")
+        );
+    });
+    assert_eq!(log_length(), 4);
+}
+
+#[test]
+fn batch_mints_are_all_or_nothing() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+
+    assert!(matches!(
+        dip721_mint_batch(items(aid(1), &[2, 3, 2])),
+        Err(NftError::Other(msg)) if msg.starts_with("item 2:")
+    ));
+    assert!(matches!(
+        dip721_mint_batch(items(aid(1), &[2, 1])),
+        Err(NftError::Other(msg)) if msg.starts_with("item 1:")
+    ));
+    let too_many: Vec<u32> = (10..11 + MAX_MINT_BATCH_SIZE as u32).collect();
+    assert!(dip721_mint_batch(items(aid(1), &too_many)).is_err());
+
+    register_hooks(VetoToken(4));
+    assert!(matches!(
+        dip721_mint_batch(items(aid(1), &[2, 3, 4, 5])),
+        Err(NftError::Other(msg)) if msg.starts_with("item 2:")
+    ));
+    clear_hooks();
+
+    assert_eq!(dip721_total_supply(), Nat::from(1u32));
+    assert_eq!(log_length(), 1);
+}
+
+fn owners(ids: &[u32]) -> Vec<Option<AccountIdentifier_shiku>> {
+    ids.iter()
+        .map(|id| dip721_owner_of(Nat::from(*id)).unwrap())
+        .collect()
+}
+
+#[test]
+fn batch_transfers_move_every_token_to_its_recipient() {
+    as_custodian();
+    let (alice, bob, carol) = (aid(1), aid(2), aid(3));
+    dip721_mint_batch(items(aid(1), &[1, 2, 3])).unwrap();
+    ledger::set_caller(pid(1));
+
+    let transfers = vec![(bob, Nat::from(1u32)), (carol, Nat::from(3u32))];
+    assert_eq!(
+        dip721_transfer_batch(alice, transfers).unwrap(),
+        nats(&[3, 4])
+    );
+    assert_eq!(owners(&[1, 2, 3]), [Some(bob), Some(alice), Some(carol)]);
+    ledger::with(|ledger| {
+        let btypes: Vec<_> = ledger.blocks[3..]
+            .iter()
+            .map(|block| match block {
+                Value::Map(entries) => entries.iter().find(|(k, _)| k == "btype").cloned(),
+                _ => None,
+            })
+            .collect();
+        let xfer = Some(("btype".to_string(), Value::Text("7xfer".to_string())));
+        assert_eq!(btypes, [xfer.clone(), xfer]);
+    });
+}
+
+#[test]
+fn batch_transfers_are_all_or_nothing() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1, 2])).unwrap();
+    dip721_mint_batch(items(aid(2), &[3])).unwrap();
+
+    assert!(matches!(
+        dip721_transfer_batch(alice, vec![(bob, Nat::from(1u32))]),
+        Err(NftError::Other(msg)) if msg.starts_with("item 0:")
+    ));
+    ledger::set_caller(pid(1));
+    for transfers in [
+        vec![(bob, Nat::from(1u32)), (bob, Nat::from(3u32))],
+        vec![(bob, Nat::from(1u32)), (bob, Nat::from(1u32))],
+        vec![(bob, Nat::from(1u32)), (alice, Nat::from(2u32))],
+        vec![(bob, Nat::from(1u32)), (bob, Nat::from(9u32))],
+    ] {
+        assert!(matches!(
+            dip721_transfer_batch(alice, transfers),
+            Err(NftError::Other(msg)) if msg.starts_with("item 1:")
+        ));
+    }
+
+    ledger::with_mut(|ledger| {
+        let transfers = vec![(Nat::from(1u32), Some(bob)), (Nat::from(2u32), Some(bob))];
+        register_hooks(VetoTransfer(2));
+        assert!(ledger.transfer_batch(alice, transfers).is_err());
+        clear_hooks();
+    });
+
+    assert_eq!(owners(&[1, 2, 3]), [Some(alice), Some(alice), Some(bob)]);
+    assert_eq!(log_length(), 3);
+}
+
+#[test]
+fn classes_track_supply_and_balances() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1, 2, 3, 4, 5])).unwrap();
+    dip721_set_class("gold".to_string(), nats(&[4, 2, 3])).unwrap();
+    dip721_set_class("gold".to_string(), nats(&[2])).unwrap();
+
+    assert!(dip721_set_class("silver".to_string(), nats(&[5, 2])).is_err());
+    assert!(dip721_set_class("silver".to_string(), nats(&[5, 9])).is_err());
+    assert!(dip721_set_class(String::new(), nats(&[5])).is_err());
+    ledger::set_caller(pid(1));
+    assert!(dip721_set_class("silver".to_string(), nats(&[5])).is_err());
+    as_custodian();
+    ledger::with(|ledger| assert_eq!(ledger.class_of(&Nat::from(5u32)), None));
+
+    ledger::set_caller(pid(1));
+    dip721_transfer_batch(alice, vec![(bob, Nat::from(3u32))]).unwrap();
+    assert_eq!(dip721_class_supply("gold".to_string()), Nat::from(3u32));
+    assert_eq!(
+        dip721_class_balance_of("gold".to_string(), alice),
+        Nat::from(2u32)
+    );
+    assert_eq!(
+        dip721_class_balance_of("gold".to_string(), bob),
+        Nat::from(1u32)
+    );
+    assert_eq!(dip721_class_supply("silver".to_string()), Nat::from(0u32));
+}
+
+#[test]
+fn class_ranges_select_tokens_in_order() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1, 2, 3, 4])).unwrap();
+    dip721_set_class("gold".to_string(), nats(&[4, 1, 3])).unwrap();
+
+    ledger::with_mut(|ledger| {
+        assert_eq!(ledger.class_range("gold", 0, 2).unwrap(), nats(&[1, 3]));
+        assert_eq!(ledger.class_range("gold", 1, 3).unwrap(), nats(&[3, 4]));
+        assert!(ledger.class_range("gold", 2, 4).is_err());
+        assert!(ledger.class_range("gold", 2, 1).is_err());
+        assert!(ledger.class_range("silver", 0, 1).is_err());
+
+        let range = ledger.class_range("gold", 0, 3).unwrap();
+        let transfers = range.into_iter().map(|token_id| (token_id, Some(bob)));
+        register_hooks(VetoTransfer(4));
+        assert!(ledger.transfer_batch(alice, transfers.collect()).is_err());
+        clear_hooks();
+    });
+    assert_eq!(owners(&[1, 3, 4]), [Some(alice), Some(alice), Some(alice)]);
+    assert_eq!(
+        dip721_class_balance_of("gold".to_string(), alice),
+        Nat::from(3u32)
+    );
+}
+
+#[test]
+fn list_queries_page_in_ascending_order() {
+    as_custodian();
+    let alice = aid(1);
+    dip721_mint_batch(items(aid(1), &[30, 4, 12, 7, 21])).unwrap();
+    dip721_mint_batch(items(aid(2), &[5])).unwrap();
+
+    let first = dip721_owner_token_identifiers(alice, None, Some(Nat::from(2u32))).unwrap();
+    assert_eq!(first, nats(&[4, 7]));
+    let rest = dip721_owner_token_identifiers(alice, first.last().cloned(), None).unwrap();
+    assert_eq!(rest, nats(&[12, 21, 30]));
+    assert_eq!(
+        dip721_token_identitfier_operator(alice, Some(Nat::from(21u32)), None).unwrap(),
+        nats(&[30])
+    );
+    assert!(dip721_owner_token_identifiers(aid(3), None, None).is_err());
+
+    for (idx, byte) in [(9, "c"), (2, "a"), (5, "b")] {
+        dip721_add_aid_idx(&idx, &byte.to_string());
+    }
+    assert_eq!(
+        dip721_get_registry(None, Some(Nat::from(2u32))),
+        [(2, "a".to_string()), (5, "b".to_string())]
+    );
+    assert_eq!(dip721_get_registry(Some(5), None), [(9, "c".to_string())]);
+
+    restore_minted_info(nats(&[8, 3, 6]));
+    assert_eq!(dip721_minted_info(None, None), nats(&[3, 6, 8]));
+    assert_eq!(
+        dip721_minted_info(Some(Nat::from(3u32)), Some(Nat::from(1u32))),
+        nats(&[6])
+    );
+
+    let history = dip721_history(None, Some(Nat::from(4u32)));
+    let ids: Vec<Nat> = history.iter().map(|block| block.id.clone()).collect();
+    assert_eq!(ids, nats(&[0, 1, 2, 3]));
+    let history = dip721_history(Some(Nat::from(3u32)), None);
+    let ids: Vec<Nat> = history.iter().map(|block| block.id.clone()).collect();
+    assert_eq!(ids, nats(&[4, 5]));
+    assert!(dip721_history(Some(Nat::from(5u32)), None).is_empty());
+}
+
+#[test]
+fn batch_reads_answer_per_token() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    dip721_mint_batch(items(aid(2), &[2])).unwrap();
+
+    let owners = dip721_owner_of_batch(nats(&[2, 9, 1])).unwrap();
+    assert!(matches!(
+        owners.as_slice(),
+        [Ok(Some(b)), Err(NftError::TokenNotFound), Ok(Some(a))] if *b == bob && *a == alice
+    ));
+    let metadata = dip721_token_metadata_batch(nats(&[9, 1])).unwrap();
+    assert!(matches!(
+        metadata.as_slice(),
+        [Err(NftError::TokenNotFound), Ok(token)] if token.owner == Some(alice)
+    ));
+    assert!(dip721_owner_of_batch(Vec::new()).unwrap().is_empty());
+
+    let too_many: Vec<u32> = (0..=MAX_QUERY_BATCH_SIZE as u32).collect();
+    assert!(dip721_owner_of_batch(nats(&too_many)).is_err());
+    assert!(dip721_token_metadata_batch(nats(&too_many)).is_err());
+}
+
+#[test]
+fn pausing_stops_every_change_until_resumed() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1, 2])).unwrap();
+    dip721_set_paused(true).unwrap();
+    assert!(dip721_is_paused());
+
+    assert!(dip721_mint_batch(items(aid(1), &[3])).is_err());
+    assert!(matches!(
+        dip721_change_status(Nat::from(2u32), TokenAction::Lock),
+        Err(NftError::Other(msg)) if msg.contains("paused")
+    ));
+    ledger::set_caller(pid(1));
+    assert!(dip721_transfer_batch(alice, vec![(bob, Nat::from(1u32))]).is_err());
+    ledger::with_mut(|ledger| {
+        let token = Nat::from(1u32);
+        assert_eq!(
+            ledger.transfer(alice, &token, Some(bob)),
+            Err(ShikuNftError::Paused)
+        );
+        assert_eq!(
+            ledger.approve(alice, &token, Some(bob)),
+            Err(ShikuNftError::Paused)
+        );
+        assert_eq!(ledger.burn(alice, &token), Err(ShikuNftError::Paused));
+        assert_eq!(
+            ledger.revoke_collection_approvals(alice, None, 0),
+            Err(ShikuNftError::Paused)
+        );
+    });
+    assert_eq!(log_length(), 2);
+
+    as_custodian();
+    dip721_set_paused(false).unwrap();
+    ledger::set_caller(pid(1));
+    dip721_transfer_batch(alice, vec![(bob, Nat::from(1u32))]).unwrap();
+    assert_eq!(owners(&[1, 2]), [Some(bob), Some(alice)]);
+}
+
+#[test]
+fn locked_tokens_stay_put_but_remain_readable() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1, 2])).unwrap();
+    dip721_set_locked(Nat::from(1u32), true).unwrap();
+    assert!(dip721_is_locked(Nat::from(1u32)));
+    assert!(dip721_set_locked(Nat::from(9u32), true).is_err());
+
+    ledger::set_caller(pid(1));
+    assert!(matches!(
+        dip721_transfer_batch(alice, vec![(bob, Nat::from(2u32)), (bob, Nat::from(1u32))]),
+        Err(NftError::Other(msg)) if msg.starts_with("item 1:")
+    ));
+    ledger::with_mut(|ledger| {
+        let token = Nat::from(1u32);
+        assert_eq!(ledger.burn(alice, &token), Err(ShikuNftError::TokenLocked));
+        assert!(ledger.approve(alice, &token, Some(bob)).is_ok());
+    });
+    assert_eq!(owners(&[1]), [Some(alice)]);
+    assert!(dip721_token_metadata(Nat::from(1u32)).is_ok());
+
+    as_custodian();
+    dip721_set_locked(Nat::from(1u32), false).unwrap();
+    ledger::set_caller(pid(1));
+    dip721_transfer_batch(alice, vec![(bob, Nat::from(1u32))]).unwrap();
+    assert_eq!(owners(&[1]), [Some(bob)]);
+}
+
+#[test]
+fn frozen_metadata_cannot_change() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    dip721_set_name("Before".to_string()).unwrap();
+    dip721_freeze_metadata().unwrap();
+    assert!(dip721_is_metadata_frozen());
+
+    assert!(dip721_set_name("After".to_string()).is_err());
+    assert!(dip721_set_symbol("AFT".to_string()).is_err());
+    assert!(dip721_set_class("gold".to_string(), nats(&[1])).is_err());
+    assert_eq!(icrc7_name(), "Before");
+
+    dip721_mint_batch(items(aid(1), &[2])).unwrap();
+}
+
+#[test]
+fn statuses_follow_the_transition_table() {
+    use TokenAction::*;
+    use TokenStatus::*;
+    assert_eq!(Active.after(List), Ok(Listed));
+    assert_eq!(Listed.after(Transfer), Ok(Active));
+    assert_eq!(Locked.after(Transfer), Err(ShikuNftError::TokenLocked));
+    assert_eq!(
+        Staked.after(Burn),
+        Err(ShikuNftError::InvalidTransition {
+            status: Staked,
+            action: Burn
+        })
+    );
+    for action in [Transfer, Burn, Lock, Unlock, Stake, Unstake, List, Delist] {
+        assert!(Burned.after(action).is_err());
+    }
+}
+
+#[test]
+fn every_operation_checks_the_token_status() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(items(aid(1), &[1, 2, 3, 4])).unwrap();
+    assert_eq!(
+        dip721_status_of(Nat::from(1u32)).unwrap(),
+        TokenStatus::Active
+    );
+
+    let status = |token: u32, action| dip721_change_status(Nat::from(token), action);
+    assert_eq!(status(1, TokenAction::Stake).unwrap(), TokenStatus::Staked);
+    assert_eq!(status(2, TokenAction::List).unwrap(), TokenStatus::Listed);
+    assert!(status(1, TokenAction::List).is_err());
+    assert!(status(3, TokenAction::Transfer).is_err());
+    assert!(status(9, TokenAction::Lock).is_err());
+
+    ledger::set_caller(pid(1));
+    assert!(dip721_transfer_batch(alice, vec![(bob, Nat::from(1u32))]).is_err());
+    dip721_transfer_batch(alice, vec![(bob, Nat::from(2u32))]).unwrap();
+    assert_eq!(
+        dip721_status_of(Nat::from(2u32)).unwrap(),
+        TokenStatus::Active
+    );
+    ledger::with_mut(|ledger| ledger.burn(alice, &Nat::from(3u32))).unwrap();
+
+    assert_eq!(
+        dip721_tokens_with_status(TokenStatus::Active, None, None),
+        nats(&[2, 4])
+    );
+    assert_eq!(
+        dip721_tokens_with_status(TokenStatus::Active, Some(Nat::from(2u32)), None),
+        nats(&[4])
+    );
+    assert_eq!(
+        dip721_tokens_with_status(TokenStatus::Staked, None, None),
+        nats(&[1])
+    );
+    assert_eq!(
+        dip721_tokens_with_status(TokenStatus::Burned, None, None),
+        nats(&[3])
+    );
+    assert!(status(3, TokenAction::Unlock).is_err());
+}
+
+#[test]
+fn soulbound_tokens_can_be_burned_but_never_change_hands() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    dip721_mint_batch(vec![
+        (alice, Nat::from(1u32), None, Some(true)),
+        (alice, Nat::from(2u32), None, None),
+    ])
+    .unwrap();
+    assert!(dip721_token_metadata(Nat::from(1u32)).unwrap().soulbound);
+    assert!(!dip721_token_metadata(Nat::from(2u32)).unwrap().soulbound);
+
+    ledger::set_caller(pid(1));
+    assert!(matches!(
+        dip721_transfer_batch(alice, vec![(bob, Nat::from(2u32)), (bob, Nat::from(1u32))]),
+        Err(NftError::Other(msg)) if msg.contains("soulbound")
+    ));
+    assert!(dip721_change_status(Nat::from(1u32), TokenAction::List).is_err());
+    ledger::with_mut(|ledger| {
+        let token = Nat::from(1u32);
+        assert_eq!(
+            ledger.transfer(alice, &token, Some(bob)),
+            Err(ShikuNftError::Soulbound)
+        );
+        assert_eq!(
+            ledger.approve(alice, &token, Some(bob)),
+            Err(ShikuNftError::Soulbound)
+        );
+        assert!(ledger.burn(alice, &token).is_ok());
+    });
+    assert_eq!(owners(&[1, 2]), [None, Some(alice)]);
+}
+
+#[test]
+fn soulbound_collections_bind_every_token() {
+    as_custodian();
+    let (alice, bob) = (aid(1), aid(2));
+    let args = InitArgs {
+        name: None,
+        logo: None,
+        symbol: None,
+        custodians: None,
+        cap: None,
+        soulbound: Some(true),
+        mint_policy: None,
+    };
+    ledger::with_mut(|ledger| ledger.init_metadata(Principal::anonymous(), Some(args)));
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+
+    assert!(dip721_token_metadata(Nat::from(1u32)).unwrap().soulbound);
+    ledger::set_caller(pid(1));
+    assert!(dip721_transfer_batch(alice, vec![(bob, Nat::from(1u32))]).is_err());
+    ledger::with_mut(|ledger| {
+        let approval = ApprovalInfo {
+            spender: Account {
+                owner: Principal::from_slice(&[2; 10]),
+                subaccount: None,
+            },
+            from_subaccount: None,
+            expires_at: None,
+            memo: None,
+            created_at_time: 0,
+        };
+        assert_eq!(
+            ledger.approve_collection(alice, approval, 0),
+            Err(ShikuNftError::Soulbound)
+        );
+    });
+}
+
+#[test]
+fn mint_policy_caps_supply_and_mints_per_account() {
+    as_custodian();
+    let alice = aid(1);
+    dip721_set_mint_policy(MintPolicy {
+        max_supply: Some(4),
+        max_per_account: Some(2),
+        ..MintPolicy::default()
+    })
+    .unwrap();
+    assert_eq!(icrc7_supply_cap(), Some(Nat::from(4u32)));
+    assert_eq!(dip721_mint_policy().max_per_account, Some(2));
+
+    assert!(matches!(
+        dip721_mint_batch(items(aid(1), &[1, 2, 3])),
+        Err(NftError::Other(msg)) if msg.starts_with("item 2:")
+    ));
+    dip721_mint_batch(items(aid(1), &[1, 2])).unwrap();
+    assert!(dip721_mint(aid(1), &Nat::from(3u32), None, None).is_err());
+
+    ledger::with_mut(|ledger| ledger.burn(alice, &Nat::from(1u32))).unwrap();
+    assert!(matches!(
+        dip721_mint_batch(items(aid(2), &[3, 4, 5])),
+        Err(NftError::Other(msg)) if msg.starts_with("item 2:")
+    ));
+    dip721_mint_batch(items(aid(2), &[3, 4])).unwrap();
+    assert!(dip721_mint(aid(2), &Nat::from(5u32), None, None).is_err());
+    assert_eq!(dip721_total_supply(), Nat::from(4u32));
+}
+
+#[test]
+fn mints_only_happen_within_the_window() {
+    as_custodian();
+    let now = ledger::now();
+    let window = |mint_start, mint_end| MintPolicy {
+        mint_start,
+        mint_end,
+        ..MintPolicy::default()
+    };
+    assert!(dip721_set_mint_policy(window(Some(now), Some(now))).is_err());
+
+    dip721_set_mint_policy(window(Some(now + 60_000_000_000), None)).unwrap();
+    assert!(dip721_mint(aid(1), &Nat::from(1u32), None, None).is_err());
+    dip721_set_mint_policy(window(None, Some(now))).unwrap();
+    assert!(dip721_mint_batch(items(aid(1), &[1])).is_err());
+    dip721_set_mint_policy(window(Some(now), Some(now + 60_000_000_000))).unwrap();
+    assert!(dip721_mint(aid(1), &Nat::from(1u32), None, None).is_ok());
+}
+
+#[test]
+fn public_mints_go_to_the_caller() {
+    let caller = Principal::from_slice(&[1; 10]);
+    let public = MintPolicy {
+        phase: MintPhase::Public,
+        ..MintPolicy::default()
+    };
+    ledger::set_caller(caller);
+    assert!(dip721_set_mint_policy(public.clone()).is_err());
+    assert!(dip721_mint(aid(1), &Nat::from(1u32), None, None).is_err());
+
+    as_custodian();
+    dip721_set_mint_policy(public).unwrap();
+    assert!(check_mint_caller(caller, &[aid(1), aid(1)]).is_ok());
+    ledger::set_caller(caller);
+    assert!(dip721_mint(aid(2), &Nat::from(1u32), None, None).is_err());
+    dip721_mint(aid(1), &Nat::from(1u32), None, None).unwrap();
+}
+
+#[test]
+fn only_custodians_administer_the_collection() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    ledger::set_caller(pid(1));
+    assert!(dip721_set_paused(true).is_err());
+    assert!(dip721_freeze_metadata().is_err());
+    assert!(dip721_set_name("Name".to_string()).is_err());
+    assert!(dip721_set_logo("Logo".to_string()).is_err());
+    assert!(dip721_set_symbol("SYM".to_string()).is_err());
+    assert!(dip721_set_locked(Nat::from(1u32), true).is_err());
+    assert!(!dip721_is_paused() && !dip721_is_metadata_frozen());
+    assert!(!dip721_is_locked(Nat::from(1u32)));
+
+    as_custodian();
+    dip721_set_locked(Nat::from(1u32), true).unwrap();
+    dip721_set_name("Name".to_string()).unwrap();
+    dip721_set_paused(true).unwrap();
+    assert!(dip721_is_paused());
+}
+
+#[test]
+fn owners_change_the_status_of_their_own_tokens() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    let status = |action| dip721_change_status(Nat::from(1u32), action);
+
+    ledger::set_caller(pid(2));
+    assert!(matches!(
+        status(TokenAction::Stake),
+        Err(NftError::UnauthorizedOperator)
+    ));
+    ledger::set_caller(pid(1));
+    assert!(status(TokenAction::Lock).is_err());
+    assert_eq!(status(TokenAction::Stake).unwrap(), TokenStatus::Staked);
+    assert_eq!(status(TokenAction::Unstake).unwrap(), TokenStatus::Active);
+
+    as_custodian();
+    assert_eq!(status(TokenAction::Lock).unwrap(), TokenStatus::Locked);
+}
+
+#[test]
+fn batch_mints_check_the_caller() {
+    ledger::set_caller(pid(1));
+    assert!(dip721_mint_batch(items(aid(1), &[1])).is_err());
+    as_custodian();
+    dip721_set_mint_policy(MintPolicy {
+        phase: MintPhase::Public,
+        ..MintPolicy::default()
+    })
+    .unwrap();
+    ledger::set_caller(pid(1));
+    assert!(dip721_mint_batch(items(aid(2), &[1])).is_err());
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    assert_eq!(log_length(), 1);
+}
+
+#[test]
+fn approvals_for_all_are_logged_and_reported() {
+    as_custodian();
+    let (alice, bob) = (
+        Principal::from_slice(&[1; 10]),
+        Principal::from_slice(&[2; 10]),
+    );
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    ledger::set_caller(alice);
+
+    dip721_set_approval_for_all(bob, true).unwrap();
+    assert!(dip721_is_approved_for_all(alice, bob).unwrap());
+    assert!(!dip721_is_approved_for_all(bob, alice).unwrap());
+    let event = dip721_transaction(Nat::from(log_length() - 1)).unwrap();
+    assert_eq!(event.caller, aid(1));
+
+    dip721_set_approval_for_all(bob, false).unwrap();
+    assert!(!dip721_is_approved_for_all(alice, bob).unwrap());
+    assert_eq!(dip721_total_transactions(), Nat::from(log_length()));
+    assert!(matches!(
+        dip721_transaction(Nat::from(log_length())),
+        Err(NftError::TxNotFound)
+    ));
+}
+
+#[test]
+fn tokens_move_to_bare_account_identifiers() {
+    as_custodian();
+    let (alice, bob, carol) = (aid(1), aid(2), aid(3));
+    dip721_mint_batch(items(aid(1), &[1, 2])).unwrap();
+    ledger::set_caller(pid(1));
+
+    dip721_transfer_from(alice, bob, Nat::from(1u32), None).unwrap();
+    let request = TransferRequest {
+        amount: Nat::from(1u32),
+        from: User::principal(pid(1)),
+        memo: Vec::new(),
+        notify: false,
+        subaccount: None,
+        to: User::address(carol.to_hex()),
+        token: TokenRef::from(2).to_token_identifier(CanisterId(ledger::canister_id())),
+    };
+    assert!(matches!(
+        ext_transfer(request, None),
+        TransferResponse::ok(_)
+    ));
+    assert_eq!(owners(&[1, 2]), [Some(bob), Some(carol)]);
+    ledger::with(|ledger| {
+        assert_eq!(ledger.account_of(&bob), None);
+        assert_eq!(ledger.account_of(&carol), None);
+    });
+}
+
+#[test]
+fn only_the_owner_or_an_approved_caller_transfers() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+
+    ledger::set_caller(pid(2));
+    assert!(matches!(
+        dip721_transfer_from(aid(1), aid(2), Nat::from(1u32), None),
+        Err(NftError::UnauthorizedOperator)
+    ));
+    ledger::set_caller(pid(1));
+    dip721_transfer_from(aid(1), aid(2), Nat::from(1u32), None).unwrap();
+    assert_eq!(dip721_owner_of(Nat::from(1u32)).unwrap(), Some(aid(2)));
+}
+
+#[test]
+fn approved_operators_transfer_on_the_owners_behalf() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1, 2, 3])).unwrap();
+    ledger::set_caller(pid(1));
+    dip721_approve(aid(2), Nat::from(1u32)).unwrap();
+    dip721_set_approval_for_all(pid(3), true).unwrap();
+
+    ledger::set_caller(pid(2));
+    assert!(dip721_transfer_from(aid(1), aid(4), Nat::from(2u32), None).is_err());
+    dip721_transfer_from(aid(1), aid(4), Nat::from(1u32), None).unwrap();
+    ledger::set_caller(pid(3));
+    dip721_transfer_from(aid(1), aid(4), Nat::from(2u32), None).unwrap();
+    dip721_transfer_batch(aid(1), vec![(aid(4), Nat::from(3u32))]).unwrap();
+    assert_eq!(owners(&[1, 2, 3]), [Some(aid(4)); 3]);
+}
+
+#[test]
+fn ext_allowances_agree_with_transfers() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    ledger::set_caller(pid(1));
+    dip721_set_approval_for_all(pid(2), true).unwrap();
+
+    let allowance = |spender: u8| {
+        ext_allowance(AllowanceRequest {
+            owner: User::principal(pid(1)),
+            spender: pid(spender),
+            token: TokenRef::from(1).to_token_identifier(CanisterId(ledger::canister_id())),
+        })
+    };
+    assert!(matches!(allowance(2), Result_2::ok(n) if n == 1u32));
+    assert!(matches!(allowance(3), Result_2::ok(n) if n == 0u32));
+    ledger::set_caller(pid(2));
+    dip721_transfer_from(aid(1), aid(3), Nat::from(1u32), None).unwrap();
+    assert!(matches!(allowance(2), Result_2::err(_)));
+}
+
+#[test]
+fn status_changes_are_logged_and_hooked() {
+    as_custodian();
+    dip721_mint_batch(items(aid(1), &[1])).unwrap();
+    ledger::set_caller(pid(1));
+
+    dip721_change_status(Nat::from(1u32), TokenAction::Stake).unwrap();
+    let event = dip721_transaction(Nat::from(log_length() - 1)).unwrap();
+    assert_eq!(event.operation, "dip721status");
+    assert_eq!(event.caller, aid(1));
+
+    register_hooks(VetoStatus(TokenAction::Unstake));
+    assert!(dip721_change_status(Nat::from(1u32), TokenAction::Unstake).is_err());
+    clear_hooks();
+    assert_eq!(log_length(), 2);
+    assert_eq!(
+        dip721_status_of(Nat::from(1u32)).unwrap(),
+        TokenStatus::Staked
+    );
+}
@@ -0,0 +1,41 @@
+use ext_based_dip721_lib::{CommonError, NftError, ShikuNftError, TransferResponseDetails};
+
+const AID: &str = "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79";
+
+#[test]
+fn ext_errors_keep_their_variant() {
+    let err = ShikuNftError::Unauthorized(AID.to_string());
+    assert!(matches!(
+        TransferResponseDetails::from(err.clone()),
+        TransferResponseDetails::Unauthorized(aid) if aid == AID
+    ));
+    assert_eq!(
+        ShikuNftError::from(TransferResponseDetails::from(err.clone())),
+        err
+    );
+
+    let err = ShikuNftError::CannotNotify(AID.to_string());
+    assert!(matches!(
+        TransferResponseDetails::from(err),
+        TransferResponseDetails::CannotNotify(aid) if aid == AID
+    ));
+
+    let err = ShikuNftError::InvalidToken("token".to_string());
+    assert!(matches!(CommonError::from(err), CommonError::InvalidToken(token) if token == "token"));
+}
+
+#[test]
+fn token_not_found_is_not_an_invalid_token() {
+    assert!(matches!(
+        CommonError::from(ShikuNftError::TokenNotFound),
+        CommonError::Other(msg) if msg == "token not found"
+    ));
+    assert!(matches!(
+        TransferResponseDetails::from(ShikuNftError::TokenNotFound),
+        TransferResponseDetails::Other(msg) if msg == "token not found"
+    ));
+    assert!(matches!(
+        NftError::from(ShikuNftError::TokenNotFound),
+        NftError::TokenNotFound
+    ));
+}
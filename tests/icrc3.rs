@@ -0,0 +1,186 @@
+use candid::{Int, Nat, Principal};
+use ext_based_dip721_lib::*;
+
+fn account(byte: u8) -> Account {
+    Account {
+        owner: Principal::from_slice(&[byte; 10]),
+        subaccount: None,
+    }
+}
+
+fn aid(account: Account) -> AccountIdentifier_shiku {
+    AccountIdentifier_shiku::from(account)
+}
+
+fn token_metadata(token_id: u32, owner: AccountIdentifier_shiku) -> TokenMetadata {
+    TokenMetadata {
+        token_identifier: Nat::from(token_id),
+        owner: Some(owner),
+        operator: Some(owner),
+        is_burned: false,
+        properties: None,
+        minted_at: 0,
+        minted_by: owner,
+        transferred_at: None,
+        transferred_by: None,
+        approved_at: None,
+        approved_by: None,
+        burned_at: None,
+        burned_by: None,
+        status: TokenStatus::Active,
+        soulbound: false,
+    }
+}
+
+fn field<'a>(block: &'a Value, key: &str) -> Option<&'a Value> {
+    match block {
+        Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn btype(block: &Value) -> &str {
+    match field(block, "btype") {
+        Some(Value::Text(btype)) => btype,
+        other => panic!("block without btype: {:?}", other),
+    }
+}
+
+fn hex(hash: [u8; 32]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn values_hash_as_specified() {
+    assert_eq!(
+        hex(hash_value(&Value::Nat(Nat::from(42u32)))),
+        "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1"
+    );
+    assert_eq!(
+        hex(hash_value(&Value::Int(Int::from(-42)))),
+        "de5a6f78116eca62d7fc5ce159d23ae6b889b365a1739ad2cf36f925a140d0cc"
+    );
+    assert_eq!(
+        hex(hash_value(&Value::Text("Hello, World!".to_string()))),
+        "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+    );
+    assert_eq!(
+        hex(hash_value(&Value::Array(vec![
+            Value::Nat(Nat::from(3u32)),
+            Value::Text("foo".to_string()),
+            Value::Blob(b"hi".to_vec()),
+        ]))),
+        "a744c2d359bbb43a1a8b62b4ea84d2ddb630b17cde9a2ea8cfeca13533485e9e"
+    );
+}
+
+#[test]
+fn map_hashes_ignore_entry_order() {
+    let a = ("a".to_string(), Value::Nat(Nat::from(1u32)));
+    let b = ("b".to_string(), Value::Text("two".to_string()));
+    assert_eq!(
+        hash_value(&Value::Map(vec![a.clone(), b.clone()])),
+        hash_value(&Value::Map(vec![b, a]))
+    );
+}
+
+#[test]
+fn every_ledger_change_is_chained() {
+    let (alice, bob) = (account(1), account(2));
+    let token = Nat::from(1u32);
+    let mut ledger = Ledger::default();
+    ledger.link_account(alice);
+    ledger.link_account(bob);
+
+    ledger.mint(&token, token_metadata(1, aid(alice))).unwrap();
+    ledger.approve(aid(alice), &token, Some(aid(bob))).unwrap();
+    ledger.transfer(aid(bob), &token, Some(aid(bob))).unwrap();
+    ledger.burn(aid(bob), &token).unwrap();
+
+    let btypes: Vec<&str> = ledger.blocks.iter().map(btype).collect();
+    assert_eq!(btypes, ["7mint", "37approve", "37xfer", "7burn"]);
+    assert!(field(&ledger.blocks[0], "phash").is_none());
+    for pair in ledger.blocks.windows(2) {
+        assert_eq!(
+            field(&pair[1], "phash"),
+            Some(&Value::Blob(hash_value(&pair[0]).to_vec()))
+        );
+    }
+
+    let tx = field(&ledger.blocks[2], "tx").unwrap();
+    let alice_value = Value::Array(vec![Value::Blob(alice.owner.as_slice().to_vec())]);
+    assert_eq!(field(tx, "from"), Some(&alice_value));
+    assert_eq!(field(tx, "tid"), Some(&Value::Nat(token)));
+
+    let (index, hash) = ledger.tip().unwrap();
+    assert_eq!(index, Nat::from(3u32));
+    assert_eq!(hash, hash_value(&ledger.blocks[3]));
+}
+
+#[test]
+fn get_blocks_serves_ranges_of_the_log() {
+    let minter = aid(account(1));
+    ledger::with_mut(|ledger| {
+        ledger.link_account(account(1));
+        for token_id in 0..5 {
+            ledger
+                .mint(&Nat::from(token_id), token_metadata(token_id, minter))
+                .unwrap();
+        }
+    });
+
+    let range = |start: u32, length: u32| GetBlocksArgs {
+        start: Nat::from(start),
+        length: Nat::from(length),
+    };
+    let result = icrc3_get_blocks(vec![range(0, 2), range(3, 10), range(9, 1)]);
+    assert_eq!(result.log_length, Nat::from(5u32));
+    assert!(result.archived_blocks.is_empty());
+    let ids: Vec<Nat> = result.blocks.iter().map(|b| b.id.clone()).collect();
+    assert_eq!(
+        ids,
+        [0u32, 1, 3, 4]
+            .iter()
+            .map(|id| Nat::from(*id))
+            .collect::<Vec<_>>()
+    );
+    assert!(icrc3_get_archives(GetArchivesArgs { from: None }).is_empty());
+}
+
+#[test]
+fn collection_approvals_are_logged_at_the_index_they_return() {
+    let (alice, bob) = (account(1), account(2));
+    ledger::set_caller(alice.owner);
+    ledger::with_mut(|ledger| {
+        ledger.link_account(alice);
+        ledger
+            .mint(&Nat::from(1u32), token_metadata(1, aid(alice)))
+            .unwrap();
+    });
+
+    let approval_info = ApprovalInfo {
+        spender: bob,
+        from_subaccount: None,
+        expires_at: None,
+        memo: None,
+        created_at_time: ledger::now(),
+    };
+    let approved = icrc37_approve_collection(vec![ApproveCollectionArg { approval_info }]);
+    assert_eq!(approved, [Some(Ok(Nat::from(1u32)))]);
+    let revoked = icrc37_revoke_collection_approvals(vec![RevokeCollectionApprovalArg {
+        spender: None,
+        from_subaccount: None,
+        memo: None,
+        created_at_time: None,
+    }]);
+    assert_eq!(revoked, [Some(Ok(Nat::from(2u32)))]);
+
+    let blocks = ledger::with(|ledger| ledger.blocks.clone());
+    let btypes: Vec<&str> = blocks.iter().map(btype).collect();
+    assert_eq!(btypes, ["7mint", "37approve_coll", "37revoke_coll"]);
+    let tx = field(&blocks[1], "tx").unwrap();
+    let bob_value = Value::Array(vec![Value::Blob(bob.owner.as_slice().to_vec())]);
+    assert_eq!(field(tx, "spender"), Some(&bob_value));
+    assert!(field(tx, "tid").is_none());
+    assert_eq!(icrc3_supported_block_types().len(), 9);
+}
@@ -0,0 +1,236 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::*;
+
+fn account(byte: u8) -> Account {
+    Account {
+        owner: Principal::from_slice(&[byte; 10]),
+        subaccount: None,
+    }
+}
+
+fn aid(account: Account) -> AccountIdentifier_shiku {
+    AccountIdentifier_shiku::from(account)
+}
+
+fn approval(spender: Account, expires_at: Option<u64>) -> ApprovalInfo {
+    ApprovalInfo {
+        spender,
+        from_subaccount: None,
+        expires_at,
+        memo: None,
+        created_at_time: 0,
+    }
+}
+
+fn ledger_with_token(owner: Account) -> Ledger {
+    let mut ledger = Ledger::default();
+    let owner = ledger.link_account(owner);
+    let token_metadata = TokenMetadata {
+        token_identifier: Nat::from(1u32),
+        owner: Some(owner),
+        operator: Some(owner),
+        is_burned: false,
+        properties: None,
+        minted_at: 0,
+        minted_by: owner,
+        transferred_at: None,
+        transferred_by: None,
+        approved_at: None,
+        approved_by: None,
+        burned_at: None,
+        burned_by: None,
+        status: TokenStatus::Active,
+        soulbound: false,
+    };
+    ledger.mint(&Nat::from(1u32), token_metadata).unwrap();
+    ledger
+}
+
+#[test]
+fn token_approvals_expire() {
+    let (owner, alice, bob) = (account(1), account(2), account(3));
+    let token = Nat::from(1u32);
+    let mut ledger = ledger_with_token(owner);
+
+    ledger
+        .approve_token(aid(owner), &token, approval(alice, Some(100)), 0)
+        .unwrap();
+    ledger
+        .approve_token(aid(owner), &token, approval(bob, None), 0)
+        .unwrap();
+
+    assert!(ledger.is_approved(&token, &aid(alice), 99));
+    assert!(!ledger.is_approved(&token, &aid(alice), 100));
+    assert!(ledger.is_approved(&token, &aid(bob), 100));
+    assert!(!ledger.is_approved(&token, &aid(owner), 0));
+    assert_eq!(ledger.token_approvals(&token, 0).count(), 2);
+    assert_eq!(ledger.token_approvals(&token, 100).count(), 1);
+}
+
+#[test]
+fn only_the_owner_approves_and_never_itself() {
+    let (owner, alice) = (account(1), account(2));
+    let token = Nat::from(1u32);
+    let mut ledger = ledger_with_token(owner);
+
+    assert_eq!(
+        ledger.approve_token(aid(alice), &token, approval(alice, None), 0),
+        Err(ShikuNftError::UnauthorizedOwner)
+    );
+    assert_eq!(
+        ledger.approve_token(aid(owner), &token, approval(owner, None), 0),
+        Err(ShikuNftError::SelfApprove)
+    );
+    assert_eq!(
+        ledger.approve_token(aid(owner), &Nat::from(2u32), approval(alice, None), 0),
+        Err(ShikuNftError::TokenNotFound)
+    );
+}
+
+#[test]
+fn approvals_are_capped_and_replaced_per_spender() {
+    let owner = account(1);
+    let token = Nat::from(1u32);
+    let mut ledger = ledger_with_token(owner);
+
+    for spender in 0..MAX_APPROVALS_PER_TOKEN_OR_COLLECTION as u8 {
+        let spender = account(10 + spender);
+        ledger
+            .approve_token(aid(owner), &token, approval(spender, None), 0)
+            .unwrap();
+    }
+    ledger
+        .approve_token(aid(owner), &token, approval(account(10), Some(5)), 0)
+        .unwrap();
+    assert!(ledger
+        .approve_token(aid(owner), &token, approval(account(2), None), 0)
+        .is_err());
+
+    // Expired approvals free their slot.
+    ledger
+        .approve_token(aid(owner), &token, approval(account(2), None), 5)
+        .unwrap();
+    assert!(!ledger.is_approved(&token, &aid(account(10)), 5));
+}
+
+#[test]
+fn revoking_token_approvals() {
+    let (owner, alice, bob) = (account(1), account(2), account(3));
+    let token = Nat::from(1u32);
+    let mut ledger = ledger_with_token(owner);
+    ledger
+        .approve_token(aid(owner), &token, approval(alice, None), 0)
+        .unwrap();
+    ledger
+        .approve_token(aid(owner), &token, approval(bob, None), 0)
+        .unwrap();
+
+    assert_eq!(
+        ledger.revoke_token_approvals(aid(owner), &token, Some(aid(account(4))), 0),
+        Err(ShikuNftError::ApprovalNotFound)
+    );
+    ledger
+        .revoke_token_approvals(aid(owner), &token, Some(aid(alice)), 0)
+        .unwrap();
+    assert!(!ledger.is_approved(&token, &aid(alice), 0));
+    assert!(ledger.is_approved(&token, &aid(bob), 0));
+
+    ledger
+        .revoke_token_approvals(aid(owner), &token, None, 0)
+        .unwrap();
+    assert!(!ledger.is_approved(&token, &aid(bob), 0));
+    assert_eq!(
+        ledger.revoke_token_approvals(aid(owner), &token, None, 0),
+        Err(ShikuNftError::ApprovalNotFound)
+    );
+}
+
+#[test]
+fn collection_approvals_cover_every_token_of_the_owner() {
+    let (owner, alice) = (account(1), account(2));
+    let token = Nat::from(1u32);
+    let mut ledger = ledger_with_token(owner);
+
+    ledger
+        .approve_collection(aid(owner), approval(alice, Some(10)), 0)
+        .unwrap();
+    assert!(ledger.is_approved(&token, &aid(alice), 0));
+    assert!(!ledger.is_approved(&token, &aid(alice), 10));
+    assert_eq!(ledger.collection_approvals(&aid(owner), 0).count(), 1);
+
+    ledger
+        .revoke_collection_approvals(aid(owner), Some(aid(alice)), 0)
+        .unwrap();
+    assert!(!ledger.is_approved(&token, &aid(alice), 0));
+    assert_eq!(
+        ledger.revoke_collection_approvals(aid(owner), None, 0),
+        Err(ShikuNftError::ApprovalNotFound)
+    );
+}
+
+#[test]
+fn dip721_operators_count_as_approved() {
+    let (owner, alice) = (account(1), account(2));
+    let token = Nat::from(1u32);
+    let mut ledger = ledger_with_token(owner);
+    ledger.tokens.get_mut(&token).unwrap().operator = Some(aid(alice));
+
+    assert!(ledger.is_approved(&token, &aid(alice), 0));
+}
+
+struct VetoCollectionApprovals;
+
+impl LedgerHooks for VetoCollectionApprovals {
+    fn before_approve_collection(
+        &self,
+        _ledger: &Ledger,
+        _approved_by: &AccountIdentifier_shiku,
+        _operator: Option<&AccountIdentifier_shiku>,
+    ) -> Result<(), ShikuNftError> {
+        Err(ShikuNftError::Rejected)
+    }
+}
+
+#[test]
+fn hooks_can_veto_collection_approvals() {
+    let (owner, alice) = (account(1), account(2));
+    let mut ledger = ledger_with_token(owner);
+    ledger
+        .approve_collection(aid(owner), approval(alice, None), 0)
+        .unwrap();
+    register_hooks(VetoCollectionApprovals);
+
+    assert_eq!(
+        ledger.approve_collection(aid(owner), approval(account(3), None), 0),
+        Err(ShikuNftError::Rejected)
+    );
+    assert_eq!(
+        ledger.revoke_collection_approvals(aid(owner), None, 0),
+        Err(ShikuNftError::Rejected)
+    );
+    assert!(ledger.is_approved(&Nat::from(1u32), &aid(alice), 0));
+    clear_hooks();
+}
+
+#[test]
+fn is_approved_looks_at_the_given_subaccount() {
+    let (owner, alice) = (account(1), account(2));
+    ledger::with_mut(|ledger| {
+        *ledger = ledger_with_token(owner);
+        ledger
+            .approve_collection(aid(owner), approval(alice, None), 0)
+            .unwrap();
+    });
+    let arg = |from_subaccount| IsApprovedArg {
+        spender: alice,
+        from_subaccount,
+        token_id: Nat::from(1u32),
+    };
+    assert_eq!(
+        icrc37_is_approved(vec![
+            arg(None),
+            arg(Some(ic_ledger_types::Subaccount([7; 32])))
+        ]),
+        [true, false]
+    );
+}
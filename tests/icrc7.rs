@@ -0,0 +1,119 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::*;
+
+fn account(byte: u8) -> Account {
+    Account {
+        owner: Principal::from_slice(&[byte; 10]),
+        subaccount: None,
+    }
+}
+
+fn mint(token_id: u32, to: Account) {
+    let owner = AccountIdentifier_shiku::from(to);
+    let token_metadata = TokenMetadata {
+        token_identifier: Nat::from(token_id),
+        owner: Some(owner),
+        operator: Some(owner),
+        is_burned: false,
+        properties: None,
+        minted_at: 0,
+        minted_by: owner,
+        transferred_at: None,
+        transferred_by: None,
+        approved_at: None,
+        approved_by: None,
+        burned_at: None,
+        burned_by: None,
+        status: TokenStatus::Active,
+        soulbound: false,
+    };
+    ledger::with_mut(|ledger| {
+        ledger.link_account(to);
+        ledger.mint(&Nat::from(token_id), token_metadata).unwrap();
+    });
+}
+
+fn nats(ids: &[u32]) -> Vec<Nat> {
+    ids.iter().map(|id| Nat::from(*id)).collect()
+}
+
+#[test]
+fn queries_read_the_shared_ledger() {
+    let (alice, bob) = (account(1), account(2));
+    for token_id in [5, 1, 3] {
+        mint(token_id, alice);
+    }
+    mint(2, bob);
+
+    assert_eq!(icrc7_total_supply(), Nat::from(4u32));
+    assert_eq!(
+        icrc7_balance_of(vec![alice, bob, account(3)]),
+        nats(&[3, 1, 0])
+    );
+    assert_eq!(
+        icrc7_owner_of(nats(&[1, 2, 4])),
+        vec![Some(alice), Some(bob), None]
+    );
+    assert_eq!(dip721_owner_of(Nat::from(2u32)).unwrap(), Some(bob.into()));
+
+    assert_eq!(icrc7_tokens(None, None), nats(&[1, 2, 3, 5]));
+    assert_eq!(
+        icrc7_tokens(Some(Nat::from(1u32)), Some(Nat::from(2u32))),
+        nats(&[2, 3])
+    );
+    assert_eq!(
+        icrc7_tokens_of(alice, Some(Nat::from(1u32)), None),
+        nats(&[3, 5])
+    );
+    assert_eq!(icrc7_tokens_of(account(3), None, None), nats(&[]));
+
+    let metadata = icrc7_token_metadata(nats(&[1, 4]));
+    assert!(metadata[0].is_some());
+    assert!(metadata[1].is_none());
+}
+
+#[test]
+fn supported_standards_include_icrc7() {
+    let names: Vec<String> = icrc10_supported_standards()
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert_eq!(names, ["ICRC-7", "ICRC-37", "ICRC-3", "ICRC-10"]);
+}
+
+#[test]
+fn owners_without_a_known_account_are_none() {
+    let (alice, stranger) = (account(1), account(9));
+    mint(1, alice);
+    assert_eq!(icrc7_owner_of(nats(&[1, 2])), [Some(alice), None]);
+
+    let (from, unknown) = (
+        AccountIdentifier_shiku::from(alice),
+        AccountIdentifier_shiku::from(stranger),
+    );
+    ledger::with_mut(|ledger| ledger.transfer(from, &Nat::from(1u32), Some(unknown))).unwrap();
+    assert_eq!(icrc7_owner_of(nats(&[1])), [None]);
+    assert_eq!(icrc7_balance_of(vec![stranger]), [Nat::from(1u32)]);
+}
+
+#[test]
+fn failed_transfers_link_no_account() {
+    let (alice, stranger) = (account(1), account(9));
+    mint(1, alice);
+    ledger::set_caller(alice.owner);
+    ledger::with_mut(|ledger| ledger.paused = true);
+
+    let arg = Icrc7TransferArg {
+        from_subaccount: None,
+        to: stranger,
+        token_id: Nat::from(1u32),
+        memo: None,
+        created_at_time: None,
+    };
+    assert!(matches!(
+        icrc7_transfer(vec![arg]).as_slice(),
+        [Some(Err(_))]
+    ));
+    let unknown = AccountIdentifier_shiku::from(stranger);
+    assert_eq!(ledger::with(|ledger| ledger.account_of(&unknown)), None);
+}
@@ -0,0 +1,187 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::stable::{self, LedgerV0, TokenMetadataV0};
+use ext_based_dip721_lib::*;
+use std::collections::{HashMap, HashSet};
+
+fn account(byte: u8) -> Account {
+    Account {
+        owner: Principal::from_slice(&[byte; 10]),
+        subaccount: None,
+    }
+}
+
+fn token_v0(token_id: u32, owner: AccountIdentifier_shiku, is_burned: bool) -> TokenMetadataV0 {
+    TokenMetadataV0 {
+        token_identifier: Nat::from(token_id),
+        owner: (!is_burned).then_some(owner),
+        operator: (!is_burned).then_some(owner),
+        is_burned,
+        properties: None,
+        minted_at: 1,
+        minted_by: owner,
+        transferred_at: None,
+        transferred_by: None,
+        approved_at: None,
+        approved_by: None,
+        burned_at: is_burned.then_some(2),
+        burned_by: is_burned.then_some(owner),
+        status: 1,
+    }
+}
+
+/// Stable memory holds whole pages, so the state is followed by zeros.
+fn page(mut bytes: Vec<u8>) -> Vec<u8> {
+    bytes.resize(bytes.len() + 64, 0);
+    bytes
+}
+
+#[test]
+fn the_current_layout_round_trips() {
+    let alice = account(1);
+    let minter = Principal::from_slice(&[9; 10]);
+    let mut ledger = Ledger::default();
+    let owner = ledger.link_account(alice);
+    ledger.soulbound = true;
+    ledger.mint_policy.max_supply = Some(10);
+    ledger
+        .mint(
+            &Nat::from(1u32),
+            TokenMetadata {
+                token_identifier: Nat::from(1u32),
+                owner: Some(owner),
+                operator: Some(owner),
+                is_burned: false,
+                properties: None,
+                minted_at: 0,
+                minted_by: owner,
+                transferred_at: None,
+                transferred_by: None,
+                approved_at: None,
+                approved_by: None,
+                burned_at: None,
+                burned_by: None,
+                status: TokenStatus::Staked,
+                soulbound: true,
+            },
+        )
+        .unwrap();
+
+    let (mint_policy, mints_per_account, blocks, certified) = (
+        ledger.mint_policy.clone(),
+        ledger.mints_per_account.clone(),
+        ledger.blocks.clone(),
+        CertifiedState::of(&ledger),
+    );
+
+    let bytes = stable::encode(ledger, 3, &[Nat::from(1u32)], minter).unwrap();
+    let (restored, tid, minted, restored_minter) = stable::decode(&page(bytes)).unwrap();
+    assert_eq!(
+        (tid, minted, restored_minter),
+        (3, vec![Nat::from(1u32)], minter)
+    );
+    assert!(restored.soulbound);
+    assert_eq!(restored.mint_policy, mint_policy);
+    assert_eq!(restored.mints_per_account, mints_per_account);
+    assert_eq!(restored.blocks, blocks);
+    assert_eq!(restored.certified, certified);
+    assert_eq!(restored.accounts.get(&owner), Some(&alice));
+    let token = &restored.tokens[&Nat::from(1u32)];
+    assert_eq!((token.status, token.soulbound), (TokenStatus::Staked, true));
+}
+
+#[test]
+fn unversioned_ledgers_are_migrated() {
+    let (alice, bob) = (
+        AccountIdentifier_shiku::from(account(1)),
+        AccountIdentifier_shiku::from(account(2)),
+    );
+    let minter = Principal::from_slice(&[9; 10]);
+    let tokens = [(1, alice, false), (2, alice, true), (3, bob, false)];
+    let saved = LedgerV0 {
+        metadata: MetaData {
+            name: Some("Old".to_string()),
+            ..MetaData::default()
+        },
+        tokens: tokens
+            .iter()
+            .map(|&(id, owner, is_burned)| (Nat::from(id), token_v0(id, owner, is_burned)))
+            .collect(),
+        owners: HashMap::from([
+            (alice, HashSet::from([Nat::from(1u32)])),
+            (bob, HashSet::from([Nat::from(3u32)])),
+        ]),
+        operators: HashMap::new(),
+        custodians: HashSet::new(),
+        idx2aid: HashMap::from([(1, "a".to_string())]),
+        tx_count: Nat::from(4u32),
+    };
+    let minted = vec![Nat::from(1u32), Nat::from(2u32), Nat::from(3u32)];
+    let bytes = candid::encode_args((saved, 3u32, minted.clone(), minter)).unwrap();
+
+    let (ledger, tid, restored_minted, restored_minter) = stable::decode(&page(bytes)).unwrap();
+    assert_eq!((tid, restored_minted, restored_minter), (3, minted, minter));
+    assert_eq!(ledger.metadata().name.as_deref(), Some("Old"));
+    assert_eq!(ledger.tx_count, Nat::from(4u32));
+    assert_eq!(ledger.owner_of(&Nat::from(3u32)), Ok(Some(bob)));
+    assert_eq!(ledger.status_of(&Nat::from(1u32)), Ok(TokenStatus::Active));
+    assert_eq!(ledger.status_of(&Nat::from(2u32)), Ok(TokenStatus::Burned));
+    assert_eq!(ledger.mints_per_account[&alice], 2);
+    assert_eq!(ledger.mints_per_account[&bob], 1);
+    assert_eq!(ledger.get_registry()[&1], "a");
+    assert!(!ledger.soulbound && ledger.blocks.is_empty());
+}
+
+#[test]
+fn migrated_logs_continue_after_the_counted_transactions() {
+    let saved = LedgerV0 {
+        metadata: MetaData::default(),
+        tokens: HashMap::new(),
+        owners: HashMap::new(),
+        operators: HashMap::new(),
+        custodians: HashSet::new(),
+        idx2aid: HashMap::new(),
+        tx_count: Nat::from(4u32),
+    };
+    let bytes = candid::encode_args((saved, 0u32, Vec::<Nat>::new(), Principal::anonymous()));
+    let (migrated, ..) = stable::decode(&page(bytes.unwrap())).unwrap();
+    assert_eq!(migrated.log_start, 4);
+
+    let alice = AccountIdentifier_shiku::from(account(1));
+    let index = ledger::with_mut(|ledger| {
+        *ledger = migrated;
+        ledger
+            .mint(&Nat::from(1u32), token_v0(1, alice, false).into())
+            .unwrap();
+        ledger.tx_index()
+    });
+    assert_eq!(index, Nat::from(4u32));
+    assert_eq!(dip721_total_transactions(), Nat::from(5u32));
+    assert!(dip721_transaction(Nat::from(4u32)).is_ok());
+    assert!(dip721_transaction(Nat::from(3u32)).is_err());
+    assert_eq!(
+        dip721_history(None, None)
+            .into_iter()
+            .map(|block| block.id)
+            .collect::<Vec<_>>(),
+        [Nat::from(4u32)]
+    );
+
+    let blocks = icrc3_get_blocks(vec![GetBlocksArgs {
+        start: Nat::from(0u32),
+        length: Nat::from(10u32),
+    }]);
+    assert_eq!(blocks.log_length, Nat::from(5u32));
+    assert_eq!(blocks.blocks.len(), 1);
+    assert_eq!(blocks.blocks[0].id, Nat::from(4u32));
+    assert_eq!(
+        ledger::with(|ledger| ledger.tip()).map(|(index, _)| index),
+        Some(Nat::from(4u32))
+    );
+}
+
+#[test]
+fn anything_else_is_refused() {
+    assert!(stable::decode(&[]).is_err());
+    let bytes = candid::encode_args((Nat::from(1u32), 3u32)).unwrap();
+    assert!(stable::decode(&page(bytes)).is_err());
+}
@@ -0,0 +1,123 @@
+use candid::{Nat, Principal};
+use ext_based_dip721_lib::{
+    decode_token_id, encode_token_id, get_canister_token_index, get_token_index, is_valid_token_id,
+    CanisterId, CommonError, TokenIndex, TokenRef,
+};
+use proptest::prelude::*;
+
+fn canister() -> impl Strategy<Value = Principal> {
+    prop::collection::vec(any::<u8>(), 0..=21).prop_map(|bytes| Principal::from_slice(&bytes))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn token_ids_round_trip(pid in canister(), index in any::<u32>()) {
+        let tid = encode_token_id(CanisterId(pid), TokenIndex(index));
+        let tobj = decode_token_id(&tid).unwrap();
+        prop_assert_eq!(tobj.index, TokenIndex(index));
+        prop_assert_eq!(tobj.canister, pid.as_slice().to_vec());
+        prop_assert_eq!(get_token_index(&tid).unwrap(), TokenIndex(index));
+        prop_assert!(is_valid_token_id(&tid, &CanisterId(pid)));
+        prop_assert_eq!(get_canister_token_index(&tid, &CanisterId(pid)).unwrap(), TokenIndex(index));
+    }
+
+    #[test]
+    fn tokens_of_other_canisters_are_rejected(
+        pid in canister(),
+        other in canister(),
+        index in any::<u32>(),
+    ) {
+        prop_assume!(pid != other);
+        let tid = encode_token_id(CanisterId(pid), TokenIndex(index));
+        prop_assert!(!is_valid_token_id(&tid, &CanisterId(other)));
+        let rejected = get_canister_token_index(&tid, &CanisterId(other));
+        prop_assert!(matches!(rejected, Err(CommonError::InvalidToken(t)) if t == tid));
+    }
+
+    #[test]
+    fn arbitrary_text_does_not_panic(tid in ".*") {
+        let _ = decode_token_id(&tid);
+        let _ = get_token_index(&tid);
+    }
+
+    #[test]
+    fn arbitrary_principals_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..=29)) {
+        let mut bytes = bytes;
+        if bytes.len() >= 4 {
+            bytes[..4].copy_from_slice(b"\x0Atid");
+        }
+        let tid = Principal::from_slice(&bytes).to_text();
+        let _ = decode_token_id(&tid);
+    }
+}
+
+#[test]
+fn token_identifier_layout() {
+    let canister = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+    let tid = encode_token_id(CanisterId(canister), TokenIndex(0x0102_0304));
+    let mut expected = b"\x0Atid".to_vec();
+    expected.extend_from_slice(canister.as_slice());
+    expected.extend_from_slice(&[1, 2, 3, 4]);
+    assert_eq!(
+        Principal::from_text(&tid).unwrap().as_slice(),
+        expected.as_slice()
+    );
+}
+
+#[test]
+fn malformed_token_identifiers_are_errors() {
+    let short = Principal::from_slice(b"\x0Atid\x01\x02").to_text();
+    assert!(matches!(
+        get_token_index(&short),
+        Err(CommonError::InvalidToken(_))
+    ));
+    assert!(matches!(
+        get_token_index(&"not a principal".to_string()),
+        Err(CommonError::InvalidToken(_))
+    ));
+    let canister = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai")
+        .unwrap()
+        .to_text();
+    assert!(matches!(
+        get_token_index(&canister),
+        Err(CommonError::Other(_))
+    ));
+}
+
+proptest! {
+    #[test]
+    fn token_refs_convert_between_forms(pid in canister(), index in any::<u32>()) {
+        let token = TokenRef::from(index);
+        prop_assert_eq!(TokenRef::from(TokenIndex(index)), token);
+        prop_assert_eq!(TokenRef::try_from(Nat::from(index)).unwrap(), token);
+        prop_assert_eq!(Nat::from(token), Nat::from(index));
+        prop_assert_eq!(u32::from(token), index);
+        prop_assert_eq!(TokenRef::try_from(index.to_string()).unwrap(), token);
+
+        let tid = token.to_token_identifier(CanisterId(pid));
+        prop_assert_eq!(TokenRef::try_from(&tid).unwrap(), token);
+        prop_assert_eq!(TokenRef::from_token_identifier(&tid, &CanisterId(pid)).unwrap(), token);
+    }
+
+    #[test]
+    fn nats_beyond_u32_are_rejected(excess in 1u64..=u32::MAX as u64) {
+        let nat = Nat::from(u32::MAX as u64 + excess);
+        prop_assert!(matches!(TokenRef::try_from(&nat), Err(CommonError::InvalidToken(t)) if t == nat.to_string()));
+        prop_assert!(TokenRef::try_from(nat.to_string()).is_err());
+    }
+}
+
+#[test]
+fn token_ref_rejects_foreign_text() {
+    let canister = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+    let other = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+    let tid = TokenRef::from(7).to_token_identifier(CanisterId(canister));
+    assert!(matches!(
+        TokenRef::from_token_identifier(&tid, &CanisterId(other)),
+        Err(CommonError::InvalidToken(_))
+    ));
+    assert!(TokenRef::try_from("").is_err());
+    assert!(TokenRef::try_from("-1").is_err());
+}